@@ -42,7 +42,6 @@ pub enum SDistError {
 
 impl SDist {
     /// Create this struct from a path
-    #[allow(dead_code)]
     pub fn from_path(
         path: &Path,
         normalized_package_name: &NormalizedPackageName,
@@ -872,7 +871,7 @@ mod tests {
 
         let artifact_info = vec![ArtifactInfo {
             filename: ArtifactName::STree(stree_file_name),
-            url: url,
+            url,
             is_direct_url: true,
             hashes: None,
             requires_python: None,
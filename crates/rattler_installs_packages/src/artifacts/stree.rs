@@ -3,9 +3,9 @@ use crate::types::ArtifactFromSource;
 use crate::types::ReadPyProjectError;
 use crate::types::{HasArtifactName, STreeFilename, SourceArtifactName};
 use fs_err as fs;
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
+use rattler_digest::{digest::Digest, Sha256};
 use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
 
 /// Represents a source tree which can be a simple directory on filesystem
 /// or something cloned from git
@@ -37,6 +37,47 @@ impl STree {
         }
         Ok(())
     }
+
+    /// Adds the path, size and modification time of every file in `dir` to the hasher, so that
+    /// editing, adding or removing a file changes the hash. Directories that only contain VCS
+    /// data, caches or the output of earlier builds are skipped.
+    fn hash_dir(root: &Path, dir: &Path, hasher: &mut Sha256) -> std::io::Result<()> {
+        let mut entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let relative = path.strip_prefix(root).unwrap_or(&path);
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                let is_build_output = dir == root && matches!(name.as_str(), "build" | "dist");
+                if name.starts_with('.')
+                    || name == "__pycache__"
+                    || name.ends_with(".egg-info")
+                    || is_build_output
+                {
+                    continue;
+                }
+                Self::hash_dir(root, &path, hasher)?;
+            } else if file_type.is_symlink() {
+                hasher.update(relative.to_string_lossy().as_bytes());
+                hasher.update(b"\0->");
+                hasher.update(fs::read_link(&path)?.to_string_lossy().as_bytes());
+                hasher.update(b"\0");
+            } else {
+                let metadata = entry.metadata()?;
+                let modified = metadata
+                    .modified()?
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default();
+                hasher.update(relative.to_string_lossy().as_bytes());
+                hasher.update(b"\0");
+                hasher.update(metadata.len().to_le_bytes());
+                hasher.update(modified.as_nanos().to_le_bytes());
+            }
+        }
+        Ok(())
+    }
 }
 
 impl HasArtifactName for STree {
@@ -48,21 +89,15 @@ impl HasArtifactName for STree {
 }
 
 impl ArtifactFromSource for STree {
+    /// Returns a fingerprint of the source tree, derived from its location and the path, size
+    /// and modification time of all its files.
     fn try_get_bytes(&self) -> Result<Vec<u8>, std::io::Error> {
-        let vec = vec![];
-        let inner = self.lock_data();
-        let mut dir_entry = fs::read_dir(inner.as_path())?;
-
-        let next_entry = dir_entry.next();
-        if let Some(Ok(root_folder)) = next_entry {
-            let modified = root_folder.metadata()?.modified()?;
-            let mut hasher = DefaultHasher::new();
-            modified.hash(&mut hasher);
-            let hash = hasher.finish().to_be_bytes().as_slice().to_owned();
-            return Ok(hash);
-        }
-
-        Ok(vec)
+        let root = self.lock_data().clone();
+        let mut hasher = Sha256::new();
+        hasher.update(root.to_string_lossy().as_bytes());
+        hasher.update(b"\0");
+        Self::hash_dir(&root, &root, &mut hasher)?;
+        Ok(hasher.finalize().to_vec())
     }
 
     fn distribution_name(&self) -> String {
@@ -103,4 +138,8 @@ impl ArtifactFromSource for STree {
         let src = self.lock_data();
         Self::copy_dir_all(src.as_path(), work_dir)
     }
+
+    fn source_tree(&self) -> Option<PathBuf> {
+        Some(self.lock_data().clone())
+    }
 }
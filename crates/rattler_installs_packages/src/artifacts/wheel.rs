@@ -18,11 +18,12 @@ use async_zip::base::read::seek::ZipFileReader;
 use configparser::ini::Ini;
 use data_encoding::BASE64URL_NOPAD;
 use fs_err as fs;
+use itertools::Itertools;
 use miette::IntoDiagnostic;
 use parking_lot::Mutex;
 use pep440_rs::Version;
-use rattler_digest::Sha256;
-use std::io::{BufRead, BufReader};
+use rattler_digest::{digest::Digest, Sha256};
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
 use std::sync::mpsc::channel;
 use std::{
     borrow::Cow,
@@ -323,6 +324,13 @@ impl Wheel {
         })
     }
 
+    /// Writes the wheel file to `writer`, e.g. to store a wheel that was built from an sdist.
+    pub fn write_to(self, writer: &mut impl Write) -> std::io::Result<u64> {
+        let mut reader = self.archive.into_inner().into_inner();
+        reader.seek(SeekFrom::Start(0))?;
+        std::io::copy(&mut reader, writer)
+    }
+
     /// Get the metadata from the wheel archive
     pub fn metadata(&self) -> miette::Result<(Vec<u8>, WheelCoreMetadata)> {
         let WheelVitals {
@@ -333,6 +341,192 @@ impl Wheel {
         Ok((metadata_blob, metadata))
     }
 
+    /// Validates the contents of the wheel without installing it.
+    ///
+    /// This checks that every file in the archive is listed in `RECORD` with a matching sha256
+    /// hash and size, that every file listed in `RECORD` is present, that no path escapes the
+    /// installation directory, that the `Wheel-Version` is supported and that the name and
+    /// version of the `.dist-info` directory and `METADATA` match the [`WheelFilename`].
+    ///
+    /// Returns all the issues that were found, an empty list means the wheel is valid. An error
+    /// is returned if the wheel is too broken to be validated at all.
+    pub fn validate(&self) -> Result<Vec<WheelValidationIssue>, WheelVitalsError> {
+        let mut archive = self.archive.lock();
+        let mut issues = Vec::new();
+
+        // Determine the top level filenames in the wheel
+        let top_level_names = archive
+            .file_names()
+            .map(|filename| {
+                filename
+                    .split_once(['/', '\\'])
+                    .map_or_else(|| filename, |(base, _)| base)
+                    .to_owned()
+            })
+            .collect::<HashSet<_>>();
+
+        // Determine the name of the dist-info directory. If it doesn't match the filename we
+        // continue with the only dist-info directory there is to validate the rest of the wheel.
+        let expected_dist_info = format!(
+            "{}-{}.dist-info",
+            self.name.distribution.as_source_str(),
+            self.name.version
+        );
+        let dist_info = match Wheel::find_special_wheel_dir(
+            top_level_names.iter().map(String::as_str),
+            &self.name.distribution,
+            &self.name.version,
+            ".dist-info",
+        )? {
+            Some(dist_info) => dist_info.to_owned(),
+            None => {
+                let mut candidates = top_level_names
+                    .iter()
+                    .filter(|name| name.ends_with(".dist-info"));
+                match (candidates.next(), candidates.next()) {
+                    (Some(dist_info), None) => {
+                        issues.push(WheelValidationIssue::DistInfoMismatch {
+                            dist_info: dist_info.clone(),
+                            expected: expected_dist_info,
+                        });
+                        dist_info.clone()
+                    }
+                    (None, _) => return Err(WheelVitalsError::DistInfoMissing),
+                    (Some(_), Some(_)) => {
+                        return Err(WheelVitalsError::MultipleSpecialDirs(String::from(
+                            ".dist-info",
+                        )))
+                    }
+                }
+            }
+        };
+
+        // Check that we support the version of the wheel format
+        let wheel_path = format!("{dist_info}/WHEEL");
+        let wheel_metadata = read_entry_to_end(&mut archive, &wheel_path)?;
+        let wheel_version = RFC822ish::from_str(&String::from_utf8_lossy(&wheel_metadata))
+            .map_err(WheelVitalsError::FailedToParseWheel)?
+            .take("Wheel-Version")
+            .map_err(|_| WheelVitalsError::MissingKeyInWheel(String::from("Wheel-Version")))?;
+        issues.extend(check_wheel_version(&wheel_version));
+
+        // Check that the metadata matches the filename
+        let metadata_path = format!("{dist_info}/METADATA");
+        let metadata = WheelCoreMetadata::try_from(
+            read_entry_to_end(&mut archive, &metadata_path)?.as_slice(),
+        )?;
+        if metadata.name != self.name.distribution {
+            issues.push(WheelValidationIssue::MetadataMismatch {
+                field: "name",
+                metadata: metadata.name.as_source_str().to_owned(),
+                filename: self.name.distribution.as_source_str().to_owned(),
+            });
+        }
+        if metadata.version != self.name.version {
+            issues.push(WheelValidationIssue::MetadataMismatch {
+                field: "version",
+                metadata: metadata.version.to_string(),
+                filename: self.name.version.to_string(),
+            });
+        }
+
+        // Read the RECORD file from the wheel
+        let record_filename = format!("{dist_info}/RECORD");
+        let record = Record::from_reader(
+            archive
+                .by_name(&record_filename)
+                .map_err(|err| WheelVitalsError::from_zip(record_filename.clone(), err))?,
+        )
+        .map_err(WheelVitalsError::InvalidRecord)?;
+
+        // Index the RECORD entries by their path. Strip any preceding slashes from the path since
+        // all paths in the wheel RECORD should be relative.
+        let mut recorded = HashMap::new();
+        for entry in record.iter() {
+            if !is_safe_relative_path(&entry.path) {
+                issues.push(WheelValidationIssue::UnsafePath(entry.path.clone()));
+            }
+            recorded.insert(entry.path.trim_start_matches('/'), entry);
+        }
+        recorded.remove(record_filename.as_str());
+
+        for index in 0..archive.len() {
+            let mut zip_entry = archive
+                .by_index(index)
+                .map_err(|err| WheelVitalsError::from_zip(format!("<index {index}>"), err))?;
+            let path = zip_entry.name().to_owned();
+
+            if zip_entry.enclosed_name().is_none() || !is_safe_relative_path(&path) {
+                issues.push(WheelValidationIssue::UnsafePath(path));
+                continue;
+            }
+
+            // The RECORD file and its signatures are not mentioned in RECORD itself.
+            // See <https://peps.python.org/pep-0491/>
+            if zip_entry.is_dir()
+                || path == record_filename
+                || path == format!("{record_filename}.jws")
+                || path == format!("{record_filename}.p7s")
+            {
+                continue;
+            }
+
+            let Some(entry) = recorded.remove(path.as_str()) else {
+                issues.push(WheelValidationIssue::MissingFromRecord(path));
+                continue;
+            };
+
+            // Compute the hash and size of the file, using the algorithm of the recorded hash
+            let algorithm = entry
+                .hash
+                .as_deref()
+                .and_then(|hash| hash.split_once('='))
+                .map_or("sha256", |(algorithm, _)| algorithm);
+            let (size, actual) = match algorithm {
+                "sha384" => hash_entry::<sha2::Sha384>(&mut zip_entry, algorithm)?,
+                "sha512" => hash_entry::<sha2::Sha512>(&mut zip_entry, algorithm)?,
+                _ => hash_entry::<Sha256>(&mut zip_entry, "sha256")?,
+            };
+
+            match &entry.hash {
+                None => issues.push(WheelValidationIssue::MissingHash(path.clone())),
+                Some(recorded_hash) if !matches!(algorithm, "sha256" | "sha384" | "sha512") => {
+                    issues.push(WheelValidationIssue::UnsupportedHash {
+                        path: path.clone(),
+                        hash: recorded_hash.clone(),
+                    })
+                }
+                Some(recorded_hash) if recorded_hash != &actual => {
+                    issues.push(WheelValidationIssue::HashMismatch {
+                        path: path.clone(),
+                        recorded: recorded_hash.clone(),
+                        actual,
+                    })
+                }
+                Some(_) => {}
+            }
+
+            if let Some(recorded_size) = entry.size.filter(|&recorded_size| recorded_size != size) {
+                issues.push(WheelValidationIssue::SizeMismatch {
+                    path,
+                    recorded: recorded_size,
+                    actual: size,
+                });
+            }
+        }
+
+        // Any entries that are left are listed in RECORD but are not part of the wheel
+        issues.extend(
+            recorded
+                .into_keys()
+                .filter(|path| !path.ends_with('/'))
+                .sorted()
+                .map(|path| WheelValidationIssue::MissingFromWheel(path.to_owned())),
+        );
+
+        Ok(issues)
+    }
+
     /// Read metadata from bytes-stream
     pub async fn read_metadata_bytes(
         name: &WheelFilename,
@@ -387,6 +581,12 @@ pub enum WheelVitalsError {
 
     #[error("missing key from WHEEL '{0}'")]
     MissingKeyInWheel(String),
+
+    #[error(".dist-info/RECORD missing")]
+    RecordMissing,
+
+    #[error("RECORD file is invalid")]
+    InvalidRecord(#[source] csv::Error),
 }
 
 impl WheelVitalsError {
@@ -398,6 +598,8 @@ impl WheelVitalsError {
                     WheelVitalsError::WheelMissing
                 } else if file.ends_with("METADATA") {
                     WheelVitalsError::MetadataMissing
+                } else if file.ends_with("RECORD") {
+                    WheelVitalsError::RecordMissing
                 } else {
                     WheelVitalsError::ZipError(file, err)
                 }
@@ -431,6 +633,42 @@ fn parse_format_metadata_and_check_version(
     Ok(parsed)
 }
 
+/// Checks the `Wheel-Version` of a wheel. Installers should refuse wheels with a newer major
+/// version and warn about wheels with a newer minor version than they support (1.0).
+fn check_wheel_version(version: &str) -> Option<WheelValidationIssue> {
+    let (major, minor) = version.trim().split_once('.').unwrap_or((version, ""));
+    match (major.parse::<u64>(), minor.parse::<u64>()) {
+        (Ok(1), Ok(0)) => None,
+        (Ok(1), Ok(_)) => Some(WheelValidationIssue::NewerWheelVersion(version.to_owned())),
+        _ => Some(WheelValidationIssue::UnsupportedWheelVersion(
+            version.to_owned(),
+        )),
+    }
+}
+
+/// Reads all bytes from `reader` and returns their size and their hash in the format of a RECORD
+/// entry, e.g. `sha256=<urlsafe base64 digest>`.
+fn hash_entry<D: Digest + Default>(
+    reader: impl Read,
+    algorithm: &str,
+) -> std::io::Result<(u64, String)> {
+    let mut reader = rattler_digest::HashingReader::<_, D>::new(reader);
+    let size = std::io::copy(&mut reader, &mut std::io::sink())?;
+    let (_, digest) = reader.finalize();
+    Ok((
+        size,
+        format!("{algorithm}={}", BASE64URL_NOPAD.encode(&digest)),
+    ))
+}
+
+/// Returns true if `path` is a relative path that stays within the directory it is relative to.
+fn is_safe_relative_path(path: &str) -> bool {
+    path.split(['/', '\\']).all(|component| component != "..")
+        && Path::new(path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+}
+
 /// Helper method to read a particular file from a zip archive.
 fn read_entry_to_end<R: ReadAndSeek>(
     archive: &mut ZipArchive<R>,
@@ -454,6 +692,7 @@ pub struct InstallPaths {
     scripts: PathBuf,
     data: PathBuf,
     headers: PathBuf,
+    include: PathBuf,
     windows: bool,
 }
 
@@ -490,9 +729,44 @@ impl InstallPaths {
             data,
             windows,
             headers: PathBuf::from("include"),
+            include: if windows {
+                PathBuf::from("Include")
+            } else {
+                PathBuf::from("include")
+            },
         }
     }
 
+    /// Populates mappings of installation targets from the paths of the `sysconfig` installation
+    /// scheme of an interpreter (`sysconfig.get_paths()`). This also works for interpreters that
+    /// are not part of a virtual environment, e.g. system or conda interpreters. Paths are made
+    /// relative to `prefix` (`sys.prefix`) where possible.
+    ///
+    /// Returns `None` if one of the `purelib`, `platlib`, `scripts`, `data` or `include` paths is
+    /// missing.
+    pub fn from_sysconfig(
+        prefix: &Path,
+        paths: &HashMap<String, PathBuf>,
+        windows: bool,
+    ) -> Option<Self> {
+        let path = |name: &str| {
+            paths.get(name).map(|path| {
+                path.strip_prefix(prefix)
+                    .map_or_else(|_| path.clone(), Path::to_path_buf)
+            })
+        };
+        let include = path("include")?;
+        Some(Self {
+            purelib: path("purelib")?,
+            platlib: path("platlib")?,
+            scripts: path("scripts")?,
+            data: path("data")?,
+            headers: include.clone(),
+            include,
+            windows,
+        })
+    }
+
     /// Determines whether this is a windows InstallPath
     pub fn is_windows(&self) -> bool {
         self.windows
@@ -525,11 +799,7 @@ impl InstallPaths {
 
     /// Returns the location of the include directory
     pub fn include(&self) -> PathBuf {
-        if self.windows {
-            PathBuf::from("Include")
-        } else {
-            PathBuf::from("include")
-        }
+        self.include.clone()
     }
 
     /// Returns the location of the headers directory. The location of headers is specific to a
@@ -569,8 +839,11 @@ pub enum UnpackError {
     #[error("RECORD file is invalid")]
     RecordCsv(#[from] csv::Error),
 
-    #[error("RECORD file doesn't match wheel contents: {0}")]
-    RecordFile(String),
+    #[error("{wheel} failed validation:\n{}", .issues.iter().map(|issue| format!("  - {issue}")).join("\n"))]
+    ValidationFailed {
+        wheel: String,
+        issues: Vec<WheelValidationIssue>,
+    },
 
     #[error("unrecognized .data directory: {0}")]
     UnsupportedDataDirectory(String),
@@ -597,6 +870,97 @@ impl UnpackError {
     }
 }
 
+/// A problem with a wheel that was found by [`Wheel::validate`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[allow(missing_docs)]
+pub enum WheelValidationIssue {
+    #[error("hash mismatch for {path}. Recorded: {recorded}, Actual: {actual}")]
+    HashMismatch {
+        path: String,
+        recorded: String,
+        actual: String,
+    },
+
+    #[error("size mismatch for {path}. Recorded: {recorded}, Actual: {actual}")]
+    SizeMismatch {
+        path: String,
+        recorded: u64,
+        actual: u64,
+    },
+
+    #[error("missing hash for {0} in RECORD")]
+    MissingHash(String),
+
+    #[error("unsupported hash for {path} in RECORD: {hash}")]
+    UnsupportedHash { path: String, hash: String },
+
+    #[error("{0} is not listed in RECORD")]
+    MissingFromRecord(String),
+
+    #[error("{0} is listed in RECORD but is missing from the wheel")]
+    MissingFromWheel(String),
+
+    #[error("path {0} escapes the installation directory")]
+    UnsafePath(String),
+
+    #[error("unsupported WHEEL version {0}")]
+    UnsupportedWheelVersion(String),
+
+    #[error("WHEEL version {0} is newer than the supported version 1.0")]
+    NewerWheelVersion(String),
+
+    #[error("{dist_info} doesn't match the wheel filename, expected {expected}")]
+    DistInfoMismatch { dist_info: String, expected: String },
+
+    #[error("{field} mismatch between METADATA and filename ({metadata} != {filename})")]
+    MetadataMismatch {
+        field: &'static str,
+        metadata: String,
+        filename: String,
+    },
+}
+
+impl WheelValidationIssue {
+    /// Returns true if the issue means that the files in the wheel can't be trusted or can't be
+    /// installed safely, in which case the wheel is not installed by default.
+    pub fn is_fatal(&self) -> bool {
+        matches!(
+            self,
+            Self::HashMismatch { .. } | Self::UnsafePath(_) | Self::UnsupportedWheelVersion(_)
+        )
+    }
+}
+
+/// Determines what [`Wheel::unpack`] does with the issues found by [`Wheel::validate`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum WheelValidationStrictness {
+    /// The wheel is not validated.
+    Off,
+
+    /// Issues are logged as warnings and the wheel is installed anyway.
+    Warn,
+
+    /// The wheel is not installed if a fatal issue is found (see
+    /// [`WheelValidationIssue::is_fatal`]), other issues are logged as warnings.
+    #[default]
+    Fatal,
+
+    /// The wheel is not installed if any issue is found, except for a newer minor version of
+    /// the wheel format which is only a warning.
+    Error,
+}
+
+impl WheelValidationStrictness {
+    /// Returns true if the issue prevents the wheel from being installed.
+    pub fn rejects(self, issue: &WheelValidationIssue) -> bool {
+        match self {
+            Self::Off | Self::Warn => false,
+            Self::Fatal => issue.is_fatal(),
+            Self::Error => !matches!(issue, WheelValidationIssue::NewerWheelVersion(_)),
+        }
+    }
+}
+
 /// Additional optional settings to pass to [`Wheel::unpack`].
 ///
 /// Not all options in this struct are relevant. Typically you will default a number of fields.
@@ -625,6 +989,11 @@ pub struct UnpackWheelOptions<'i> {
     /// because when using `unpack` on the wheel we do not know where it came from.
     /// This needs to be supplied manually.
     pub direct_url_json: Option<DirectUrlJson>,
+
+    /// How strictly the wheel is validated against its `RECORD` before anything is written to
+    /// the destination. By default only hash mismatches, unsafe paths and unsupported versions of
+    /// the wheel format prevent the wheel from being installed.
+    pub validation: WheelValidationStrictness,
 }
 
 #[derive(Debug)]
@@ -639,6 +1008,10 @@ pub struct UnpackedWheel {
 
 impl Wheel {
     /// Unpacks a wheel to the given filesystem.
+    ///
+    /// Before anything is written the wheel is checked with [`Wheel::validate`], what happens
+    /// with the issues that are found is determined by [`UnpackWheelOptions::validation`].
+    ///
     /// TODO: Write better docs.
     /// The following functionality is still missing:
     /// - entry_points.txt
@@ -655,6 +1028,19 @@ impl Wheel {
         python_executable: &Path,
         options: &UnpackWheelOptions,
     ) -> Result<UnpackedWheel, UnpackError> {
+        if options.validation != WheelValidationStrictness::Off {
+            let issues = self.validate()?;
+            if issues.iter().any(|issue| options.validation.rejects(issue)) {
+                return Err(UnpackError::ValidationFailed {
+                    wheel: self.name.to_string(),
+                    issues,
+                });
+            }
+            for issue in issues {
+                tracing::warn!("{}: {issue}", self.name);
+            }
+        }
+
         let vitals = self
             .get_vitals()
             .map_err(UnpackError::FailedToParseWheelVitals)?;
@@ -680,13 +1066,8 @@ impl Wheel {
         let site_packages = dest.join(paths.site_packages());
         let mut archive = self.archive.lock();
 
-        // Read the RECORD file from the wheel
+        // The RECORD file is rewritten at the end to reflect all the files that were installed
         let record_filename = format!("{}/RECORD", &vitals.dist_info);
-        let record = Record::from_reader(
-            &mut archive
-                .by_name(&record_filename)
-                .map_err(|err| WheelVitalsError::from_zip(record_filename.clone(), err))?,
-        )?;
         let record_relative_path = Path::new(&record_filename);
 
         // Read `entry_points.txt` and parse any scripts we need to create.
@@ -808,38 +1189,8 @@ impl Wheel {
                 }
             }
 
-            // Make sure the hash matches with what we expect
+            // Store the hash of the file that was written
             if let Some(encoded_hash) = encoded_hash {
-                let relative_path_string = relative_path.display().to_string();
-
-                // Find the record in the RECORD entries
-                let recorded_hash = record
-                    .iter()
-                    .find(|entry| {
-                        // Strip any preceding slashes from the path since all paths in the wheel
-                        // RECORD should be relative.
-                        entry.path.trim_start_matches('/') == relative_path_string
-                    })
-                    .and_then(|entry| entry.hash.as_ref())
-                    .ok_or_else(|| {
-                        UnpackError::RecordFile(format!(
-                            "missing hash for {} (expected {})",
-                            relative_path.display(),
-                            encoded_hash
-                        ))
-                    })?;
-
-                // Ensure that the hashes match
-                if &encoded_hash != recorded_hash {
-                    return Err(UnpackError::RecordFile(format!(
-                        "hash mismatch for {}. Recorded: {}, Actual: {}",
-                        relative_path.display(),
-                        recorded_hash,
-                        encoded_hash,
-                    )));
-                }
-
-                // Store the hash
                 resulting_records.push(RecordEntry {
                     path: pathdiff::diff_paths(&destination, &site_packages)
                         .unwrap_or_else(|| {
@@ -1180,8 +1531,9 @@ fn write_generated_file(
         }
     }
 
-    let (size, digest) = options
-        .open(site_packages.join(relative_path))
+    let destination = site_packages.join(relative_path);
+    let (size, digest) = remove_existing_file(&destination)
+        .and_then(|_| options.open(&destination))
         .map(rattler_digest::HashingWriter::<_, Sha256>::new)
         .and_then(|mut file| {
             let content = content.as_ref();
@@ -1198,6 +1550,15 @@ fn write_generated_file(
     })
 }
 
+/// Removes the file at `path` if it exists. Files are replaced instead of overwritten because they
+/// might be hardlinked to a file in another environment (see [`crate::python_env::VEnv::clone_to`]).
+fn remove_existing_file(path: &Path) -> std::io::Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
 /// Write a file from a wheel archive to disk.
 fn write_wheel_file(
     mut reader: &mut impl Read,
@@ -1221,8 +1582,8 @@ fn write_wheel_file(
         fs::create_dir_all(parent)
             .map_err(|err| UnpackError::IoError(parent.display().to_string(), err))?;
     }
-    let mut file = options
-        .open(destination)
+    let mut file = remove_existing_file(destination)
+        .and_then(|_| options.open(destination))
         .map_err(|err| UnpackError::IoError(destination.display().to_string(), err))?;
     let size = std::io::copy(&mut reader, &mut file)
         .map_err(|err| UnpackError::IoError(destination.display().to_string(), err))?;
@@ -1325,6 +1686,19 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_write_to() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../test-data/wheels/miniblack-23.1.0-py3-none-any.whl");
+        let wheel = Wheel::from_path(&path, &"miniblack".parse().unwrap()).unwrap();
+
+        let mut bytes = Vec::new();
+        let written = wheel.write_to(&mut bytes).unwrap();
+
+        assert_eq!(written as usize, bytes.len());
+        assert_eq!(bytes, fs::read(&path).unwrap());
+    }
+
     struct UnpackedWheel {
         tmpdir: TempDir,
         _metadata: WheelCoreMetadata,
@@ -1374,7 +1748,7 @@ mod test {
 
         // Determine the location where we would expect the RECORD file to exist
         let record_path = unpacked.dist_info.join("RECORD");
-        let record_content = fs::read_to_string(&unpacked.tmpdir.path().join(&record_path))
+        let record_content = fs::read_to_string(unpacked.tmpdir.path().join(&record_path))
             .unwrap_or_else(|_| panic!("failed to read RECORD from {}", record_path.display()));
 
         insta::assert_snapshot!(filename, record_content);
@@ -1409,7 +1783,7 @@ mod test {
 
         // Determine the location where we would expect the RECORD file to exist
         let record_path = unpacked.dist_info.join("RECORD");
-        let record_content = fs::read_to_string(&unpacked.tmpdir.path().join(&record_path))
+        let record_content = fs::read_to_string(unpacked.tmpdir.path().join(&record_path))
             .unwrap_or_else(|_| panic!("failed to read RECORD from {}", record_path.display()));
 
         // Replace all cpython references with cpython-xxx to ensure that no matter the version of
@@ -1595,4 +1969,321 @@ mod test {
         let stdout = String::from_utf8_lossy(&output.stdout);
         assert_eq!(stdout.trim(), "ruff 0.1.0");
     }
+
+    const EXAMPLE_WHEEL: &[u8] =
+        b"Wheel-Version: 1.0\nGenerator: test\nRoot-Is-Purelib: true\nTag: py3-none-any\n";
+    const EXAMPLE_METADATA: &[u8] = b"Metadata-Version: 2.1\nName: example\nVersion: 1.0\n";
+    const EXAMPLE_INIT: &[u8] = b"print('hello')\n";
+
+    fn encoded_sha256(contents: &[u8]) -> String {
+        encoded_digest::<Sha256>("sha256", contents)
+    }
+
+    fn encoded_digest<D: Digest + Default + Write>(algorithm: &str, contents: &[u8]) -> String {
+        let digest = rattler_digest::compute_bytes_digest::<D>(contents);
+        format!("{algorithm}={}", BASE64URL_NOPAD.encode(&digest))
+    }
+
+    /// Creates an `example-1.0-py3-none-any.whl` from the given files. Unless one of the files is
+    /// a RECORD, a RECORD that matches the files is added.
+    fn example_wheel(files: &[(&str, &[u8])]) -> Wheel {
+        use zip::{write::FileOptions, ZipWriter};
+
+        let mut writer = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let mut record = String::new();
+        for (path, contents) in files {
+            writer.start_file(*path, FileOptions::default()).unwrap();
+            writer.write_all(contents).unwrap();
+            record.push_str(&format!(
+                "{path},{},{}\n",
+                encoded_sha256(contents),
+                contents.len()
+            ));
+        }
+        if !files.iter().any(|(path, _)| path.ends_with("/RECORD")) {
+            record.push_str("example-1.0.dist-info/RECORD,,\n");
+            writer
+                .start_file("example-1.0.dist-info/RECORD", FileOptions::default())
+                .unwrap();
+            writer.write_all(record.as_bytes()).unwrap();
+        }
+
+        let name = WheelFilename::from_filename(
+            "example-1.0-py3-none-any.whl",
+            &"example".parse().unwrap(),
+        )
+        .unwrap();
+        Wheel::from_bytes(name, Box::new(writer.finish().unwrap())).unwrap()
+    }
+
+    /// A wheel whose `__init__.py` was modified after the RECORD was written.
+    fn tampered_example_wheel() -> Wheel {
+        let record = [
+            ("example/__init__.py", EXAMPLE_INIT),
+            ("example-1.0.dist-info/WHEEL", EXAMPLE_WHEEL),
+            ("example-1.0.dist-info/METADATA", EXAMPLE_METADATA),
+        ]
+        .into_iter()
+        .map(|(path, contents)| format!("{path},{},{}\n", encoded_sha256(contents), contents.len()))
+        .chain([String::from("example-1.0.dist-info/RECORD,,\n")])
+        .collect::<String>();
+        example_wheel(&[
+            ("example/__init__.py", b"import os; os.system('evil')\n"),
+            ("example-1.0.dist-info/WHEEL", EXAMPLE_WHEEL),
+            ("example-1.0.dist-info/METADATA", EXAMPLE_METADATA),
+            ("example-1.0.dist-info/RECORD", record.as_bytes()),
+        ])
+    }
+
+    #[test]
+    fn test_validate_valid_wheels() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../test-data/wheels/purelib_and_platlib-1.0.0-cp38-cp38-linux_x86_64.whl");
+        let wheel = Wheel::from_path(&path, &"purelib_and_platlib".parse().unwrap()).unwrap();
+        assert_eq!(wheel.validate().unwrap(), vec![]);
+
+        let wheel = example_wheel(&[
+            ("example/__init__.py", EXAMPLE_INIT),
+            ("example-1.0.dist-info/WHEEL", EXAMPLE_WHEEL),
+            ("example-1.0.dist-info/METADATA", EXAMPLE_METADATA),
+        ]);
+        assert_eq!(wheel.validate().unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_validate_miniblack() {
+        // A wheel from the wild with a RECORD that is slightly off, it is still installed by
+        // default
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../test-data/wheels/miniblack-23.1.0-py3-none-any.whl");
+        let wheel = Wheel::from_path(&path, &"miniblack".parse().unwrap()).unwrap();
+        let issues = wheel.validate().unwrap();
+        assert_eq!(
+            issues,
+            vec![
+                WheelValidationIssue::SizeMismatch {
+                    path: String::from("miniblack-23.1.0.dist-info/METADATA"),
+                    recorded: 58959,
+                    actual: 58963,
+                },
+                WheelValidationIssue::MissingFromWheel(String::from("blackd/middlewares.py")),
+            ]
+        );
+        assert!(!issues
+            .iter()
+            .any(|issue| WheelValidationStrictness::default().rejects(issue)));
+    }
+
+    #[test]
+    fn test_validate_record() {
+        let record = format!(
+            "example/__init__.py,{},3\n\
+             example/no_hash.py,,\n\
+             example/removed.py,{},0\n\
+             ../outside.py,,\n\
+             example/sha384.py,{},3\n\
+             example/sha512.py,{},3\n\
+             example/md5.py,md5=ICy5YqxZB1uWSwcVLSNLcA,3\n\
+             example-1.0.dist-info/WHEEL,{},{}\n\
+             example-1.0.dist-info/METADATA,{},{}\n\
+             example-1.0.dist-info/RECORD,,\n",
+            encoded_sha256(b"old"),
+            encoded_sha256(b""),
+            encoded_digest::<sha2::Sha384>("sha384", b"384"),
+            encoded_digest::<sha2::Sha512>("sha512", b"old"),
+            encoded_sha256(b"Wheel-Version: 1.1\n"),
+            19,
+            encoded_sha256(EXAMPLE_METADATA),
+            EXAMPLE_METADATA.len(),
+        );
+        let wheel = example_wheel(&[
+            ("example/__init__.py", EXAMPLE_INIT),
+            ("example/no_hash.py", b""),
+            ("example/unrecorded.py", b""),
+            ("../outside.py", b""),
+            ("example/sha384.py", b"384"),
+            ("example/sha512.py", b"512"),
+            ("example/md5.py", b"md5"),
+            (
+                "example-1.0.dist-info/WHEEL",
+                b"Wheel-Version: 1.1\nRoot-Is-Purelib: true\n",
+            ),
+            ("example-1.0.dist-info/METADATA", EXAMPLE_METADATA),
+            ("example-1.0.dist-info/RECORD", record.as_bytes()),
+        ]);
+
+        assert_eq!(
+            wheel.validate().unwrap(),
+            vec![
+                WheelValidationIssue::NewerWheelVersion(String::from("1.1")),
+                WheelValidationIssue::UnsafePath(String::from("../outside.py")),
+                WheelValidationIssue::HashMismatch {
+                    path: String::from("example/__init__.py"),
+                    recorded: encoded_sha256(b"old"),
+                    actual: encoded_sha256(EXAMPLE_INIT),
+                },
+                WheelValidationIssue::SizeMismatch {
+                    path: String::from("example/__init__.py"),
+                    recorded: 3,
+                    actual: EXAMPLE_INIT.len() as u64,
+                },
+                WheelValidationIssue::MissingHash(String::from("example/no_hash.py")),
+                WheelValidationIssue::MissingFromRecord(String::from("example/unrecorded.py")),
+                WheelValidationIssue::UnsafePath(String::from("../outside.py")),
+                WheelValidationIssue::HashMismatch {
+                    path: String::from("example/sha512.py"),
+                    recorded: encoded_digest::<sha2::Sha512>("sha512", b"old"),
+                    actual: encoded_digest::<sha2::Sha512>("sha512", b"512"),
+                },
+                WheelValidationIssue::UnsupportedHash {
+                    path: String::from("example/md5.py"),
+                    hash: String::from("md5=ICy5YqxZB1uWSwcVLSNLcA"),
+                },
+                WheelValidationIssue::HashMismatch {
+                    path: String::from("example-1.0.dist-info/WHEEL"),
+                    recorded: encoded_sha256(b"Wheel-Version: 1.1\n"),
+                    actual: encoded_sha256(b"Wheel-Version: 1.1\nRoot-Is-Purelib: true\n"),
+                },
+                WheelValidationIssue::SizeMismatch {
+                    path: String::from("example-1.0.dist-info/WHEEL"),
+                    recorded: 19,
+                    actual: 41,
+                },
+                WheelValidationIssue::MissingFromWheel(String::from("../outside.py")),
+                WheelValidationIssue::MissingFromWheel(String::from("example/removed.py")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_names() {
+        let wheel = example_wheel(&[
+            ("example/__init__.py", EXAMPLE_INIT),
+            ("other-1.0.dist-info/WHEEL", b"Wheel-Version: 2.0\n"),
+            (
+                "other-1.0.dist-info/METADATA",
+                b"Metadata-Version: 2.1\nName: other\nVersion: 2.0\n",
+            ),
+            ("other-1.0.dist-info/RECORD", b""),
+        ]);
+
+        assert_eq!(
+            wheel.validate().unwrap(),
+            vec![
+                WheelValidationIssue::DistInfoMismatch {
+                    dist_info: String::from("other-1.0.dist-info"),
+                    expected: String::from("example-1.0.dist-info"),
+                },
+                WheelValidationIssue::UnsupportedWheelVersion(String::from("2.0")),
+                WheelValidationIssue::MetadataMismatch {
+                    field: "name",
+                    metadata: String::from("other"),
+                    filename: String::from("example"),
+                },
+                WheelValidationIssue::MetadataMismatch {
+                    field: "version",
+                    metadata: String::from("2.0"),
+                    filename: String::from("1.0"),
+                },
+                WheelValidationIssue::MissingFromRecord(String::from("example/__init__.py")),
+                WheelValidationIssue::MissingFromRecord(String::from("other-1.0.dist-info/WHEEL")),
+                WheelValidationIssue::MissingFromRecord(String::from(
+                    "other-1.0.dist-info/METADATA"
+                )),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unpack_validation_strictness() {
+        let install_paths = InstallPaths::for_venv((3, 8, 5), false);
+        let unpack = |validation| {
+            let tmpdir = tempdir().unwrap();
+            let result = tampered_example_wheel().unpack(
+                tmpdir.path(),
+                &install_paths,
+                Path::new("/invalid"),
+                &UnpackWheelOptions {
+                    validation,
+                    ..Default::default()
+                },
+            );
+            let installed = tmpdir
+                .path()
+                .join(install_paths.site_packages())
+                .join("example/__init__.py")
+                .is_file();
+            (result, installed)
+        };
+
+        // Nothing is written when the wheel is invalid
+        for validation in [
+            WheelValidationStrictness::default(),
+            WheelValidationStrictness::Error,
+        ] {
+            let (result, installed) = unpack(validation);
+            assert!(
+                matches!(result, Err(UnpackError::ValidationFailed { ref issues, .. }) if issues.len() == 2),
+                "{result:?}"
+            );
+            assert!(!installed);
+        }
+
+        for validation in [
+            WheelValidationStrictness::Warn,
+            WheelValidationStrictness::Off,
+        ] {
+            let (result, installed) = unpack(validation);
+            assert!(result.is_ok(), "{result:?}");
+            assert!(installed);
+        }
+    }
+
+    #[test]
+    fn test_validation_strictness_rejects() {
+        let unrecorded = WheelValidationIssue::MissingFromRecord(String::from("example/a.py"));
+        let mismatch = WheelValidationIssue::HashMismatch {
+            path: String::from("example/a.py"),
+            recorded: encoded_sha256(b"old"),
+            actual: encoded_sha256(b"new"),
+        };
+        let newer_minor = WheelValidationIssue::NewerWheelVersion(String::from("1.1"));
+        let newer_major = WheelValidationIssue::UnsupportedWheelVersion(String::from("2.0"));
+
+        let default = WheelValidationStrictness::default();
+        assert!(!default.rejects(&unrecorded));
+        assert!(!default.rejects(&newer_minor));
+        assert!(default.rejects(&mismatch));
+        assert!(default.rejects(&newer_major));
+        assert!(default.rejects(&WheelValidationIssue::UnsafePath(String::from("../a.py"))));
+
+        let error = WheelValidationStrictness::Error;
+        assert!(error.rejects(&unrecorded));
+        assert!(!error.rejects(&newer_minor));
+        assert!(error.rejects(&newer_major));
+
+        assert!(!WheelValidationStrictness::Warn.rejects(&mismatch));
+        assert!(!WheelValidationStrictness::Off.rejects(&newer_major));
+
+        // A newer minor version of the wheel format doesn't prevent the installation
+        let wheel = example_wheel(&[
+            ("example/__init__.py", EXAMPLE_INIT),
+            (
+                "example-1.0.dist-info/WHEEL",
+                b"Wheel-Version: 1.1\nRoot-Is-Purelib: true\n",
+            ),
+            ("example-1.0.dist-info/METADATA", EXAMPLE_METADATA),
+        ]);
+        let tmpdir = tempdir().unwrap();
+        let result = wheel.unpack(
+            tmpdir.path(),
+            &InstallPaths::for_venv((3, 8, 5), false),
+            Path::new("/invalid"),
+            &UnpackWheelOptions {
+                validation: WheelValidationStrictness::Error,
+                ..Default::default()
+            },
+        );
+        assert!(result.is_ok(), "{result:?}");
+    }
 }
@@ -1,5 +1,6 @@
 use crate::artifacts::{SDist, Wheel};
 use crate::index::http::Http;
+use crate::index::package_sources::PackageSources;
 use crate::index::{parse_hash, CacheMode};
 use crate::resolve::PypiVersion;
 use crate::types::{
@@ -19,9 +20,11 @@ use std::str::FromStr;
 use std::sync::Arc;
 use url::Url;
 
-/// Get artifact by file URL
+/// Get artifact by http URL. The artifact is downloaded from the rewritten URL but the returned
+/// [`ArtifactInfo`] and [`DirectUrlJson`] refer to the original URL.
 pub(crate) async fn get_artifacts_and_metadata<P: Into<NormalizedPackageName>>(
     http: &Http,
+    sources: &PackageSources,
     p: P,
     url: Url,
     wheel_builder: &WheelBuilder,
@@ -34,7 +37,7 @@ pub(crate) async fn get_artifacts_and_metadata<P: Into<NormalizedPackageName>>(
     // Get the contents of the artifact
     let artifact_bytes = http
         .request(
-            url.clone(),
+            sources.rewrite_url(&url),
             Method::GET,
             HeaderMap::default(),
             CacheMode::Default,
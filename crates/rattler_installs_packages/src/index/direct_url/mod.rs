@@ -1,16 +1,19 @@
 use crate::index::http::Http;
 use crate::index::package_database::DirectUrlArtifactResponse;
-use crate::types::NormalizedPackageName;
+use crate::index::package_sources::PackageSources;
+use crate::types::{DirectUrlVcs, NormalizedPackageName};
 use crate::wheel_builder::WheelBuilder;
 use url::Url;
 
 pub(crate) mod file;
-pub(crate) mod git;
 pub(crate) mod http;
+pub(crate) mod vcs;
 
-/// Get artifact directly from file, vcs, or url
+/// Get artifact directly from file, vcs, or url. Artifacts that are downloaded over http are
+/// fetched from the location returned by [`PackageSources::rewrite_url`].
 pub(crate) async fn fetch_artifact_and_metadata_by_direct_url<P: Into<NormalizedPackageName>>(
     http: &Http,
+    sources: &PackageSources,
     p: P,
     url: Url,
     wheel_builder: &WheelBuilder,
@@ -22,11 +25,17 @@ pub(crate) async fn fetch_artifact_and_metadata_by_direct_url<P: Into<Normalized
         super::direct_url::file::get_artifacts_and_metadata(p.clone(), url, wheel_builder).await
     } else if url.scheme() == "https" {
         // This can be a Wheel or SDist artifact
-        super::direct_url::http::get_artifacts_and_metadata(http, p.clone(), url, wheel_builder)
-            .await
-    } else if url.scheme() == "git+https" || url.scheme() == "git+file" {
+        super::direct_url::http::get_artifacts_and_metadata(
+            http,
+            sources,
+            p.clone(),
+            url,
+            wheel_builder,
+        )
+        .await
+    } else if DirectUrlVcs::from_url(&url).is_some() {
         // This can be a STree artifact
-        super::direct_url::git::get_artifacts_and_metadata(p.clone(), url, wheel_builder).await
+        super::direct_url::vcs::get_artifacts_and_metadata(p.clone(), url, wheel_builder).await
     } else {
         Err(miette::miette!(
             "Usage of insecure protocol or unsupported scheme {:?}",
@@ -1,5 +1,8 @@
-use crate::index::git_interop::{git_clone, GitSource, ParsedUrl};
+use crate::index::git_interop::{
+    git_clone, pinned_url, requested_revision_fragment, GitSource, ParsedUrl,
+};
 use crate::index::package_database::DirectUrlArtifactResponse;
+use crate::index::vcs_interop::vcs_checkout;
 use crate::resolve::PypiVersion;
 use crate::types::{
     ArtifactHashes, ArtifactInfo, ArtifactName, ArtifactType, DirectUrlJson, DirectUrlSource,
@@ -13,7 +16,7 @@ use std::str::FromStr;
 use std::sync::Arc;
 use url::Url;
 
-/// Get artifact by git reference
+/// Get artifact by git, Mercurial, Subversion or Bazaar reference
 pub(crate) async fn get_artifacts_and_metadata<P: Into<NormalizedPackageName>>(
     p: P,
     url: Url,
@@ -26,24 +29,31 @@ pub(crate) async fn get_artifacts_and_metadata<P: Into<NormalizedPackageName>>(
     let git_source = GitSource {
         url: parsed_url.git_url,
         rev: parsed_url.revision,
+        subdirectory: parsed_url.subdirectory.clone(),
     };
 
-    let (mut location, git_rev) = git_clone(&git_source).into_diagnostic()?;
-
-    if let Some(subdirectory) = parsed_url.subdirectory {
-        location.push(&subdirectory);
-        if !location.exists() {
-            return Err(miette::miette!(
-                "Requested subdirectory fragment {:?} can't be located at following url {:?}",
-                subdirectory,
-                url
-            ));
-        }
+    // Checking out blocks, and may wait for another process that uses the same repository
+    let vcs = parsed_url.vcs;
+    let (location, commit) = {
+        let git_source = git_source.clone();
+        let cache_dir = wheel_builder.package_db().cache_dir().to_path_buf();
+        tokio::task::spawn_blocking(move || match vcs {
+            DirectUrlVcs::Git => git_clone(&git_source, &cache_dir)
+                .map(|(location, git_rev)| (location, git_rev.get_commit())),
+            _ => vcs_checkout(vcs, &git_source, &cache_dir),
+        })
+        .await
+        .into_diagnostic()?
+        .into_diagnostic()?
     };
 
+    // The artifact refers to the exact commit that was checked out, so that installing it later
+    // results in exactly the same source.
+    let pinned_url = pinned_url(&url, &commit);
+
     let (wheel_metadata, artifact) = super::file::get_stree_from_file_path(
         &normalized_package_name,
-        url.clone(),
+        pinned_url.clone(),
         Some(location),
         wheel_builder,
     )
@@ -64,19 +74,24 @@ pub(crate) async fn get_artifacts_and_metadata<P: Into<NormalizedPackageName>>(
     let direct_url_json = DirectUrlJson {
         url: Url::from_str(parsed_url.url.as_str()).expect("URL should be parseable"),
         source: DirectUrlSource::Vcs {
-            vcs: DirectUrlVcs::Git,
-            requested_revision: git_source.rev,
-            commit_id: git_rev.get_commit(),
+            vcs,
+            // A pinned url remembers the revision it was pinned from
+            requested_revision: requested_revision_fragment(&url)
+                .map(ToOwned::to_owned)
+                .or(git_source.rev),
+            commit_id: commit,
         },
     };
 
     let project_hash = ArtifactHashes {
-        sha256: Some(compute_bytes_digest::<Sha256>(url.as_str().as_bytes())),
+        sha256: Some(compute_bytes_digest::<Sha256>(
+            pinned_url.as_str().as_bytes(),
+        )),
     };
 
     let artifact_info = Arc::new(ArtifactInfo {
         filename: ArtifactName::STree(artifact.name().clone()),
-        url: url.clone(),
+        url: pinned_url,
         is_direct_url: true,
         hashes: Some(project_hash),
         requires_python,
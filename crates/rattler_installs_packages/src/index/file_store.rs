@@ -2,14 +2,18 @@
 // Licensed under MIT or Apache-2.0
 
 use crate::types::ArtifactHashes;
-use crate::utils::retry_interrupted;
+use crate::utils::{retry_interrupted, SeekSlice};
+use crate::wheel_builder::WheelCacheKey;
+use filetime::FileTime;
 use fs4::FileExt;
 use fs_err as fs;
+use rattler_digest::{digest::Digest, Sha256};
 use std::{
     io,
     io::{Read, Seek, SeekFrom, Write},
     marker::PhantomData,
     path::{Path, PathBuf},
+    time::SystemTime,
 };
 use tokio::task;
 
@@ -62,6 +66,41 @@ impl CacheKey for ArtifactHashes {
     }
 }
 
+impl CacheKey for WheelCacheKey {
+    fn key(&self) -> PathBuf {
+        let mut path = PathBuf::from("sdist");
+        path.push(self.as_str().as_bytes().key());
+        path
+    }
+}
+
+/// A file in a [`FileStore`]
+#[derive(Debug, Clone)]
+pub struct FileStoreEntry {
+    /// The path of the file
+    pub path: PathBuf,
+
+    /// The size of the file in bytes
+    pub size: u64,
+
+    /// The last time the file was written or read
+    pub last_access: SystemTime,
+}
+
+// Every file in the store ends with a trailer that contains the length and the SHA256 digest of
+// the content. This is used to detect files that were truncated or otherwise corrupted, e.g.
+// because the disk was full. A file looks like:
+//   [CONTENT][SHA256 (32 bytes)][LENGTH OF CONTENT (8 bytes, little endian)][TRAILER_MAGIC]
+// The length is checked whenever a file is opened, the digest is checked while the content is
+// read, see `EntryReader`, and by `FileStore::verify`.
+const TRAILER_MAGIC: &[u8; 4] = b"RIPS";
+pub(super) const TRAILER_LEN: u64 = 32 + 8 + TRAILER_MAGIC.len() as u64;
+
+// Older versions of rip stored files without a trailer directly in the base directory. Files with
+// a trailer are stored in a separate directory so that older versions that share the cache
+// directory never read them.
+const FORMAT_DIR: &str = "v2";
+
 #[derive(Debug)]
 /// A cache that stores its data as cbor files on the filesystem.
 pub struct FileStore {
@@ -72,8 +111,10 @@ pub struct FileStore {
 impl FileStore {
     /// Constructs a new instance of a [`FileStore`] rooted at the given `base`.
     pub fn new(base: &Path) -> io::Result<Self> {
+        let base = base.join(FORMAT_DIR);
+
         // Ensure the directory exists
-        fs::create_dir_all(base)?;
+        fs::create_dir_all(&base)?;
 
         // Get the canonical path now that we are sure the directory exists
         let base = base.canonicalize()?;
@@ -126,6 +167,96 @@ impl FileStore {
         })
     }
 
+    /// Returns all the files in the store, the most recently accessed files come first.
+    pub fn entries(&self) -> io::Result<Vec<FileStoreEntry>> {
+        let mut entries = Vec::new();
+        let mut dirs = vec![self.base.clone()];
+        // Other processes can remove files and directories while we iterate over them
+        fn skip_not_found<T>(result: io::Result<T>) -> io::Result<Option<T>> {
+            match result {
+                Ok(value) => Ok(Some(value)),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+                Err(err) => Err(err),
+            }
+        }
+
+        while let Some(dir) = dirs.pop() {
+            let Some(read_dir) = skip_not_found(fs::read_dir(&dir))? else {
+                continue;
+            };
+            for entry in read_dir {
+                let Some(entry) = skip_not_found(entry)? else {
+                    continue;
+                };
+                let path = entry.path();
+                let Some(file_type) = skip_not_found(entry.file_type())? else {
+                    continue;
+                };
+                if file_type.is_dir() {
+                    if path != self.tmp {
+                        dirs.push(path);
+                    }
+                } else if file_type.is_file()
+                    && path
+                        .extension()
+                        .map_or(true, |extension| extension != "lock")
+                {
+                    let Some(metadata) = skip_not_found(entry.metadata())? else {
+                        continue;
+                    };
+                    entries.push(FileStoreEntry {
+                        size: metadata.len(),
+                        last_access: metadata.modified()?,
+                        path,
+                    });
+                }
+            }
+        }
+        entries.sort_by(|a, b| b.last_access.cmp(&a.last_access));
+        Ok(entries)
+    }
+
+    /// Removes a file from the store. Waits until no other process or task holds the lock of the
+    /// file.
+    ///
+    /// The lock-file itself is kept, removing it would allow two processes to hold a lock for the
+    /// same file: one holding the lock on the removed lock-file and one on a newly created one.
+    pub async fn remove(&self, entry: &FileStoreEntry) -> io::Result<()> {
+        let _lock = lock(&entry.path, LockMode::Lock).await?;
+        match fs::remove_file(&entry.path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    /// Verifies the integrity of all the files in the store and removes the files that are
+    /// corrupted. Returns the removed files.
+    pub async fn verify(&self) -> io::Result<Vec<FileStoreEntry>> {
+        let mut removed = Vec::new();
+        for entry in self.entries()? {
+            let _lock = lock(&entry.path, LockMode::Lock).await?;
+            let mut file = match fs::File::open(&entry.path) {
+                Ok(file) => file,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err),
+            };
+            match verify_trailer(&mut file) {
+                Err(err) if err.kind() == io::ErrorKind::InvalidData => {
+                    tracing::warn!(
+                        "removing corrupted cache entry {}: {err}",
+                        entry.path.display()
+                    );
+                    fs::remove_file(&entry.path)?;
+                    removed.push(entry);
+                }
+                result => {
+                    result?;
+                }
+            }
+        }
+        Ok(removed)
+    }
+
     /// Locks a certain file in the cache for exclusive access if it exists only.
     ///
     /// This function exists to ensure that we don't create tons of directories just to check if an
@@ -173,10 +304,27 @@ impl<'a> Seek for LockedWriter<'a> {
 impl<'a> LockedWriter<'a> {
     /// Commit the content currently written to this instance. Returns a [`LockedReader`] which can
     /// be used to read from the file again.
-    pub fn commit(self) -> io::Result<LockedReader<'a>> {
+    pub fn commit(mut self) -> io::Result<LockedReader<'a>> {
+        // Append the trailer with the digest of the content
+        let len = self.f.seek(SeekFrom::End(0))?;
+        self.f.rewind()?;
+        let mut hasher = Sha256::default();
+        io::copy(&mut (&mut self.f).take(len), &mut hasher)?;
+        let digest = hasher.finalize();
+        self.f.seek(SeekFrom::Start(len))?;
+        self.f.write_all(&digest)?;
+        self.f.write_all(&len.to_le_bytes())?;
+        self.f.write_all(TRAILER_MAGIC)?;
+
         self.f.as_file().sync_data()?;
-        let mut file = fs::File::from_parts(self.f.persist(self.path)?, self.path);
-        file.rewind()?;
+        let file = fs::File::from_parts(self.f.persist(self.path)?, self.path);
+        let digest = digest
+            .as_slice()
+            .try_into()
+            .expect("the digest is 32 bytes");
+        let mut file = EntryReader::new(file, self.path.to_path_buf(), len, digest)?;
+        // We just computed the digest of the content ourselves
+        file.state = Verification::Verified;
         Ok(LockedReader {
             file,
             _data: Default::default(),
@@ -187,7 +335,7 @@ impl<'a> LockedWriter<'a> {
 /// A [`LockedReader`] is created from a [`FileLock`]. It holds a lifetime to the lock to ensure the
 /// lock is not dropped before the file itself.
 pub struct LockedReader<'a> {
-    file: fs::File,
+    file: EntryReader,
     _data: PhantomData<&'a ()>,
 }
 
@@ -204,10 +352,140 @@ impl<'a> Seek for LockedReader<'a> {
 }
 
 impl<'a> LockedReader<'a> {
-    /// Returns access to the underlying file ignoring the lock file.
-    pub fn detach_unlocked(self) -> fs::File {
+    /// Returns access to the content of the underlying file ignoring the lock file.
+    pub fn detach_unlocked(self) -> EntryReader {
         self.file
     }
+
+    /// Verifies the digest of the content, see [`EntryReader::verify`].
+    pub fn verify(&mut self) -> io::Result<()> {
+        self.file.verify()
+    }
+}
+
+/// Reads the content of a file in the [`FileStore`] and hashes it while it is read. Once all the
+/// content has been hashed the digest is compared with the digest in the trailer. If they differ
+/// the read fails with an [`io::ErrorKind::InvalidData`] error and the file is removed from the
+/// store, the next lookup of the entry misses and the content is fetched again.
+///
+/// Content that is skipped by seeking forward is hashed before the content after it is returned,
+/// content that is read again after seeking backwards is not hashed again.
+pub struct EntryReader {
+    file: SeekSlice<fs::File>,
+    path: PathBuf,
+    len: u64,
+    digest: [u8; 32],
+    /// The current position in the content.
+    pos: u64,
+    /// The content before this position has been hashed.
+    hashed: u64,
+    state: Verification,
+}
+
+enum Verification {
+    /// The content is being hashed.
+    Pending(Sha256),
+    /// The digest of the content matches the digest in the trailer.
+    Verified,
+    /// The digest of the content does not match the digest in the trailer.
+    Corrupted,
+}
+
+impl EntryReader {
+    fn new(file: fs::File, path: PathBuf, len: u64, digest: [u8; 32]) -> io::Result<Self> {
+        Ok(Self {
+            file: SeekSlice::new(file, 0, len)?,
+            path,
+            len,
+            digest,
+            pos: 0,
+            hashed: 0,
+            state: Verification::Pending(Sha256::default()),
+        })
+    }
+
+    /// Hashes the content that was not read yet and compares the digest with the digest in the
+    /// trailer. Returns an [`io::ErrorKind::InvalidData`] error and removes the file from the store
+    /// if the content is corrupted. The position of the reader is not changed.
+    pub fn verify(&mut self) -> io::Result<()> {
+        self.hash_until(self.len)?;
+        self.finish()
+    }
+
+    /// Hashes the content between `self.hashed` and `end` that was skipped.
+    fn hash_until(&mut self, end: u64) -> io::Result<()> {
+        let Verification::Pending(hasher) = &mut self.state else {
+            return Ok(());
+        };
+        if end <= self.hashed {
+            return Ok(());
+        }
+        self.file.seek(SeekFrom::Start(self.hashed))?;
+        let hashed = io::copy(&mut (&mut self.file).take(end - self.hashed), hasher)?;
+        if hashed != end - self.hashed {
+            return Err(corrupted(
+                "the file is shorter than the length of the content",
+            ));
+        }
+        self.hashed = end;
+        self.file.seek(SeekFrom::Start(self.pos))?;
+        Ok(())
+    }
+
+    /// Compares the digests once all the content has been hashed.
+    fn finish(&mut self) -> io::Result<()> {
+        match &self.state {
+            Verification::Pending(_) if self.hashed == self.len => {
+                let Verification::Pending(hasher) =
+                    std::mem::replace(&mut self.state, Verification::Corrupted)
+                else {
+                    unreachable!("the state is pending");
+                };
+                if hasher.finalize().as_slice() == self.digest {
+                    self.state = Verification::Verified;
+                    return Ok(());
+                }
+                tracing::warn!(
+                    "removing corrupted cache entry {}: the checksum of the content does not match",
+                    self.path.display()
+                );
+                if let Err(err) = fs::remove_file(&self.path) {
+                    tracing::warn!("failed to remove {}: {err}", self.path.display());
+                }
+                Err(corrupted("the checksum of the content does not match"))
+            }
+            Verification::Corrupted => Err(corrupted("the checksum of the content does not match")),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Read for EntryReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Verification::Corrupted = self.state {
+            return Err(corrupted("the checksum of the content does not match"));
+        }
+
+        self.hash_until(self.pos)?;
+        let read = self.file.read(buf)?;
+        let end = self.pos + read as u64;
+        if let Verification::Pending(hasher) = &mut self.state {
+            if end > self.hashed {
+                hasher.update(&buf[(self.hashed - self.pos) as usize..read]);
+                self.hashed = end;
+            }
+        }
+        self.pos = end;
+        self.finish()?;
+        Ok(read)
+    }
+}
+
+impl Seek for EntryReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.pos = self.file.seek(pos)?;
+        Ok(self.pos)
+    }
 }
 
 /// Holds a lock to a file in the [`FileStore`], can be used get a readable or writeable instance.
@@ -224,10 +502,35 @@ pub struct FileLock {
 
 impl FileLock {
     /// Creates a reader to read the contents of the locked file. Returns `None` if the file could
-    /// not be opened.
+    /// not be opened. If the file is truncated it is removed from the store and `None` is returned,
+    /// other corruptions are detected while the content is read, see [`EntryReader`].
     pub fn reader(&self) -> Option<LockedReader> {
+        let mut file = fs::File::open(&self.path).ok()?;
+        let (len, digest) = match read_trailer(&mut file) {
+            Ok(trailer) => trailer,
+            Err(err) => {
+                tracing::warn!(
+                    "removing corrupted cache entry {}: {err}",
+                    self.path.display()
+                );
+                if let Err(err) = self.remove() {
+                    tracing::warn!("failed to remove {}: {err}", self.path.display());
+                }
+                return None;
+            }
+        };
+
+        // The modification time is used to determine which files were used least recently.
+        // Access times are not reliable because many filesystems are mounted without them.
+        if let Err(err) = filetime::set_file_mtime(&self.path, FileTime::now()) {
+            tracing::debug!(
+                "failed to update the access time of {}: {err}",
+                self.path.display()
+            );
+        }
+
         Some(LockedReader {
-            file: fs::File::open(&self.path).ok()?,
+            file: EntryReader::new(file, self.path.clone(), len, digest).ok()?,
             _data: Default::default(),
         })
     }
@@ -242,12 +545,57 @@ impl FileLock {
     }
 
     /// Removes the file from the store.
-    pub fn remove(self) -> io::Result<()> {
-        fs::remove_file(self.path)?;
+    pub fn remove(&self) -> io::Result<()> {
+        fs::remove_file(&self.path)?;
         Ok(())
     }
 }
 
+fn corrupted(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason.to_string())
+}
+
+/// Reads the trailer of a file in the store and checks the length of the content, see
+/// [`TRAILER_MAGIC`]. Returns the length and the digest of the content or an
+/// [`io::ErrorKind::InvalidData`] error if the file is truncated.
+fn read_trailer(file: &mut fs::File) -> io::Result<(u64, [u8; 32])> {
+    let file_len = file.seek(SeekFrom::End(0))?;
+    if file_len < TRAILER_LEN {
+        return Err(corrupted("the file is too small to contain a checksum"));
+    }
+    file.seek(SeekFrom::Start(file_len - TRAILER_LEN))?;
+    let mut trailer = [0u8; TRAILER_LEN as usize];
+    file.read_exact(&mut trailer)?;
+    let (digest, rest) = trailer.split_at(32);
+    let (len, magic) = rest.split_at(8);
+    if magic != TRAILER_MAGIC {
+        return Err(corrupted("the file does not end with a checksum"));
+    }
+    let len = u64::from_le_bytes(len.try_into().expect("the length is 8 bytes"));
+    if len != file_len - TRAILER_LEN {
+        return Err(corrupted("the length of the content does not match"));
+    }
+
+    file.rewind()?;
+    Ok((len, digest.try_into().expect("the digest is 32 bytes")))
+}
+
+/// Verifies the length and digest of the content of a file in the store, see [`TRAILER_MAGIC`].
+/// Returns the length of the content or an [`io::ErrorKind::InvalidData`] error if the file is
+/// corrupted.
+fn verify_trailer(file: &mut fs::File) -> io::Result<u64> {
+    let (len, digest) = read_trailer(file)?;
+
+    let mut hasher = Sha256::default();
+    io::copy(&mut file.take(len), &mut hasher)?;
+    if hasher.finalize().as_slice() != digest {
+        return Err(corrupted("the checksum of the content does not match"));
+    }
+
+    file.rewind()?;
+    Ok(len)
+}
+
 #[derive(Eq, PartialEq)]
 enum LockMode {
     Lock,
@@ -315,6 +663,130 @@ mod test {
         assert_eq!(read_back, hello);
     }
 
+    #[tokio::test]
+    async fn test_entries_and_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileStore::new(dir.path()).unwrap();
+
+        for key in [b"first".as_slice(), b"second".as_slice()] {
+            store.get_or_set(&key, |w| w.write_all(key)).await.unwrap();
+        }
+
+        // Reading an entry marks it as the most recently used entry
+        let old = FileTime::from_unix_time(0, 0);
+        for entry in store.entries().unwrap() {
+            filetime::set_file_mtime(&entry.path, old).unwrap();
+        }
+        store.get(&b"first".as_slice()).await.unwrap();
+
+        let entries = store.entries().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].size, b"first".len() as u64 + TRAILER_LEN);
+        assert!(entries[0].last_access > entries[1].last_access);
+
+        store.remove(&entries[0]).await.unwrap();
+        assert!(store.get(&b"first".as_slice()).await.is_none());
+        assert!(store.get(&b"second".as_slice()).await.is_some());
+        assert_eq!(store.entries().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_corrupted_entries_are_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileStore::new(dir.path()).unwrap();
+
+        for key in [b"first".as_slice(), b"second".as_slice()] {
+            store.get_or_set(&key, |w| w.write_all(key)).await.unwrap();
+        }
+        assert!(store.verify().await.unwrap().is_empty());
+
+        // Truncate the first entry, e.g. as if the disk was full
+        let path = store.base.join(b"first".as_slice().key());
+        let len = fs::metadata(&path).unwrap().len();
+        fs::OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(len - 1)
+            .unwrap();
+        assert!(store.get(&b"first".as_slice()).await.is_none());
+        assert!(!path.exists());
+
+        // The entry can be populated again
+        let mut read_back = Vec::new();
+        store
+            .get_or_set(&b"first".as_slice(), |w| w.write_all(b"first"))
+            .await
+            .unwrap()
+            .read_to_end(&mut read_back)
+            .unwrap();
+        assert_eq!(read_back, b"first");
+
+        // Change the content of the second entry, reading it fails once all the content has been
+        // read and removes the entry
+        let path = store.base.join(b"second".as_slice().key());
+        let corrupt = || {
+            let mut content = fs::read(&path).unwrap();
+            content[0] = b'S';
+            fs::write(&path, content).unwrap();
+        };
+        corrupt();
+        let err = store
+            .get(&b"second".as_slice())
+            .await
+            .unwrap()
+            .read_to_end(&mut Vec::new())
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(!path.exists());
+        assert!(store.get(&b"second".as_slice()).await.is_none());
+
+        // The entry is populated again and `verify` also detects the corruption
+        store
+            .get_or_set(&b"second".as_slice(), |w| w.write_all(b"second"))
+            .await
+            .unwrap();
+        corrupt();
+        let removed = store.verify().await.unwrap();
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].path, path);
+        assert!(store.get(&b"first".as_slice()).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_corrupted_content_is_detected_when_seeking() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileStore::new(dir.path()).unwrap();
+        let content = (0..=255u8).cycle().take(100_000).collect::<Vec<_>>();
+        let key = b"seek".as_slice();
+        store
+            .get_or_set(&key, |w| w.write_all(&content))
+            .await
+            .unwrap();
+
+        // Jumping back and forth through intact content works
+        let mut reader = store.get(&key).await.unwrap();
+        let mut buf = [0u8; 10];
+        reader.seek(SeekFrom::Start(50_000)).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf.as_slice(), &content[50_000..50_010]);
+        reader.rewind().unwrap();
+        let mut read_back = Vec::new();
+        reader.read_to_end(&mut read_back).unwrap();
+        assert_eq!(read_back, content);
+
+        // The skipped content is hashed before the end of the content is returned
+        let path = store.base.join(key.key());
+        let mut corrupted = fs::read(&path).unwrap();
+        corrupted[10] ^= 0xff;
+        fs::write(&path, corrupted).unwrap();
+        let mut reader = store.get(&key).await.unwrap();
+        reader.seek(SeekFrom::End(-10)).unwrap();
+        let err = reader.read_exact(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(!path.exists());
+    }
+
     /// Test deadlock situation that occurred
     /// We want to test that progress can still be made even though a task is holding the lock
     /// In the old implementation this would deadlock.
@@ -2,13 +2,22 @@ use std::collections::HashMap;
 use std::fmt;
 use std::{
     fmt::{Display, Formatter},
-    path::PathBuf,
-    process::Command,
+    path::{Path, PathBuf},
     str::FromStr,
+    sync::OnceLock,
 };
 
-use fs_extra::dir::remove;
+use crate::types::DirectUrlVcs;
+use fs4::FileExt;
+use fs_err as fs;
+use git2::build::CheckoutBuilder;
+use git2::{
+    AutotagOption, Cred, CredentialType, Direction, ErrorClass, ErrorCode, FetchOptions, Oid,
+    ProxyOptions, RemoteCallbacks, Repository, SubmoduleUpdateOptions, WorktreeAddOptions,
+    WorktreePruneOptions,
+};
 use miette::IntoDiagnostic;
+use rattler_digest::Sha256;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use url::Url;
@@ -96,8 +105,11 @@ impl Default for GitRev {
 /// cleaned url with revision and subdirectory
 /// parsed from
 /// git+https://github.com/example/repo.git@1.0.0#subdirectry=some
+/// or a url of another VCS, e.g. hg+https://hg.example.com/repo@1.0.0
 #[derive(Debug)]
 pub struct ParsedUrl {
+    /// The VCS of the repository
+    pub vcs: DirectUrlVcs,
     /// Url to the git repository
     pub git_url: GitUrl,
     /// Url to the git repository
@@ -110,24 +122,34 @@ pub struct ParsedUrl {
 
 impl ParsedUrl {
     pub fn new(url: &Url) -> miette::Result<Self> {
-        let url_str = url.as_str();
-
-        let revision = Self::extract_revision_from_git_url(url_str);
-        let subdirectory = Self::subdirectory_fragment(url_str);
-        let mut clean_url = Self::clean_url(url_str);
+        let (path, revision) = match url.path().rsplit_once('@') {
+            Some((path, revision)) => (path, Some(revision.to_owned())),
+            None => (url.path(), None),
+        };
+        let subdirectory = Self::subdirectory_fragment(url.as_str());
+        let vcs =
+            DirectUrlVcs::from_url(url).ok_or_else(|| miette::miette!("{url} is not a VCS url"))?;
 
-        let git_url = if clean_url.contains("git+https") {
-            clean_url = clean_url.replace("git+https", "https");
-            let url = Url::from_str(&clean_url).into_diagnostic()?;
-            GitUrl::Url(url)
-        } else {
-            let path = url.path();
-            clean_url = path.replace(".git", "");
-            let path = PathBuf::from_str(&clean_url).into_diagnostic()?;
-            GitUrl::Path(path)
+        let (git_url, clean_url) = match url.as_str().split_once('+').map(|(_, url)| url) {
+            Some(url_str) if url_str.starts_with("file:") => {
+                let path = PathBuf::from_str(path).into_diagnostic()?;
+                (
+                    GitUrl::Path(path.clone()),
+                    format!("file://{}", path.display()),
+                )
+            }
+            Some(url_str) => {
+                let mut clean_url = Url::parse(url_str).into_diagnostic()?;
+                clean_url.set_path(path);
+                clean_url.set_query(None);
+                clean_url.set_fragment(None);
+                (GitUrl::Url(clean_url.clone()), clean_url.to_string())
+            }
+            None => unreachable!("VCS urls contain a +"),
         };
 
         Ok(ParsedUrl {
+            vcs,
             git_url,
             url: clean_url,
             revision,
@@ -135,24 +157,6 @@ impl ParsedUrl {
         })
     }
 
-    /// Extract git revision if it's present
-    /// and return url without revision and the revision
-    fn extract_revision_from_git_url(url: &str) -> Option<String> {
-        // Split the string at '@' and take the second part
-        let rev = if url.contains('@') {
-            let split: Vec<&str> = url.split('@').collect();
-            if let Some((rev, _)) = split.split_last() {
-                Some(String::from(*rev))
-            } else {
-                None
-            }
-        } else {
-            None
-        };
-
-        rev
-    }
-
     fn subdirectory_fragment(url: &str) -> Option<String> {
         let subdirectory_fragment_re = Regex::new(r#"[#&]subdirectory=([^&]*)"#).unwrap();
 
@@ -163,21 +167,86 @@ impl ParsedUrl {
         }
         None
     }
+}
 
-    fn clean_url(url: &str) -> String {
-        // Find the index of ".git" in the repository URL, or use the length if ".git" is not present
-        let repo_index = url.find(".git").map(|index| index + 4).unwrap_or_else(|| {
-            // .git is missing, remove @ if present
-            url.find('@').unwrap_or(url.len())
-        });
+/// The fragment parameter of a pinned url that contains the revision that was requested before
+/// the url was pinned, e.g. a branch or tag.
+const REQUESTED_REVISION_FRAGMENT: &str = "requested_revision";
+
+/// Returns the given VCS url with its revision replaced by `commit`, so that the url always
+/// refers to exactly the same source. A requested branch or tag is kept in the fragment of the
+/// url, see [`is_pinned_url_of`].
+pub fn pinned_url(url: &Url, commit: &str) -> Url {
+    let (path, requested) = match url.path().rsplit_once('@') {
+        Some((path, rev)) => (path, Some(rev)),
+        None => (url.path(), None),
+    };
+    let mut pinned = url.clone();
+    pinned.set_path(&format!("{path}@{commit}"));
+
+    let vcs = DirectUrlVcs::from_url(url);
+    let is_commit = |rev: &str| vcs.map_or(false, |vcs| vcs.is_commit_id(rev));
+    if let Some(requested) = requested.filter(|rev| *rev != commit && !is_commit(rev)) {
+        if requested_revision_fragment(url).is_none() {
+            let parameter = format!("{REQUESTED_REVISION_FRAGMENT}={requested}");
+            let fragment = match url.fragment().filter(|fragment| !fragment.is_empty()) {
+                Some(fragment) => format!("{fragment}&{parameter}"),
+                None => parameter,
+            };
+            pinned.set_fragment(Some(&fragment));
+        }
+    }
+    pinned
+}
 
-        // Remove everything after ".git"
-        let clean_url = url.chars().take(repo_index).collect();
+/// Returns the revision that was requested before the url was pinned, see [`pinned_url`].
+pub(crate) fn requested_revision_fragment(url: &Url) -> Option<&str> {
+    url.fragment()?.split('&').find_map(|parameter| {
+        parameter
+            .strip_prefix(REQUESTED_REVISION_FRAGMENT)?
+            .strip_prefix('=')
+    })
+}
 
-        clean_url
+/// Returns true if `pinned` is a url returned by [`pinned_url`] for a revision that `requested`
+/// can refer to. A commit that was pinned from a branch or tag only satisfies a request for the
+/// same branch or tag.
+pub fn is_pinned_url_of(requested: &Url, pinned: &Url) -> bool {
+    let pinned_from = requested_revision_fragment(pinned);
+    let (Ok(requested), Ok(pinned)) = (ParsedUrl::new(requested), ParsedUrl::new(pinned)) else {
+        return false;
+    };
+    if requested.vcs != pinned.vcs
+        || requested.url != pinned.url
+        || requested.subdirectory != pinned.subdirectory
+    {
+        return false;
+    }
+    let vcs = pinned.vcs;
+    let Some(commit) = pinned.revision.filter(|rev| vcs.is_commit_id(rev)) else {
+        return false;
+    };
+    match requested.revision {
+        Some(rev) if vcs.is_commit_id(&rev) => rev == commit,
+        Some(rev)
+            if matches!(vcs, DirectUrlVcs::Git | DirectUrlVcs::Mercurial)
+                && rev.chars().all(|c| c.is_ascii_hexdigit())
+                && commit.starts_with(&rev) =>
+        {
+            true
+        }
+        rev => rev.as_deref() == pinned_from,
     }
 }
 
+/// Returns true if the revision is a full (SHA-1) commit hash
+fn is_full_commit_hash(rev: &str) -> bool {
+    DirectUrlVcs::Git.is_commit_id(rev)
+}
+
+/// The file that is written to a checkout in the cache once it is complete
+pub(crate) const CHECKOUT_OK_MARKER: &str = ".rip-ok";
+
 /// Git source information.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct GitSource {
@@ -185,6 +254,8 @@ pub struct GitSource {
     pub url: GitUrl,
     /// Optionally a revision to checkout, defaults to `HEAD`
     pub rev: Option<String>,
+    /// Optionally a subdirectory of the repository that contains the package
+    pub subdirectory: Option<String>,
 }
 impl GitSource {
     /// Get the git url.
@@ -202,234 +273,975 @@ pub enum SourceError {
     #[error("Failed to download source from url: {0}")]
     Url(#[from] reqwest::Error),
 
-    #[error("FileSystem error: '{0}'")]
-    FileSystemError(fs_extra::error::Error),
+    #[error("Git operation failed: {0}")]
+    Git(#[from] git2::Error),
+
+    #[error("revision '{0}' does not exist in the repository")]
+    UnknownRevision(String),
+
+    #[error("subdirectory '{0}' does not exist in the repository")]
+    MissingSubdirectory(String),
 
-    #[error("Download could not be validated with checksum!")]
-    ValidationFailed,
+    #[error("authentication failed for {url}: {message}")]
+    AuthenticationFailed { url: String, message: String },
 
-    #[error("Failed to run git command: {0}")]
-    GitError(String),
+    #[error("repository {url} was not found: {message}")]
+    RepositoryNotFound { url: String, message: String },
 
-    #[error("Failed to run git command: {0}")]
-    GitErrorStr(&'static str),
+    #[error("could not connect to {url}: {message}")]
+    NetworkError { url: String, message: String },
+
+    #[error("`{0}` is required to check out the repository, but it was not found in PATH")]
+    MissingVcsTool(&'static str),
+
+    #[error("`{command}` failed: {message}")]
+    VcsCommandFailed { command: String, message: String },
 }
 
-/// Create a `git` command with the given subcommand.
-fn git_command(sub_cmd: &str) -> Command {
-    let mut command = Command::new("git");
-    command.arg(sub_cmd);
+/// Returns the url or path that is passed to libgit2 to access the repository.
+fn remote_location(url: &GitUrl) -> Result<String, SourceError> {
+    match url {
+        GitUrl::Url(url) => Ok(url.to_string()),
+        GitUrl::Path(path) => Ok(local_repository_path(path)?.to_string_lossy().to_string()),
+    }
+}
 
-    command
+/// Returns the absolute path of a local repository. The repository doesn't have to exist, commits
+/// that were fetched before can still be used from the cache.
+pub(crate) fn local_repository_path(path: &Path) -> Result<PathBuf, SourceError> {
+    // libgit2 doesn't support UNC paths, hence we can't use std::fs::canonicalize
+    match dunce::canonicalize(path) {
+        Ok(path) => Ok(path),
+        Err(_) => Ok(std::env::current_dir()?.join(path)),
+    }
 }
 
-fn git_version() -> miette::Result<(u8, u8)> {
-    let output = Command::new("git")
-        .arg("version")
-        .output()
-        .into_diagnostic()?;
+/// Callbacks that provide credentials when the remote asks for them. For ssh the keys of the ssh
+/// agent are tried first, followed by the default key files in `~/.ssh`. For http the
+/// credential helpers that are configured for git are used.
+fn remote_callbacks<'cb>() -> RemoteCallbacks<'cb> {
+    let mut tried_ssh_agent = false;
+    let mut ssh_keys = dirs::home_dir()
+        .map(|home| {
+            ["id_ed25519", "id_ecdsa", "id_rsa"]
+                .into_iter()
+                .map(|name| home.join(".ssh").join(name))
+                .filter(|path| path.is_file())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default()
+        .into_iter();
+    let mut tried_credential_helper = false;
 
-    let output_str = String::from_utf8_lossy(&output.stdout);
-    let regex = Regex::new(r"^git version (\d+)\.(\d+)(?:\s+\(.*\))?*").into_diagnostic()?;
-    let captures = regex.captures(&output_str);
-    if let Some(version) = captures {
-        let major = u8::from_str(&version[1]).into_diagnostic()?;
-        let minor = u8::from_str(&version[2]).into_diagnostic()?;
-        Ok((major, minor))
-    } else {
-        Err(miette::miette!(
-            help = "Can't parse git version.",
-            "{}",
-            output_str
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(move |url, username_from_url, allowed| {
+        let username = username_from_url.unwrap_or("git");
+        if allowed.contains(CredentialType::USERNAME) {
+            return Cred::username(username);
+        }
+        if allowed.contains(CredentialType::SSH_KEY) {
+            if !tried_ssh_agent {
+                tried_ssh_agent = true;
+                return Cred::ssh_key_from_agent(username);
+            }
+            if let Some(key) = ssh_keys.next() {
+                return Cred::ssh_key(username, None, &key, None);
+            }
+        }
+        if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) && !tried_credential_helper {
+            tried_credential_helper = true;
+            let cred = git2::Config::open_default()
+                .and_then(|config| Cred::credential_helper(&config, url, username_from_url));
+            if let Ok(cred) = cred {
+                return Ok(cred);
+            }
+        }
+        Err(git2::Error::new(
+            ErrorCode::Auth,
+            ErrorClass::Callback,
+            "no (valid) credentials available",
         ))
-    }
+    });
+    callbacks
 }
 
-fn support_partial_clone() -> miette::Result<bool> {
-    let version = git_version()?;
-    if version >= (2, 17) {
-        return Ok(true);
-    }
-    Ok(false)
+/// Options used for every fetch from a remote.
+fn fetch_options<'cb>() -> FetchOptions<'cb> {
+    let mut proxy_options = ProxyOptions::new();
+    proxy_options.auto();
+    let mut fetch_options = FetchOptions::new();
+    fetch_options
+        .remote_callbacks(remote_callbacks())
+        .proxy_options(proxy_options)
+        .download_tags(AutotagOption::None);
+    fetch_options
 }
 
-fn get_revision_sha(dest: &PathBuf, rev: Option<String>) -> Result<GitRev, SourceError> {
-    // Pass rev to pre-filter the list.
-    let rev = if let Some(rev) = rev {
-        rev
-    } else {
-        "HEAD".to_owned()
+/// Turns errors that indicate that the credentials were missing or rejected, or that the
+/// repository does not exist, into more specific errors.
+fn classify_error(err: SourceError, location: &str) -> SourceError {
+    let SourceError::Git(err) = err else {
+        return err;
     };
 
-    let output = Command::new("git")
-        .args(["show-ref", &rev])
-        .current_dir(dest)
-        .output()?;
-
-    let output_str = String::from_utf8_lossy(&output.stdout);
-    let refs: HashMap<_, _> = output_str
-        .lines()
-        .filter(|line| !line.trim().is_empty())
-        .map(|line| {
-            let mut parts = line.splitn(2, ' ');
-            let ref_sha = parts.next().unwrap().to_string();
-            let ref_name = parts.next().unwrap().to_string();
-            (ref_name, ref_sha)
-        })
-        .collect();
+    // Don't show passwords that are part of the url
+    let url = match Url::parse(location) {
+        Ok(mut url) if url.password().is_some() => {
+            url.set_password(None).ok();
+            url.to_string()
+        }
+        _ => location.to_owned(),
+    };
+    let message = err.message().to_owned();
+    match classify_message(&message) {
+        Some(FailureKind::Authentication) => SourceError::AuthenticationFailed { url, message },
+        Some(FailureKind::NotFound) => SourceError::RepositoryNotFound { url, message },
+        Some(FailureKind::Network) => SourceError::NetworkError { url, message },
+        None if err.code() == ErrorCode::Auth => SourceError::AuthenticationFailed { url, message },
+        None if err.class() == ErrorClass::Net => SourceError::NetworkError { url, message },
+        None => SourceError::Git(err),
+    }
+}
 
-    let branch_ref = format!("refs/remotes/origin/{}", rev);
-    let tag_ref = format!("refs/tags/{}", rev);
+/// Why accessing a repository failed
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum FailureKind {
+    Authentication,
+    NotFound,
+    Network,
+}
+
+/// Determines from the error message of git, or of the command line tool of another VCS, whether
+/// the credentials were missing or rejected, whether the repository does not exist, or whether the
+/// remote could not be reached.
+pub(crate) fn classify_message(message: &str) -> Option<FailureKind> {
+    const AUTHENTICATION: &[&str] = &[
+        "authentication",
+        "authorization failed",
+        "permission denied",
+        "credentials",
+        "status code: 401",
+        "status code: 403",
+        "http error 401",
+        "http error 403",
+    ];
+    const NOT_FOUND: &[&str] = &[
+        // git
+        "could not find repository",
+        "does not appear to be a git repository",
+        "repository not found",
+        "repository does not exist",
+        "status code: 404",
+        // mercurial
+        "http error 404",
+        "no repository found",
+        // subversion: path not found, unable to open repository, url doesn't exist
+        "e160013:",
+        "e180001:",
+        "e170000: url",
+        // bazaar
+        "bzr: error: not a branch",
+    ];
+    const NETWORK: &[&str] = &[
+        // subversion: unable to connect to a repository, connection failed
+        "e170013:",
+        "e175002:",
+        "could not resolve host",
+        "failed to resolve address",
+        "name or service not known",
+        "temporary failure in name resolution",
+        "connection refused",
+        "connection timed out",
+        "network is unreachable",
+        "failed to connect",
+    ];
+    // `hg` reports a missing repository as "abort: repository <url> not found"
+    static HG_NOT_FOUND: OnceLock<Regex> = OnceLock::new();
+    let hg_not_found =
+        HG_NOT_FOUND.get_or_init(|| Regex::new(r"abort: repository \S+ not found").unwrap());
 
-    let sha = refs.get(&branch_ref).cloned();
-    if let Some(sha) = sha {
-        return Ok(GitRev::Branch(sha));
+    let message = message.to_lowercase();
+    let matches = |patterns: &[&str]| patterns.iter().any(|pattern| message.contains(pattern));
+    if matches(AUTHENTICATION) {
+        Some(FailureKind::Authentication)
+    } else if matches(NOT_FOUND) || hg_not_found.is_match(&message) {
+        Some(FailureKind::NotFound)
+    } else if matches(NETWORK) {
+        Some(FailureKind::Network)
+    } else {
+        None
     }
+}
+
+/// Returns the references that the remote advertises, with the commits they point to. Annotated
+/// tags are peeled to the commit they point to.
+fn list_remote_refs(
+    repo: &Repository,
+    location: &str,
+) -> Result<HashMap<String, Oid>, SourceError> {
+    let mut remote = repo.remote_anonymous(location)?;
+    let mut proxy_options = ProxyOptions::new();
+    proxy_options.auto();
+    let connection = remote.connect_auth(
+        Direction::Fetch,
+        Some(remote_callbacks()),
+        Some(proxy_options),
+    )?;
 
-    let sha = refs.get(&tag_ref).cloned();
-    if let Some(sha) = sha {
-        return Ok(GitRev::Tag(sha));
+    let mut refs = HashMap::new();
+    for head in connection.list()? {
+        match head.name().strip_suffix("^{}") {
+            Some(name) => {
+                refs.insert(name.to_owned(), head.oid());
+            }
+            None => {
+                refs.entry(head.name().to_owned()).or_insert(head.oid());
+            }
+        }
     }
+    Ok(refs)
+}
 
-    Ok(GitRev::Commit(rev.to_owned()))
+/// How much of the history to fetch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FetchDepth {
+    /// Only fetch the commits the refspecs point to, if the remote supports shallow fetches.
+    Shallow,
+    /// Fetch the complete history, a shallow repository is unshallowed.
+    Full,
 }
 
-/// Fetch the git repository specified by the given source and place it in the cache directory.
-pub fn git_clone(source: &GitSource) -> Result<(PathBuf, GitRev), SourceError> {
-    // test if git is available locally as we fetch the git from PATH,
-    if !Command::new("git")
-        .arg("--version")
-        .output()?
-        .status
-        .success()
-    {
-        return Err(SourceError::GitErrorStr(
-            "`git` command not found in `PATH`",
-        ));
+/// Returns true if libgit2 can fetch shallowly from the location, it does not support shallow
+/// fetches from local repositories.
+fn supports_shallow_fetch(location: &str) -> bool {
+    Url::parse(location)
+        .is_ok_and(|url| matches!(url.scheme(), "http" | "https" | "ssh" | "git") && url.has_host())
+}
+
+/// Fetches the given refspecs from the remote into the repository.
+fn fetch(
+    repo: &Repository,
+    location: &str,
+    refspecs: &[&str],
+    depth: FetchDepth,
+) -> Result<(), git2::Error> {
+    let mut remote = repo.remote_anonymous(location)?;
+    match depth {
+        FetchDepth::Shallow if supports_shallow_fetch(location) => {
+            let mut options = fetch_options();
+            options.depth(1);
+            // Not every server supports shallow fetches, fall back to fetching everything
+            if let Err(err) = remote.fetch(refspecs, Some(&mut options), None) {
+                tracing::debug!("shallow fetch from {location} failed, fetching everything: {err}");
+                remote.fetch(refspecs, Some(&mut fetch_options()), None)?;
+            }
+            Ok(())
+        }
+        FetchDepth::Full if repo.is_shallow() => {
+            let mut options = fetch_options();
+            // GIT_FETCH_DEPTH_UNSHALLOW
+            options.depth(i32::MAX);
+            remote.fetch(refspecs, Some(&mut options), None)
+        }
+        _ => remote.fetch(refspecs, Some(&mut fetch_options()), None),
     }
+}
 
-    let tmp_dir = tempfile::tempdir().unwrap().into_path();
+/// Fetches the requested revision from the remote and returns the commit it resolves to, together
+/// with the kind of revision that was requested.
+fn fetch_revision(
+    repo: &Repository,
+    location: &str,
+    rev: Option<&str>,
+) -> Result<(GitRev, Oid), SourceError> {
+    let refs = list_remote_refs(repo, location)?;
 
-    let cache_dir = tmp_dir.join("rip-git-cache");
-    let recipe_dir = tmp_dir.join("rip-clone-dir");
+    let Some(rev) = rev else {
+        let oid = *refs
+            .get("HEAD")
+            .ok_or_else(|| SourceError::UnknownRevision("HEAD".to_owned()))?;
+        fetch(
+            repo,
+            location,
+            &["+HEAD:refs/remotes/origin/HEAD"],
+            FetchDepth::Shallow,
+        )?;
+        return Ok((GitRev::Head, oid));
+    };
+
+    let branch_ref = format!("refs/heads/{rev}");
+    if let Some(oid) = refs.get(&branch_ref) {
+        fetch(
+            repo,
+            location,
+            &[&format!("+{branch_ref}:refs/remotes/origin/{rev}")],
+            FetchDepth::Shallow,
+        )?;
+        return Ok((GitRev::Branch(rev.to_owned()), *oid));
+    }
+
+    let tag_ref = format!("refs/tags/{rev}");
+    if let Some(oid) = refs.get(&tag_ref) {
+        fetch(
+            repo,
+            location,
+            &[&format!("+{tag_ref}:{tag_ref}")],
+            FetchDepth::Shallow,
+        )?;
+        return Ok((GitRev::Tag(rev.to_owned()), *oid));
+    }
+
+    // Otherwise the revision should be a commit. Try to fetch it directly, not every server
+    // allows that and abbreviated hashes can't be fetched, so fall back to fetching all branches
+    // and tags, including their history, and looking the commit up locally.
+    let fetched_directly =
+        is_full_commit_hash(rev) && fetch(repo, location, &[rev], FetchDepth::Shallow).is_ok();
+    if !fetched_directly {
+        fetch(
+            repo,
+            location,
+            &[
+                "+refs/heads/*:refs/remotes/origin/*",
+                "+refs/tags/*:refs/tags/*",
+            ],
+            FetchDepth::Full,
+        )?;
+    }
+    let commit = repo
+        .revparse_single(rev)
+        .and_then(|object| object.peel_to_commit())
+        .map_err(|_| SourceError::UnknownRevision(rev.to_owned()))?;
+    Ok((GitRev::Commit(rev.to_owned()), commit.id()))
+}
 
-    let filename = match &source.url() {
-        GitUrl::Url(url) => (|| Some(url.path_segments()?.last()?.to_string()))()
-            .ok_or_else(|| SourceError::GitErrorStr("failed to get filename from url"))?,
-        GitUrl::Path(path) => recipe_dir
-            .join(path)
-            .canonicalize()?
-            .file_name()
-            .expect("unreachable, canonicalized paths shouldn't end with ..")
-            .to_string_lossy()
-            .to_string(),
+/// Returns the name of the directories in the cache for the repository. The name is derived from
+/// the normalized url so that different spellings of the same url share a cache entry.
+pub(crate) fn cache_name(url: &GitUrl) -> Result<String, SourceError> {
+    let (normalized, name) = match url {
+        GitUrl::Url(url) => {
+            let mut normalized = url.clone();
+            normalized.set_query(None);
+            normalized.set_fragment(None);
+            let path = normalized.path().trim_end_matches('/').to_owned();
+            let path = path.strip_suffix(".git").unwrap_or(&path).to_owned();
+            normalized.set_path(&path);
+            let name = path.rsplit('/').next().unwrap_or_default().to_owned();
+            (normalized.to_string(), name)
+        }
+        GitUrl::Path(path) => {
+            let path = local_repository_path(path)?;
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            (path.to_string_lossy().to_string(), name)
+        }
     };
+    let hash = rattler_digest::compute_bytes_digest::<Sha256>(normalized.as_bytes());
+    let name = name.strip_suffix(".git").unwrap_or(&name);
+    Ok(format!("{name}-{}", &format!("{hash:x}")[..16]))
+}
 
-    let cache_name = PathBuf::from(filename);
-    let cache_path = cache_dir.join(cache_name);
-
-    // Initialize or clone the repository depending on the source's git_url.
-    match &source.url() {
-        GitUrl::Url(_) => {
-            // If the cache_path exists, initialize the repo and fetch the specified revision.
-            if !cache_path.exists() {
-                let mut command = git_command("clone");
-                if support_partial_clone().is_ok() {
-                    command.arg("--filter=blob:none");
-                } else {
-                    command.arg("--recursive");
-                }
+/// Opens the bare repository in the cache, or creates it if it doesn't exist yet.
+fn open_database(path: &Path, location: &str) -> Result<Repository, SourceError> {
+    match Repository::open_bare(path) {
+        Ok(repo) => Ok(repo),
+        Err(err) => {
+            if path.exists() {
+                tracing::warn!("removing invalid git database {}: {err}", path.display());
+                fs::remove_dir_all(path)?;
+            }
+            let repo = Repository::init_bare(path)?;
+            repo.remote("origin", location)?;
+            Ok(repo)
+        }
+    }
+}
 
-                command
-                    .arg(source.url().to_string().as_str())
-                    .arg(cache_path.as_os_str());
+/// Returns the worktree for the commit, the worktree is created if it doesn't exist yet.
+/// Worktrees are never modified after they have been created.
+fn checkout_worktree(db: &Repository, path: &Path, oid: Oid) -> Result<(), SourceError> {
+    let ok_marker = path.join(CHECKOUT_OK_MARKER);
+    if ok_marker.is_file() {
+        return Ok(());
+    }
 
-                let output = command
-                    .output()
-                    .map_err(|_e| SourceError::GitErrorStr("Failed to execute clone command"))?;
-                if !output.status.success() {
-                    return Err(SourceError::GitErrorStr("Git clone failed for source"));
-                }
+    // Clean up the remains of a checkout that did not finish
+    let name = oid.to_string();
+    if let Ok(worktree) = db.find_worktree(&name) {
+        worktree.prune(Some(
+            WorktreePruneOptions::new()
+                .valid(true)
+                .locked(true)
+                .working_tree(true),
+        ))?;
+    }
+    if path.exists() {
+        fs::remove_dir_all(path)?;
+    }
+    fs::create_dir_all(path.parent().expect("worktrees are stored in a directory"))?;
+
+    // A worktree can only be created for a branch, the branch is removed again after the head of
+    // the worktree is detached.
+    let branch = db.branch(&format!("rip-checkout-{name}"), &db.find_commit(oid)?, true)?;
+    let worktree = db.worktree(
+        &name,
+        path,
+        Some(WorktreeAddOptions::new().reference(Some(branch.get()))),
+    )?;
+    let repo = Repository::open_from_worktree(&worktree)?;
+    repo.set_head_detached(oid)?;
+    repo.checkout_head(Some(CheckoutBuilder::new().force()))?;
+    branch.into_reference().delete()?;
+
+    update_submodules(&repo)?;
+
+    fs::write(ok_marker, "")?;
+    Ok(())
+}
+
+/// Recursively clones and checks out the submodules of the repository.
+fn update_submodules(repo: &Repository) -> Result<(), SourceError> {
+    for mut submodule in repo.submodules()? {
+        let mut options = SubmoduleUpdateOptions::new();
+        options.fetch(fetch_options());
+        submodule
+            .update(true, Some(&mut options))
+            .map_err(|err| classify_error(err.into(), submodule.url().unwrap_or_default()))?;
+        update_submodules(&submodule.open()?)?;
+    }
+    Ok(())
+}
+
+/// Opens the file and locks it exclusively, blocks until the lock is acquired. The lock is
+/// released when the returned file is dropped.
+pub(crate) fn lock_exclusive(path: &Path) -> Result<fs::File, SourceError> {
+    let file = fs::OpenOptions::new().write(true).create(true).open(path)?;
+    file.file().lock_exclusive()?;
+    Ok(file)
+}
+
+/// Returns the location of the subdirectory in the checkout, or the checkout itself if no
+/// subdirectory is requested.
+pub(crate) fn subdirectory_location(
+    checkout: PathBuf,
+    subdirectory: Option<&str>,
+) -> Result<PathBuf, SourceError> {
+    match subdirectory {
+        Some(subdirectory) => {
+            let location = checkout.join(subdirectory);
+            if !location.is_dir() {
+                return Err(SourceError::MissingSubdirectory(subdirectory.to_owned()));
             }
+            Ok(location)
         }
-        GitUrl::Path(path) => {
-            if cache_path.exists() {
-                // Remove old cache so it can be overwritten.
-                if let Err(remove_error) = remove(&cache_path) {
-                    tracing::error!("Failed to remove old cache directory: {}", remove_error);
-                    return Err(SourceError::FileSystemError(remove_error));
+        None => Ok(checkout),
+    }
+}
+
+/// Fetch the git repository specified by the given source into the cache directory and check out
+/// the requested revision.
+///
+/// Every repository is stored as a bare repository in `cache_dir/git/db` which is updated with a
+/// fetch when a branch or tag is requested. Every commit that is used gets its own worktree in
+/// `cache_dir/git/checkouts`. A commit that is requested by its full hash and that was fetched
+/// before is used without accessing the remote.
+///
+/// Returns the location of the checkout, or of the requested subdirectory within it, and the
+/// requested revision. The revision contains the hash of the commit that was checked out.
+pub fn git_clone(source: &GitSource, cache_dir: &Path) -> Result<(PathBuf, GitRev), SourceError> {
+    let location = remote_location(source.url())?;
+    let name = cache_name(source.url())?;
+    let git_dir = cache_dir.join("git");
+    let db_path = git_dir.join("db").join(&name);
+    fs::create_dir_all(git_dir.join("db"))?;
+
+    // Make sure that other processes don't modify the repository at the same time
+    let lock = lock_exclusive(&git_dir.join("db").join(format!("{name}.lock")))?;
+
+    let db = open_database(&db_path, &location)?;
+    let pinned_commit = source
+        .rev
+        .as_deref()
+        .filter(|rev| is_full_commit_hash(rev))
+        .and_then(|rev| db.find_commit(Oid::from_str(rev).ok()?).ok());
+    let (git_rev, oid) = match pinned_commit {
+        Some(commit) => (GitRev::Commit(commit.id().to_string()), commit.id()),
+        None => {
+            if let GitUrl::Path(path) = source.url() {
+                if !path.exists() {
+                    return Err(SourceError::RepositoryNotFound {
+                        url: location,
+                        message: "the path does not exist".to_owned(),
+                    });
                 }
             }
-            // git doesn't support UNC paths, hence we can't use std::fs::canonicalize
-            let path = dunce::canonicalize(path).map_err(|e| {
-                tracing::error!("Path not found on system: {}", e);
-                SourceError::GitError(format!("{}: Path not found on system", e))
-            })?;
-
-            let mut command = git_command("clone");
-
-            command
-                .arg("--recursive")
-                .arg(path)
-                .arg(cache_path.as_os_str());
-
-            let output = command
-                .output()
-                .map_err(|_| SourceError::ValidationFailed)?;
-
-            if !output.status.success() {
-                tracing::error!("Command failed: {:?}", command);
-                let err = String::from_utf8(output.stdout).unwrap();
-                let err_msg = format!(
-                    "failed to execute clone from file {:?} {:?}",
-                    output.status, err
-                );
-                return Err(SourceError::GitError(err_msg));
-            }
+            fetch_revision(&db, &location, source.rev.as_deref())
+                .map_err(|err| classify_error(err, &location))?
         }
     };
 
-    let git_rev = get_revision_sha(&cache_path, source.rev.clone())?;
+    if git_rev.is_head() {
+        tracing::debug!(
+            "checking out the default branch of {} at {oid}",
+            source.url()
+        );
+    } else {
+        tracing::debug!("checking out {} at {oid}", source.url());
+    }
+    let checkout_path = git_dir.join("checkouts").join(&name).join(oid.to_string());
+    checkout_worktree(&db, &checkout_path, oid)?;
+    drop(lock);
 
-    let mut checkout = git_command("checkout");
+    let location = subdirectory_location(checkout_path, source.subdirectory.as_deref())?;
 
-    let cmd = if !git_rev.is_head() {
-        Some(checkout.args(["-q", git_rev.get_commit().as_str()]))
-    } else {
-        None
+    let git_rev = match git_rev {
+        GitRev::Branch(_) => GitRev::Branch(oid.to_string()),
+        GitRev::Tag(_) => GitRev::Tag(oid.to_string()),
+        GitRev::Head | GitRev::Commit(_) => GitRev::Commit(oid.to_string()),
     };
+    Ok((location, git_rev))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::{Signature, Tree};
+    use std::path::Path;
+
+    /// Creates a tree from the given files and submodule commits
+    fn tree<'a>(
+        repo: &'a Repository,
+        files: &[(&str, &str)],
+        submodules: &[(&str, Oid)],
+    ) -> Tree<'a> {
+        let entry = |path: &str, mode: u32, id: Oid| git2::IndexEntry {
+            ctime: git2::IndexTime::new(0, 0),
+            mtime: git2::IndexTime::new(0, 0),
+            dev: 0,
+            ino: 0,
+            mode,
+            uid: 0,
+            gid: 0,
+            file_size: 0,
+            id,
+            flags: path.len() as u16,
+            flags_extended: 0,
+            path: path.as_bytes().to_vec(),
+        };
+
+        let mut index = git2::Index::new().unwrap();
+        for (path, content) in files {
+            let blob = repo.blob(content.as_bytes()).unwrap();
+            index.add(&entry(path, 0o100644, blob)).unwrap();
+        }
+        for (path, commit) in submodules {
+            index.add(&entry(path, 0o160000, *commit)).unwrap();
+        }
+        let oid = index.write_tree_to(repo).unwrap();
+        repo.find_tree(oid).unwrap()
+    }
+
+    /// Adds a commit with the given files to the branch and returns its id
+    fn commit(repo: &Repository, branch: &str, files: &[(&str, &str)]) -> Oid {
+        commit_with_submodules(repo, branch, files, &[])
+    }
+
+    fn commit_with_submodules(
+        repo: &Repository,
+        branch: &str,
+        files: &[(&str, &str)],
+        submodules: &[(&str, Oid)],
+    ) -> Oid {
+        let signature = Signature::now("rip", "rip@example.com").unwrap();
+        let refname = format!("refs/heads/{branch}");
+        let parent = repo
+            .find_reference(&refname)
+            .ok()
+            .map(|reference| reference.peel_to_commit().unwrap());
+        repo.commit(
+            Some(&refname),
+            &signature,
+            &signature,
+            "commit",
+            &tree(repo, files, submodules),
+            &parent.iter().collect::<Vec<_>>(),
+        )
+        .unwrap()
+    }
+
+    /// Creates a bare repository with the history:
+    ///
+    /// * `main`: two commits, the first one is tagged with the annotated tag `v1`
+    /// * `feature`: a single commit with a `pkg` subdirectory
+    fn create_repository(path: &Path) -> (Oid, Oid, Oid) {
+        let repo = Repository::init_bare(path).unwrap();
+        let first = commit(&repo, "main", &[("version.txt", "1")]);
+        let second = commit(&repo, "main", &[("version.txt", "2")]);
+        let feature = commit(
+            &repo,
+            "feature",
+            &[("root.txt", "root"), ("pkg/version.txt", "feature")],
+        );
+        repo.set_head("refs/heads/main").unwrap();
+
+        let signature = Signature::now("rip", "rip@example.com").unwrap();
+        repo.tag(
+            "v1",
+            &repo.find_object(first, None).unwrap(),
+            &signature,
+            "v1",
+            false,
+        )
+        .unwrap();
+
+        (first, second, feature)
+    }
+
+    /// Clones the repository `repo.git` in `dir` using the cache `dir/cache`
+    fn try_clone(
+        dir: &Path,
+        rev: Option<&str>,
+        subdirectory: Option<&str>,
+    ) -> Result<(PathBuf, GitRev), SourceError> {
+        git_clone(
+            &GitSource {
+                url: GitUrl::Path(dir.join("repo.git")),
+                rev: rev.map(ToOwned::to_owned),
+                subdirectory: subdirectory.map(ToOwned::to_owned),
+            },
+            &dir.join("cache"),
+        )
+    }
+
+    fn clone(dir: &Path, rev: Option<&str>, subdirectory: Option<&str>) -> (PathBuf, GitRev) {
+        try_clone(dir, rev, subdirectory).unwrap()
+    }
+
+    fn read(path: &Path) -> String {
+        fs_err::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn clone_head() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_, second, _) = create_repository(&dir.path().join("repo.git"));
+
+        let (checkout, rev) = clone(dir.path(), None, None);
+        assert_eq!(read(&checkout.join("version.txt")), "2");
+        assert_eq!(rev, GitRev::Commit(second.to_string()));
+        assert!(!rev.is_head());
+        assert!(GitRev::default().is_head());
+    }
+
+    #[test]
+    fn shallow_fetches_are_only_used_for_remotes() {
+        assert!(supports_shallow_fetch(
+            "https://github.com/pallets/flask.git"
+        ));
+        assert!(supports_shallow_fetch(
+            "ssh://git@github.com/pallets/flask.git"
+        ));
+        assert!(!supports_shallow_fetch("file:///tmp/repo.git"));
+        assert!(!supports_shallow_fetch("/tmp/repo.git"));
+    }
+
+    #[test]
+    fn clone_branch_and_tag() {
+        let dir = tempfile::tempdir().unwrap();
+        let (first, _, feature) = create_repository(&dir.path().join("repo.git"));
+
+        let (checkout, rev) = clone(dir.path(), Some("feature"), None);
+        assert_eq!(read(&checkout.join("pkg/version.txt")), "feature");
+        assert!(!checkout.join("version.txt").exists());
+        assert_eq!(rev, GitRev::Branch(feature.to_string()));
+
+        let (checkout, rev) = clone(dir.path(), Some("v1"), None);
+        assert_eq!(read(&checkout.join("version.txt")), "1");
+        assert_eq!(rev, GitRev::Tag(first.to_string()));
+    }
+
+    #[test]
+    fn clone_commit() {
+        let dir = tempfile::tempdir().unwrap();
+        let (first, _, _) = create_repository(&dir.path().join("repo.git"));
 
-    if let Some(cmd) = cmd {
-        let output = cmd
-            .current_dir(&cache_path)
-            .output()
-            .map_err(|_| SourceError::GitErrorStr("git checkout failed"))?;
-
-        if !output.status.success() {
-            tracing::error!(
-                "Command failed: `git checkout \"{}\"`",
-                &git_rev.to_string()
-            );
-            return Err(SourceError::GitErrorStr(
-                "failed to checkout for a valid rev",
-            ));
+        // A commit that isn't the tip of a branch, by its full and abbreviated hash
+        let full = first.to_string();
+        for rev in [full.as_str(), &full[..8]] {
+            let (checkout, rev) = clone(dir.path(), Some(rev), None);
+            assert_eq!(read(&checkout.join("version.txt")), "1");
+            assert_eq!(rev, GitRev::Commit(full.clone()));
         }
+
+        let err = try_clone(dir.path(), Some("does-not-exist"), None).unwrap_err();
+        assert!(matches!(err, SourceError::UnknownRevision(rev) if rev == "does-not-exist"));
+    }
+
+    #[test]
+    fn clone_subdirectory() {
+        let dir = tempfile::tempdir().unwrap();
+        create_repository(&dir.path().join("repo.git"));
+
+        let (location, _) = clone(dir.path(), Some("feature"), Some("pkg"));
+        assert_eq!(read(&location.join("version.txt")), "feature");
+        assert_eq!(read(&location.join("../root.txt")), "root");
+
+        let err = try_clone(dir.path(), Some("feature"), Some("missing")).unwrap_err();
+        assert!(matches!(err, SourceError::MissingSubdirectory(_)));
+    }
+
+    #[test]
+    fn pinned_commits_are_checked_out_from_the_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_, second, _) = create_repository(&dir.path().join("repo.git"));
+
+        let (checkout, _) = clone(dir.path(), None, None);
+        assert_eq!(
+            checkout,
+            dir.path()
+                .join("cache/git/checkouts")
+                .join(cache_name(&GitUrl::Path(dir.path().join("repo.git"))).unwrap())
+                .join(second.to_string())
+        );
+
+        // Without the remote the commit is still available
+        fs::remove_dir_all(dir.path().join("repo.git")).unwrap();
+        let (pinned_checkout, rev) = clone(dir.path(), Some(&second.to_string()), None);
+        assert_eq!(pinned_checkout, checkout);
+        assert_eq!(rev, GitRev::Commit(second.to_string()));
+
+        // An incomplete checkout is replaced
+        fs::remove_file(checkout.join(CHECKOUT_OK_MARKER)).unwrap();
+        fs::remove_file(checkout.join("version.txt")).unwrap();
+        clone(dir.path(), Some(&second.to_string()), None);
+        assert_eq!(read(&checkout.join("version.txt")), "2");
+    }
+
+    #[test]
+    fn branches_are_fetched_again() {
+        let dir = tempfile::tempdir().unwrap();
+        create_repository(&dir.path().join("repo.git"));
+        let (old_checkout, _) = clone(dir.path(), Some("main"), None);
+
+        let repo = Repository::open_bare(dir.path().join("repo.git")).unwrap();
+        let third = commit(&repo, "main", &[("version.txt", "3")]);
+
+        let (checkout, rev) = clone(dir.path(), Some("main"), None);
+        assert_eq!(rev, GitRev::Branch(third.to_string()));
+        assert_eq!(read(&checkout.join("version.txt")), "3");
+        assert_eq!(read(&old_checkout.join("version.txt")), "2");
+    }
+
+    #[test]
+    fn cache_name_is_normalized() {
+        let name = |url: &str| cache_name(&GitUrl::Url(url.parse().unwrap())).unwrap();
+        assert_eq!(
+            name("https://github.com/pallets/flask.git"),
+            name("HTTPS://GitHub.com/pallets/flask/")
+        );
+        assert_ne!(
+            name("https://github.com/pallets/flask.git"),
+            name("https://github.com/pallets/click.git")
+        );
+        assert!(name("https://github.com/pallets/flask.git").starts_with("flask-"));
     }
 
-    // update submodules
-    if cache_path.join(".gitmodules").exists() {
-        let mut submodule = git_command("submodule");
-        let output = submodule
-            .current_dir(&cache_path)
-            .arg("update")
-            .args(["--init", "--recursive", "-q"])
-            .output()
-            .map_err(|_| SourceError::GitErrorStr("git submodule update failed"))?;
+    #[test]
+    fn parse_url() {
+        let parsed = ParsedUrl::new(
+            &"git+https://github.com/pallets/flask.git@3.0.0#subdirectory=src"
+                .parse()
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(parsed.url, "https://github.com/pallets/flask.git");
+        assert_eq!(parsed.revision.as_deref(), Some("3.0.0"));
+        assert_eq!(parsed.subdirectory.as_deref(), Some("src"));
+
+        let parsed = ParsedUrl::new(&"git+file:///tmp/repo.git@main".parse().unwrap()).unwrap();
+        assert_eq!(parsed.git_url, GitUrl::Path(PathBuf::from("/tmp/repo.git")));
+        assert_eq!(parsed.revision.as_deref(), Some("main"));
+
+        let parsed = ParsedUrl::new(
+            &"git+ssh://git@github.com/pallets/flask.git@main"
+                .parse()
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(parsed.vcs, DirectUrlVcs::Git);
+        assert_eq!(parsed.url, "ssh://git@github.com/pallets/flask.git");
+        assert_eq!(parsed.revision.as_deref(), Some("main"));
 
-        if !output.status.success() {
-            return Err(SourceError::GitErrorStr("failed to update git module"));
+        for (url, vcs, clean_url) in [
+            (
+                "hg+https://hg.example.com/repo@1.0",
+                DirectUrlVcs::Mercurial,
+                "https://hg.example.com/repo",
+            ),
+            (
+                "svn+svn://svn.example.com/repo/trunk@1234",
+                DirectUrlVcs::Svn,
+                "svn://svn.example.com/repo/trunk",
+            ),
+            (
+                "bzr+https://bzr.example.com/repo@42",
+                DirectUrlVcs::Bazaar,
+                "https://bzr.example.com/repo",
+            ),
+        ] {
+            let parsed = ParsedUrl::new(&url.parse().unwrap()).unwrap();
+            assert_eq!(parsed.vcs, vcs);
+            assert_eq!(parsed.url, clean_url);
         }
+        assert!(ParsedUrl::new(&"https://example.com/repo".parse().unwrap()).is_err());
     }
 
-    Ok((cache_path, git_rev))
+    #[test]
+    fn pin_url() {
+        let commit = "47c8046492d4db49f163bb977d20d5942e4ddb25";
+        let url: Url = "git+https://github.com/pallets/flask.git@main#subdirectory=src"
+            .parse()
+            .unwrap();
+        let pinned = pinned_url(&url, commit);
+        assert_eq!(
+            pinned.as_str(),
+            format!("git+https://github.com/pallets/flask.git@{commit}#subdirectory=src&requested_revision=main")
+        );
+        assert!(is_pinned_url_of(&url, &pinned));
+        // Pinning a pinned url doesn't change it
+        assert_eq!(pinned_url(&pinned, commit), pinned);
+
+        let unpinned: Url = "git+https://github.com/pallets/flask.git#subdirectory=src"
+            .parse()
+            .unwrap();
+        let pinned_head = pinned_url(&unpinned, commit);
+        assert_eq!(
+            pinned_head.as_str(),
+            format!("git+https://github.com/pallets/flask.git@{commit}#subdirectory=src")
+        );
+        assert!(is_pinned_url_of(&unpinned, &pinned_head));
+        assert!(is_pinned_url_of(
+            &pinned_url(&unpinned, &commit[..8]),
+            &pinned
+        ));
+
+        // A commit pinned from one branch or tag doesn't satisfy another one
+        assert!(!is_pinned_url_of(&unpinned, &pinned));
+        assert!(!is_pinned_url_of(&url, &pinned_head));
+        let v1: Url = "git+https://github.com/pallets/flask.git@v1"
+            .parse()
+            .unwrap();
+        let v2: Url = "git+https://github.com/pallets/flask.git@v2"
+            .parse()
+            .unwrap();
+        assert!(is_pinned_url_of(&v1, &pinned_url(&v1, commit)));
+        assert!(!is_pinned_url_of(&v2, &pinned_url(&v1, commit)));
+
+        // A different commit, repository or subdirectory
+        let other_commit = "0000000000000000000000000000000000000000";
+        assert!(!is_pinned_url_of(&pinned_url(&url, other_commit), &pinned));
+        let other_repository: Url = "git+https://github.com/pallets/click.git".parse().unwrap();
+        assert!(!is_pinned_url_of(&other_repository, &pinned));
+        let other_subdirectory: Url = "git+https://github.com/pallets/flask.git@main"
+            .parse()
+            .unwrap();
+        assert!(!is_pinned_url_of(&other_subdirectory, &pinned));
+
+        // The requested url must be pinned to a commit
+        assert!(!is_pinned_url_of(&pinned, &url));
+
+        // Subversion revisions are numbers
+        let svn: Url = "svn+https://svn.example.com/repo/trunk".parse().unwrap();
+        assert!(is_pinned_url_of(&svn, &pinned_url(&svn, "1234")));
+        assert!(!is_pinned_url_of(
+            &pinned_url(&svn, "1233"),
+            &pinned_url(&svn, "1234")
+        ));
+        assert!(!is_pinned_url_of(&url, &pinned_url(&svn, "1234")));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn authentication_failures_are_reported() {
+        use axum::http::{header, StatusCode};
+        use axum::response::IntoResponse;
+        use std::future::IntoFuture;
+
+        // A server that requires credentials for `/private` and doesn't know any other repository
+        let router = axum::Router::new().fallback(|uri: axum::http::Uri| async move {
+            if uri.path().starts_with("/private") {
+                (
+                    StatusCode::UNAUTHORIZED,
+                    [(header::WWW_AUTHENTICATE, "Basic realm=\"git\"")],
+                )
+                    .into_response()
+            } else {
+                StatusCode::NOT_FOUND.into_response()
+            }
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(axum::serve(listener, router).into_future());
+
+        let clone = |path: &str| {
+            let url = format!("http://{address}/{path}").parse().unwrap();
+            tokio::task::spawn_blocking(move || {
+                let dir = tempfile::tempdir().unwrap();
+                git_clone(
+                    &GitSource {
+                        url: GitUrl::Url(url),
+                        rev: None,
+                        subdirectory: None,
+                    },
+                    dir.path(),
+                )
+            })
+        };
+
+        let err = clone("private/repo.git").await.unwrap().unwrap_err();
+        assert!(
+            matches!(err, SourceError::AuthenticationFailed { .. }),
+            "{err:?}"
+        );
+        let err = clone("missing/repo.git").await.unwrap().unwrap_err();
+        assert!(
+            matches!(err, SourceError::RepositoryNotFound { .. }),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn missing_local_repository() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = try_clone(dir.path(), None, None).unwrap_err();
+        assert!(
+            matches!(err, SourceError::RepositoryNotFound { .. }),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn clone_submodules() {
+        let dir = tempfile::tempdir().unwrap();
+        let sub_path = dir.path().join("sub.git");
+        let sub = Repository::init_bare(&sub_path).unwrap();
+        let sub_commit = commit(&sub, "main", &[("sub.txt", "sub")]);
+        sub.set_head("refs/heads/main").unwrap();
+
+        let repo = Repository::init_bare(dir.path().join("repo.git")).unwrap();
+        let gitmodules = format!(
+            "[submodule \"vendored\"]\n\tpath = vendored\n\turl = {}\n",
+            sub_path.display()
+        );
+        commit_with_submodules(
+            &repo,
+            "main",
+            &[(".gitmodules", &gitmodules)],
+            &[("vendored", sub_commit)],
+        );
+        repo.set_head("refs/heads/main").unwrap();
+
+        let (checkout, _) = clone(dir.path(), None, None);
+        assert_eq!(read(&checkout.join("vendored/sub.txt")), "sub");
+    }
 }
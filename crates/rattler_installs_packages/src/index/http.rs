@@ -3,11 +3,16 @@ use super::file_store::FileStore;
 use super::package_database::NotCached;
 use crate::utils::{ReadAndSeek, SeekSlice, StreamingOrLocal};
 use bytes::Bytes;
+use futures::stream::BoxStream;
 use futures::{Stream, StreamExt, TryStreamExt};
 use http_cache_semantics::{AfterResponse, BeforeRequest, CachePolicy};
 use miette::Diagnostic;
-use reqwest::header::{ACCEPT, CACHE_CONTROL};
-use reqwest::{header::HeaderMap, Method};
+use rand::Rng;
+use reqwest::header::{
+    HeaderValue, ACCEPT, ACCEPT_RANGES, CACHE_CONTROL, CONTENT_RANGE, ETAG, IF_RANGE,
+    LAST_MODIFIED, RANGE, RETRY_AFTER,
+};
+use reqwest::{header::HeaderMap, Method, StatusCode};
 use reqwest_middleware::ClientWithMiddleware;
 use serde::{Deserialize, Serialize};
 use std::io;
@@ -16,12 +21,13 @@ use std::io::BufWriter;
 use std::io::{Read, Seek, SeekFrom, Write};
 use std::str::FromStr;
 use std::sync::Arc;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 use thiserror::Error;
 use tokio_util::compat::FuturesAsyncReadCompatExt;
 use url::Url;
 
-const CURRENT_VERSION: u8 = 1;
+// Version 2 is stored in a file store with checksums
+const CURRENT_VERSION: u8 = 2;
 const CACHE_BOM: &str = "RIP";
 
 // Attached to HTTP responses, to make testing easier
@@ -45,10 +51,56 @@ pub enum CacheMode {
     NoStore,
 }
 
+/// Defines how requests that failed because of a connection error or a transient server error
+/// (5xx, 408 or 429) are retried. Only idempotent requests are retried.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The maximum number of times a request is retried
+    pub max_retries: u32,
+
+    /// The time to wait before the first retry, the time is doubled for every next retry. A
+    /// random jitter of up to half the time is subtracted to spread out retries.
+    pub initial_backoff: Duration,
+
+    /// The maximum time to wait before a retry, this also limits the time requested by a
+    /// `Retry-After` header.
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries a request
+    pub fn no_retries() -> Self {
+        Self {
+            max_retries: 0,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the time to wait before the given retry, the first retry is `0`.
+    fn backoff(&self, retry: u32) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_backoff);
+        backoff.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Http {
     pub(crate) client: ClientWithMiddleware,
     http_cache: Arc<FileStore>,
+    retry_policy: RetryPolicy,
 }
 
 #[derive(Debug, Error, Diagnostic)]
@@ -76,9 +128,104 @@ impl Http {
         Http {
             client,
             http_cache: Arc::new(http_cache),
+            retry_policy: RetryPolicy::default(),
         }
     }
 
+    /// Returns the store in which responses are cached.
+    pub(crate) fn cache(&self) -> &FileStore {
+        &self.http_cache
+    }
+
+    /// Sets the policy that determines how failed requests are retried.
+    pub fn with_retry_policy(self, retry_policy: RetryPolicy) -> Self {
+        Self {
+            retry_policy,
+            ..self
+        }
+    }
+
+    /// Executes a request. Idempotent requests that fail because of a connection error or a
+    /// transient server error are retried according to the [`RetryPolicy`], if all retries fail
+    /// the last response is returned.
+    async fn execute(
+        &self,
+        request: reqwest::Request,
+    ) -> Result<reqwest::Response, HttpRequestError> {
+        let idempotent = matches!(*request.method(), Method::GET | Method::HEAD);
+        let mut retry = 0;
+        loop {
+            let attempt = match request
+                .try_clone()
+                .filter(|_| idempotent && retry < self.retry_policy.max_retries)
+            {
+                Some(attempt) => attempt,
+                None => return Ok(self.client.execute(request).await?),
+            };
+
+            let backoff = match self.client.execute(attempt).await {
+                Ok(response) if is_transient_status(response.status()) => {
+                    tracing::warn!(url=%request.url(), status=%response.status(), "request failed, retrying");
+                    retry_after(response.headers())
+                        .map(|retry_after| retry_after.min(self.retry_policy.max_backoff))
+                        .unwrap_or_else(|| self.retry_policy.backoff(retry))
+                }
+                Err(err) if is_transient_error(&err) => {
+                    tracing::warn!(url=%request.url(), "request failed, retrying: {err}");
+                    self.retry_policy.backoff(retry)
+                }
+                result => return Ok(result?),
+            };
+            tokio::time::sleep(backoff).await;
+            retry += 1;
+        }
+    }
+
+    /// Returns the body of a response. If the connection fails while the body of a `GET` request
+    /// is read, the rest of the body is requested with a range request if the server supports it.
+    fn body_stream(
+        &self,
+        method: &Method,
+        headers: &HeaderMap,
+        response: reqwest::Response,
+    ) -> BoxStream<'static, reqwest::Result<Bytes>> {
+        // A validator ensures that the rest of the body belongs to the same content
+        let validator = response
+            .headers()
+            .get(ETAG)
+            .filter(|etag| !etag.as_bytes().starts_with(b"W/"))
+            .or_else(|| response.headers().get(LAST_MODIFIED))
+            .cloned();
+        let accepts_ranges = response
+            .headers()
+            .get(ACCEPT_RANGES)
+            .map_or(false, |value| value.as_bytes() == b"bytes");
+        let validator = match validator {
+            Some(validator)
+                if *method == Method::GET
+                    && response.status() == StatusCode::OK
+                    && accepts_ranges
+                    && self.retry_policy.max_retries > 0 =>
+            {
+                validator
+            }
+            _ => return response.bytes_stream().boxed(),
+        };
+
+        let body = ResumableBody {
+            request: ResumeRequest {
+                http: self.clone(),
+                url: response.url().clone(),
+                headers: headers.clone(),
+                validator,
+            },
+            stream: Some(response.bytes_stream().boxed()),
+            received: 0,
+            retries: 0,
+        };
+        futures::stream::unfold(body, ResumableBody::next).boxed()
+    }
+
     /// Performs a single request caching the result internally if requested.
     pub async fn request(
         &self,
@@ -97,19 +244,23 @@ impl Http {
             .build()?;
 
         if cache_mode == CacheMode::NoStore {
-            let mut response =
-                convert_response(self.client.execute(request).await?.error_for_status()?)
-                    .map(body_to_streaming_or_local);
+            let response = self.execute(request).await?.error_for_status()?;
+            let mut response = self
+                .convert_response(&method, &headers, response)
+                .map(body_to_streaming_or_local);
 
             // Add the `CacheStatus` to the response
             response.extensions_mut().insert(CacheStatus::Uncacheable);
 
             Ok(response)
         } else {
-            let key = key_for_request(&url, method, &headers);
+            let key = key_for_request(&url, method.clone(), &headers);
             let lock = self.http_cache.lock(&key.as_slice()).await?;
 
-            if let Some((old_policy, final_url, old_body)) = lock.reader().and_then(|reader| {
+            // The body is often read out of order (e.g. wheels are zip archives) and long after it
+            // was returned. Verify it up front so a corrupted entry is fetched again instead.
+            if let Some((old_policy, final_url, old_body)) = lock.reader().and_then(|mut reader| {
+                reader.verify().ok()?;
                 read_cache(reader.detach_unlocked(), CACHE_BOM, CURRENT_VERSION).ok()
             }) {
                 match old_policy.before_request(&request, SystemTime::now()) {
@@ -135,7 +286,6 @@ impl Http {
                         // to date or not.
                         let request = convert_request(self.client.clone(), new_parts)?;
                         let response = self
-                            .client
                             .execute(request.try_clone().expect("clone of request cannot fail"))
                             .await?;
                         let final_url = response.url().clone();
@@ -158,14 +308,16 @@ impl Http {
                                     let new_body = fill_cache_async(
                                         &new_policy,
                                         &final_url,
-                                        response.bytes_stream(),
+                                        self.body_stream(&method, &headers, response),
                                         lock,
                                     )
                                     .await?;
                                     StreamingOrLocal::Local(Box::new(new_body))
                                 } else {
                                     lock.remove()?;
-                                    body_to_streaming_or_local(response.bytes_stream())
+                                    body_to_streaming_or_local(
+                                        self.body_stream(&method, &headers, response),
+                                    )
                                 };
                                 Ok(make_response(
                                     parts,
@@ -183,12 +335,11 @@ impl Http {
                 }
 
                 let response = self
-                    .client
                     .execute(request.try_clone().expect("failed to clone request?"))
                     .await?
                     .error_for_status()?;
                 let final_url = response.url().clone();
-                let response = convert_response(response);
+                let response = self.convert_response(&method, &headers, response);
 
                 let new_policy = CachePolicy::new(&request, &response);
                 let (parts, body) = response.into_parts();
@@ -203,6 +354,32 @@ impl Http {
             }
         }
     }
+
+    /// Converts a `reqwest::Response` into a `http::Response`, see [`Http::body_stream`].
+    fn convert_response(
+        &self,
+        method: &Method,
+        headers: &HeaderMap,
+        mut response: reqwest::Response,
+    ) -> http::response::Response<BoxStream<'static, reqwest::Result<Bytes>>> {
+        let mut builder = http::Response::builder()
+            .version(response.version())
+            .status(response.status());
+
+        // Take the headers from the response
+        let response_headers = builder.headers_mut().unwrap();
+        *response_headers = std::mem::take(response.headers_mut());
+        std::mem::swap(response.headers_mut(), response_headers);
+
+        // Take the extensions from the response
+        let extensions = builder.extensions_mut().unwrap();
+        *extensions = std::mem::take(response.extensions_mut());
+        extensions.insert(response.url().clone());
+
+        builder
+            .body(self.body_stream(method, headers, response))
+            .expect("building should never fail")
+    }
 }
 
 /// Constructs a `http::Response` from parts.
@@ -262,11 +439,18 @@ where
     verify_cache_bom_and_version(&mut buff_reader, bom_key, version)?;
 
     let mut struct_size_buffer = [0; 8];
-    buff_reader.read_exact(&mut struct_size_buffer).unwrap();
+    buff_reader.read_exact(&mut struct_size_buffer)?;
 
-    let data: CacheData = ciborium::de::from_reader(buff_reader).unwrap();
+    let data: CacheData = ciborium::de::from_reader(buff_reader)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
     let start = u64::from_le_bytes(struct_size_buffer);
     let end = f.seek(SeekFrom::End(0))?;
+    if start > end {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "the body starts after the end of the file",
+        ));
+    }
 
     let mut body = SeekSlice::new(f, start, end)?;
     body.rewind()?;
@@ -397,28 +581,6 @@ fn convert_request(
         .build()
 }
 
-fn convert_response(
-    mut response: reqwest::Response,
-) -> http::response::Response<impl Stream<Item = reqwest::Result<Bytes>>> {
-    let mut builder = http::Response::builder()
-        .version(response.version())
-        .status(response.status());
-
-    // Take the headers from the response
-    let headers = builder.headers_mut().unwrap();
-    *headers = std::mem::take(response.headers_mut());
-    std::mem::swap(response.headers_mut(), headers);
-
-    // Take the extensions from the response
-    let extensions = builder.extensions_mut().unwrap();
-    *extensions = std::mem::take(response.extensions_mut());
-    extensions.insert(response.url().clone());
-
-    builder
-        .body(response.bytes_stream())
-        .expect("building should never fail")
-}
-
 fn body_to_streaming_or_local(
     stream: impl Stream<Item = reqwest::Result<Bytes>> + Send + Unpin + 'static,
 ) -> StreamingOrLocal {
@@ -430,10 +592,125 @@ fn body_to_streaming_or_local(
     ))
 }
 
+/// Returns true if a request that returned the status might succeed when it is retried.
+fn is_transient_status(status: StatusCode) -> bool {
+    status.is_server_error()
+        || status == StatusCode::TOO_MANY_REQUESTS
+        || status == StatusCode::REQUEST_TIMEOUT
+}
+
+/// Returns true if a request that failed with the error might succeed when it is retried.
+fn is_transient_error(err: &reqwest_middleware::Error) -> bool {
+    match err {
+        reqwest_middleware::Error::Reqwest(err) => {
+            err.is_connect() || err.is_timeout() || err.is_request()
+        }
+        reqwest_middleware::Error::Middleware(_) => false,
+    }
+}
+
+/// Parses the `Retry-After` header, which either contains a number of seconds or a date.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?;
+    if let Ok(seconds) = value.trim().parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(date.duration_since(SystemTime::now()).unwrap_or_default())
+}
+
+/// The body of a response that is resumed with a range request if reading it fails midway.
+struct ResumableBody {
+    request: ResumeRequest,
+    stream: Option<BoxStream<'static, reqwest::Result<Bytes>>>,
+    received: u64,
+    retries: u32,
+}
+
+impl ResumableBody {
+    /// Returns the next chunk of the body, used with [`futures::stream::unfold`].
+    async fn next(mut self) -> Option<(reqwest::Result<Bytes>, Self)> {
+        loop {
+            match self.stream.as_mut()?.next().await? {
+                Ok(bytes) => {
+                    self.received += bytes.len() as u64;
+                    return Some((Ok(bytes), self));
+                }
+                Err(err) if self.retries < self.request.http.retry_policy.max_retries => {
+                    tracing::warn!(url=%self.request.url, "download failed after {} bytes, resuming: {err}", self.received);
+                    let backoff = self.request.http.retry_policy.backoff(self.retries);
+                    tokio::time::sleep(backoff).await;
+                    self.retries += 1;
+                    match self.request.resume(self.received).await {
+                        Ok(stream) => self.stream = Some(stream),
+                        Err(resume_err) => {
+                            tracing::warn!(url=%self.request.url, "could not resume download: {resume_err}");
+                            self.stream = None;
+                            return Some((Err(err), self));
+                        }
+                    }
+                }
+                Err(err) => {
+                    self.stream = None;
+                    return Some((Err(err), self));
+                }
+            }
+        }
+    }
+}
+
+/// The request that is used to resume a [`ResumableBody`].
+struct ResumeRequest {
+    http: Http,
+    url: Url,
+    headers: HeaderMap,
+    validator: HeaderValue,
+}
+
+impl ResumeRequest {
+    /// Requests the part of the body after the first `received` bytes.
+    async fn resume(
+        &self,
+        received: u64,
+    ) -> Result<BoxStream<'static, reqwest::Result<Bytes>>, HttpRequestError> {
+        let mut headers = self.headers.clone();
+        headers.insert(
+            RANGE,
+            HeaderValue::from_str(&format!("bytes={received}-"))
+                .expect("range should be a valid header value"),
+        );
+        headers.insert(IF_RANGE, self.validator.clone());
+        let request = self
+            .http
+            .client
+            .request(Method::GET, self.url.clone())
+            .headers(headers)
+            .build()?;
+        let response = self.http.execute(request).await?;
+
+        // The server returns the whole body if the content changed
+        let expected_range = format!("bytes {received}-");
+        let resumed = response.status() == StatusCode::PARTIAL_CONTENT
+            && response
+                .headers()
+                .get(CONTENT_RANGE)
+                .and_then(|range| range.to_str().ok())
+                .map_or(false, |range| range.starts_with(&expected_range));
+        if !resumed {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("the server responded with {}", response.status()),
+            )
+            .into());
+        }
+        Ok(response.bytes_stream().boxed())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::index::{
-        file_store::FileStore,
+        file_store::{FileStore, TRAILER_LEN},
         http::{write_cache_bom_and_metadata, CACHE_BOM, CURRENT_VERSION},
     };
     use http::{header::CACHE_CONTROL, HeaderMap, HeaderValue, Method};
@@ -443,7 +720,19 @@ mod tests {
     use std::{fs, io::BufWriter, sync::Arc};
     use tempfile::TempDir;
 
-    use super::{key_for_request, read_cache, CacheMode, Http};
+    use super::{key_for_request, read_cache, CacheMode, CacheStatus, Http, RetryPolicy};
+    use axum::body::Body;
+    use axum::extract::State;
+    use axum::http::StatusCode;
+    use axum::response::{IntoResponse, Response};
+    use axum::routing::get;
+    use axum::Router;
+    use futures::StreamExt;
+    use std::future::IntoFuture;
+    use std::io::Read;
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
 
     fn get_http_client() -> (Arc<Http>, TempDir) {
         let tempdir = tempfile::tempdir().unwrap();
@@ -505,4 +794,196 @@ mod tests {
 
         assert!(read_again.is_err());
     }
+
+    fn fast_retries() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(100),
+        }
+    }
+
+    async fn serve(router: Router) -> SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(axum::serve(listener, router).into_future());
+        address
+    }
+
+    async fn read_body(response: http::Response<crate::utils::StreamingOrLocal>) -> Vec<u8> {
+        let mut body = Vec::new();
+        response
+            .into_body()
+            .into_local()
+            .await
+            .unwrap()
+            .read_to_end(&mut body)
+            .unwrap();
+        body
+    }
+
+    #[tokio::test]
+    async fn test_retry_transient_errors() {
+        async fn flaky(State(requests): State<Arc<AtomicUsize>>) -> Response {
+            match requests.fetch_add(1, Ordering::SeqCst) {
+                0 => StatusCode::SERVICE_UNAVAILABLE.into_response(),
+                1 => (StatusCode::TOO_MANY_REQUESTS, [("Retry-After", "0")]).into_response(),
+                _ => "ok".into_response(),
+            }
+        }
+
+        let requests = Arc::new(AtomicUsize::new(0));
+        let address = serve(
+            Router::new()
+                .route("/flaky", get(flaky))
+                .with_state(requests.clone()),
+        )
+        .await;
+        let url = url::Url::parse(&format!("http://{address}/flaky")).unwrap();
+
+        // Without retries the first error is returned
+        let (http, _tempdir) = get_http_client();
+        let http = Http::clone(&http).with_retry_policy(RetryPolicy::no_retries());
+        let result = http
+            .request(
+                url.clone(),
+                Method::GET,
+                HeaderMap::new(),
+                CacheMode::NoStore,
+            )
+            .await;
+        assert!(result.is_err());
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        requests.store(0, Ordering::SeqCst);
+        let http = http.with_retry_policy(fast_retries());
+        let response = http
+            .request(url, Method::GET, HeaderMap::new(), CacheMode::NoStore)
+            .await
+            .unwrap();
+        assert_eq!(read_body(response).await, b"ok");
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_corrupted_cache_entry_is_fetched_again() {
+        let address = serve(Router::new().route("/cached", get(|| async { "ok" }))).await;
+        let url = url::Url::parse(&format!("http://{address}/cached")).unwrap();
+        let (http, _tempdir) = get_http_client();
+        let request = || {
+            http.request(
+                url.clone(),
+                Method::GET,
+                HeaderMap::new(),
+                CacheMode::Default,
+            )
+        };
+
+        let response = request().await.unwrap();
+        assert_eq!(response.extensions().get(), Some(&CacheStatus::Miss));
+        assert_eq!(read_body(response).await, b"ok");
+        let response = request().await.unwrap();
+        assert_ne!(response.extensions().get(), Some(&CacheStatus::Miss));
+        assert_eq!(read_body(response).await, b"ok");
+
+        // Change the last byte of the body, which is followed by the trailer of the entry
+        let entries = http.http_cache.entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        let mut content = fs::read(&entries[0].path).unwrap();
+        let last = content.len() - TRAILER_LEN as usize - 1;
+        assert_eq!(content[last], b'k');
+        content[last] = b'K';
+        fs::write(&entries[0].path, content).unwrap();
+
+        // The corrupted entry is not used but fetched and stored again
+        let response = request().await.unwrap();
+        assert_eq!(response.extensions().get(), Some(&CacheStatus::Miss));
+        assert_eq!(read_body(response).await, b"ok");
+        let response = request().await.unwrap();
+        assert_ne!(response.extensions().get(), Some(&CacheStatus::Miss));
+        assert_eq!(read_body(response).await, b"ok");
+    }
+
+    #[tokio::test]
+    async fn test_resume_interrupted_download() {
+        const CONTENT: &[u8] = b"the quick brown fox jumps over the lazy dog";
+        const ETAG: &str = "\"fox\"";
+
+        async fn interrupted(
+            State(requests): State<Arc<AtomicUsize>>,
+            headers: axum::http::HeaderMap,
+        ) -> Response {
+            requests.fetch_add(1, Ordering::SeqCst);
+            let range_start = headers
+                .get("Range")
+                .and_then(|range| range.to_str().ok())
+                .and_then(|range| range.strip_prefix("bytes="))
+                .and_then(|range| range.strip_suffix('-'))
+                .and_then(|start| start.parse::<usize>().ok());
+            match range_start {
+                Some(start) => {
+                    assert_eq!(headers.get("If-Range").unwrap(), ETAG);
+                    let content_range =
+                        format!("bytes {start}-{}/{}", CONTENT.len() - 1, CONTENT.len());
+                    (
+                        StatusCode::PARTIAL_CONTENT,
+                        [
+                            ("Content-Range", content_range.as_str()),
+                            ("Accept-Ranges", "bytes"),
+                            ("ETag", ETAG),
+                        ],
+                        &CONTENT[start..],
+                    )
+                        .into_response()
+                }
+                None => {
+                    // Send the first part of the body and then drop the connection
+                    let body = futures::stream::iter([Some(&CONTENT[..10]), None]).then(
+                        |chunk| async move {
+                            match chunk {
+                                Some(chunk) => Ok(bytes::Bytes::from_static(chunk)),
+                                None => {
+                                    tokio::time::sleep(Duration::from_millis(50)).await;
+                                    Err(std::io::Error::new(
+                                        std::io::ErrorKind::Other,
+                                        "interrupted",
+                                    ))
+                                }
+                            }
+                        },
+                    );
+                    (
+                        [
+                            ("Content-Length", CONTENT.len().to_string()),
+                            ("Accept-Ranges", "bytes".to_string()),
+                            ("ETag", ETAG.to_string()),
+                        ],
+                        Body::from_stream(body),
+                    )
+                        .into_response()
+                }
+            }
+        }
+
+        let requests = Arc::new(AtomicUsize::new(0));
+        let address = serve(
+            Router::new()
+                .route("/file", get(interrupted))
+                .with_state(requests.clone()),
+        )
+        .await;
+        let url = url::Url::parse(&format!("http://{address}/file")).unwrap();
+
+        let (http, _tempdir) = get_http_client();
+        let http = Http::clone(&http).with_retry_policy(fast_retries());
+        for cache_mode in [CacheMode::NoStore, CacheMode::Default] {
+            requests.store(0, Ordering::SeqCst);
+            let response = http
+                .request(url.clone(), Method::GET, HeaderMap::new(), cache_mode)
+                .await
+                .unwrap();
+            assert_eq!(read_body(response).await, CONTENT);
+            assert_eq!(requests.load(Ordering::SeqCst), 2);
+        }
+    }
 }
@@ -3,14 +3,15 @@
 mod file_store;
 
 mod direct_url;
-mod git_interop;
+pub(crate) mod git_interop;
 pub mod html;
 mod http;
 mod package_database;
 mod package_sources;
+mod vcs_interop;
 
-pub use package_database::{ArtifactRequest, PackageDb};
+pub use package_database::{ArtifactRequest, CacheStore, CacheUsage, PackageDb};
 pub use package_sources::{PackageSources, PackageSourcesBuilder};
 
-pub use self::http::CacheMode;
+pub use self::http::{CacheMode, RetryPolicy};
 pub use html::parse_hash;
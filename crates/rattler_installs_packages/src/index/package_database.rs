@@ -1,8 +1,8 @@
 use crate::artifacts::{SDist, STree, Wheel};
-use crate::index::file_store::FileStore;
+use crate::index::file_store::{FileStore, FileStoreEntry};
 
 use crate::index::html::{parse_package_names_html, parse_project_info_html};
-use crate::index::http::{CacheMode, Http, HttpRequestError};
+use crate::index::http::{CacheMode, Http, HttpRequestError, RetryPolicy};
 use crate::index::package_sources::PackageSources;
 use crate::resolve::PypiVersion;
 use crate::types::{
@@ -10,7 +10,11 @@ use crate::types::{
     STreeFilename, WheelCoreMetadata,
 };
 
-use crate::wheel_builder::{WheelBuildError, WheelBuilder, WheelCache};
+use crate::wheel_builder::{
+    persisted_environments, remove_incomplete_environments, remove_persisted_environment,
+    PersistedEnvironment, WheelBuildError, WheelBuilder, WheelCache, WheelCacheEntry,
+    WheelCacheKey,
+};
 use crate::{
     types::ArtifactFromBytes, types::InnerAsArtifactName, types::NormalizedPackageName,
     types::WheelFilename,
@@ -27,12 +31,14 @@ use reqwest::Method;
 use reqwest::{header::CACHE_CONTROL, StatusCode};
 use reqwest_middleware::ClientWithMiddleware;
 use std::borrow::Borrow;
+use std::collections::HashSet;
 
 use std::path::PathBuf;
 
 use itertools::Itertools;
 use std::ops::Deref;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use std::{fmt::Display, io::Read, path::Path};
 
 use url::Url;
@@ -58,6 +64,75 @@ pub struct PackageDb {
     cache_dir: PathBuf,
 }
 
+/// The stores in the cache directory of a [`PackageDb`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum CacheStore {
+    /// The responses of HTTP requests, this includes the downloaded artifacts
+    Http,
+    /// The metadata of artifacts
+    Metadata,
+    /// The wheels that were built locally from sdists and source trees
+    LocalWheels,
+    /// The environments with the build requirements of sdists, if they are persisted
+    BuildEnvironments,
+}
+
+impl CacheStore {
+    /// All the stores
+    pub const ALL: [CacheStore; 4] = [
+        Self::Http,
+        Self::Metadata,
+        Self::LocalWheels,
+        Self::BuildEnvironments,
+    ];
+
+    /// The name of the directory of the store in the cache directory
+    pub fn dir_name(&self) -> &'static str {
+        match self {
+            CacheStore::Http => "http",
+            CacheStore::Metadata => "metadata",
+            CacheStore::LocalWheels => "local_wheels",
+            CacheStore::BuildEnvironments => "build-envs",
+        }
+    }
+}
+
+/// The number of entries in (a part of) the cache and their total size.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct CacheUsage {
+    /// The number of entries
+    pub entries: usize,
+    /// The total size of the entries in bytes
+    pub size: u64,
+}
+
+/// An entry in one of the cache stores
+enum CacheEntry {
+    File(CacheStore, FileStoreEntry),
+    Wheel(Box<WheelCacheEntry>),
+    BuildEnvironment(PersistedEnvironment),
+}
+
+impl CacheEntry {
+    fn size(&self) -> u64 {
+        match self {
+            CacheEntry::File(_, entry) => entry.size,
+            CacheEntry::Wheel(entry) => entry.size,
+            CacheEntry::BuildEnvironment(entry) => entry.size,
+        }
+    }
+
+    /// The last time the entry was used. Locally built wheels don't track when they are used, so
+    /// the time the wheel was built is used instead.
+    fn last_access(&self) -> SystemTime {
+        match self {
+            CacheEntry::File(_, entry) => entry.last_access,
+            CacheEntry::Wheel(entry) => entry.time,
+            CacheEntry::BuildEnvironment(entry) => entry.last_access,
+        }
+    }
+}
+
 /// Type of request to get from the `available_artifacts` function.
 pub enum ArtifactRequest {
     /// Get the available artifacts from the index.
@@ -90,11 +165,12 @@ impl PackageDb {
     ) -> miette::Result<Self> {
         let http = Http::new(
             client,
-            FileStore::new(&cache_dir.join("http")).into_diagnostic()?,
+            FileStore::new(&cache_dir.join(CacheStore::Http.dir_name())).into_diagnostic()?,
         );
 
-        let metadata_cache = FileStore::new(&cache_dir.join("metadata")).into_diagnostic()?;
-        let local_wheel_cache = WheelCache::new(cache_dir.join("local_wheels"));
+        let metadata_cache =
+            FileStore::new(&cache_dir.join(CacheStore::Metadata.dir_name())).into_diagnostic()?;
+        let local_wheel_cache = WheelCache::new(cache_dir.join(CacheStore::LocalWheels.dir_name()));
 
         Ok(Self {
             http,
@@ -106,6 +182,14 @@ impl PackageDb {
         })
     }
 
+    /// Sets the policy that determines how failed HTTP requests are retried.
+    pub fn with_retry_policy(self, retry_policy: RetryPolicy) -> Self {
+        Self {
+            http: self.http.with_retry_policy(retry_policy),
+            ..self
+        }
+    }
+
     /// Returns the cache directory
     pub fn cache_dir(&self) -> &Path {
         &self.cache_dir
@@ -116,6 +200,168 @@ impl PackageDb {
         &self.local_wheel_cache
     }
 
+    /// Returns the number of entries in a cache store and their size.
+    pub fn cache_usage(&self, store: CacheStore) -> miette::Result<CacheUsage> {
+        Ok(self
+            .cache_entries(store)?
+            .iter()
+            .fold(CacheUsage::default(), |usage, entry| CacheUsage {
+                entries: usage.entries + 1,
+                size: usage.size + entry.size(),
+            }))
+    }
+
+    /// Removes all entries from a cache store. Returns what was removed.
+    pub async fn clean_cache(&self, store: CacheStore) -> miette::Result<CacheUsage> {
+        let entries = self.cache_entries(store)?;
+        self.remove_cache_entries(entries).await
+    }
+
+    /// Verifies the integrity of the entries in a cache store and removes the entries that are
+    /// corrupted, they are downloaded or built again when they are needed. Returns what was
+    /// removed.
+    pub async fn verify_cache(&self, store: CacheStore) -> miette::Result<CacheUsage> {
+        let removed: Vec<CacheEntry> = match store {
+            CacheStore::Http => self
+                .http
+                .cache()
+                .verify()
+                .await
+                .into_diagnostic()?
+                .into_iter()
+                .map(|entry| CacheEntry::File(store, entry))
+                .collect(),
+            CacheStore::Metadata => self
+                .metadata_cache
+                .verify()
+                .await
+                .into_diagnostic()?
+                .into_iter()
+                .map(|entry| CacheEntry::File(store, entry))
+                .collect(),
+            CacheStore::LocalWheels => self
+                .local_wheel_cache
+                .verify()
+                .into_diagnostic()?
+                .into_iter()
+                .map(|entry| CacheEntry::Wheel(Box::new(entry)))
+                .collect(),
+            CacheStore::BuildEnvironments => {
+                remove_incomplete_environments(&self.build_environments_dir())
+                    .await
+                    .into_diagnostic()?
+                    .into_iter()
+                    .map(CacheEntry::BuildEnvironment)
+                    .collect()
+            }
+        };
+        Ok(CacheUsage {
+            entries: removed.len(),
+            size: removed.iter().map(CacheEntry::size).sum(),
+        })
+    }
+
+    /// Removes the entries from all cache stores that were not used for longer than `max_age`, and
+    /// then the least recently used entries until the total size of the cache is at most
+    /// `max_size` bytes. Entries that are in use by other processes are removed once they are
+    /// released. Returns what was removed.
+    pub async fn prune_cache(
+        &self,
+        max_age: Option<Duration>,
+        max_size: Option<u64>,
+    ) -> miette::Result<CacheUsage> {
+        let mut entries = Vec::new();
+        for store in CacheStore::ALL {
+            entries.extend(self.cache_entries(store)?);
+        }
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.last_access()));
+
+        let now = SystemTime::now();
+        let mut total_size = 0;
+        let mut kept_wheels = HashSet::new();
+        let mut removed = Vec::new();
+        for entry in entries {
+            // Locally built wheels can be shared between entries, their size is only counted once
+            let size = match &entry {
+                CacheEntry::Wheel(wheel) if kept_wheels.contains(wheel.integrity()) => 0,
+                entry => entry.size(),
+            };
+            let too_old = max_age.map_or(false, |max_age| {
+                now.duration_since(entry.last_access()).unwrap_or_default() > max_age
+            });
+            let too_large = max_size.map_or(false, |max_size| total_size + size > max_size);
+            if too_old || too_large {
+                removed.push(entry);
+            } else {
+                total_size += size;
+                if let CacheEntry::Wheel(wheel) = &entry {
+                    kept_wheels.insert(wheel.integrity().clone());
+                }
+            }
+        }
+        self.remove_cache_entries(removed).await
+    }
+
+    /// Returns the entries of a cache store.
+    fn cache_entries(&self, store: CacheStore) -> miette::Result<Vec<CacheEntry>> {
+        let file_entries = |file_store: &FileStore| -> miette::Result<Vec<CacheEntry>> {
+            Ok(file_store
+                .entries()
+                .into_diagnostic()?
+                .into_iter()
+                .map(|entry| CacheEntry::File(store, entry))
+                .collect())
+        };
+        match store {
+            CacheStore::Http => file_entries(self.http.cache()),
+            CacheStore::Metadata => file_entries(&self.metadata_cache),
+            CacheStore::LocalWheels => Ok(self
+                .local_wheel_cache
+                .entries()
+                .into_diagnostic()?
+                .into_iter()
+                .map(|entry| CacheEntry::Wheel(Box::new(entry)))
+                .collect()),
+            CacheStore::BuildEnvironments => {
+                Ok(persisted_environments(&self.build_environments_dir())
+                    .into_diagnostic()?
+                    .into_iter()
+                    .map(CacheEntry::BuildEnvironment)
+                    .collect())
+            }
+        }
+    }
+
+    /// The directory that contains the persisted build environments
+    fn build_environments_dir(&self) -> PathBuf {
+        self.cache_dir
+            .join(CacheStore::BuildEnvironments.dir_name())
+    }
+
+    /// Removes entries from the cache stores.
+    async fn remove_cache_entries(&self, entries: Vec<CacheEntry>) -> miette::Result<CacheUsage> {
+        let mut removed = CacheUsage::default();
+        let mut wheels = Vec::new();
+        for entry in entries {
+            removed.entries += 1;
+            removed.size += entry.size();
+            match entry {
+                CacheEntry::File(CacheStore::Http, entry) => {
+                    self.http.cache().remove(&entry).await.into_diagnostic()?
+                }
+                CacheEntry::File(_, entry) => {
+                    self.metadata_cache.remove(&entry).await.into_diagnostic()?
+                }
+                CacheEntry::Wheel(entry) => wheels.push(*entry),
+                CacheEntry::BuildEnvironment(entry) => remove_persisted_environment(&entry)
+                    .await
+                    .into_diagnostic()?,
+            }
+        }
+        self.local_wheel_cache.remove(&wheels).into_diagnostic()?;
+        Ok(removed)
+    }
+
     /// Downloads and caches information about available artifacts of a package from the index.
     pub async fn available_artifacts<'wb>(
         &self,
@@ -246,6 +492,7 @@ impl PackageDb {
             if let Some(builder) = builder {
                 let response = super::direct_url::fetch_artifact_and_metadata_by_direct_url(
                     &self.http,
+                    &self.sources,
                     artifact_info.filename.distribution_name(),
                     artifact_info.url.clone(),
                     builder,
@@ -254,12 +501,8 @@ impl PackageDb {
 
                 let whl = match response.artifact {
                     ArtifactType::Wheel(wheel) => wheel,
-                    ArtifactType::SDist(sdist) => {
-                        builder.build_wheel(&sdist).await.into_diagnostic()?
-                    }
-                    ArtifactType::STree(stree) => {
-                        builder.build_wheel(&stree).await.into_diagnostic()?
-                    }
+                    ArtifactType::SDist(sdist) => builder.build_wheel(&sdist).await?,
+                    ArtifactType::STree(stree) => builder.build_wheel(&stree).await?,
                 };
 
                 return Ok((whl, Some(response.direct_url_json)));
@@ -275,7 +518,7 @@ impl PackageDb {
                     .get_cached_artifact::<SDist>(artifact_info, CacheMode::Default)
                     .await?;
 
-                let whl = builder.build_wheel(&sdist).await.into_diagnostic()?;
+                let whl = builder.build_wheel(&sdist).await?;
 
                 let direct_url = if artifact_info.is_direct_url {
                     let direct_url_hash = if let Some(hash) = artifact_info.hashes.clone() {
@@ -311,6 +554,47 @@ impl PackageDb {
         Ok((cached_whl, None))
     }
 
+    /// Builds an editable wheel (PEP 660) for an artifact that refers to a local source tree. The
+    /// returned [`DirectUrlJson`] marks the installation as editable.
+    pub async fn get_editable_wheel(
+        &self,
+        artifact_info: &ArtifactInfo,
+        builder: &WheelBuilder,
+    ) -> miette::Result<(Wheel, DirectUrlJson)> {
+        if !artifact_info.is_direct_url || artifact_info.url.scheme() != "file" {
+            miette::bail!(
+                "only local source trees can be installed in editable mode, '{}' is not a local directory",
+                artifact_info.url
+            );
+        }
+
+        let response = super::direct_url::fetch_artifact_and_metadata_by_direct_url(
+            &self.http,
+            &self.sources,
+            artifact_info.filename.distribution_name(),
+            artifact_info.url.clone(),
+            builder,
+        )
+        .await?;
+
+        let ArtifactType::STree(stree) = response.artifact else {
+            miette::bail!(
+                "only local source trees can be installed in editable mode, '{}' is not a directory",
+                artifact_info.url
+            );
+        };
+
+        let whl = builder.build_editable(&stree).await?;
+        let direct_url_json = DirectUrlJson {
+            url: artifact_info.url.clone(),
+            source: DirectUrlSource::Dir {
+                editable: Some(true),
+            },
+        };
+
+        Ok((whl, direct_url_json))
+    }
+
     /// Get artifact directly from file, vcs, or url
     async fn get_artifact_by_direct_url<P: Into<NormalizedPackageName>>(
         &self,
@@ -326,6 +610,7 @@ impl PackageDb {
 
         let response = super::direct_url::fetch_artifact_and_metadata_by_direct_url(
             &self.http,
+            &self.sources,
             p.clone(),
             url,
             wheel_builder,
@@ -361,6 +646,27 @@ impl PackageDb {
         Ok(())
     }
 
+    /// Reads the metadata that was generated from an sdist from the cache or returns `None` if it
+    /// was not generated before.
+    pub(crate) async fn sdist_metadata_from_cache(&self, key: &WheelCacheKey) -> Option<Vec<u8>> {
+        let mut data = self.metadata_cache.get(key).await?;
+        let mut bytes = Vec::new();
+        data.read_to_end(&mut bytes).ok()?;
+        Some(bytes)
+    }
+
+    /// Writes the metadata that was generated from an sdist into the cache.
+    pub(crate) async fn put_sdist_metadata_in_cache(
+        &self,
+        key: &WheelCacheKey,
+        blob: &[u8],
+    ) -> std::io::Result<()> {
+        self.metadata_cache
+            .get_or_set(key, |w| w.write_all(blob))
+            .await?;
+        Ok(())
+    }
+
     /// Check if we already have one of the artifacts cached. Only do this if we have more than
     /// one artifact because otherwise, we'll do a request anyway if we dont have the file
     /// cached.
@@ -454,6 +760,7 @@ impl PackageDb {
                 if let Some(wheel_builder) = wheel_builder {
                     let response = super::direct_url::fetch_artifact_and_metadata_by_direct_url(
                         &self.http,
+                        &self.sources,
                         ai.filename.distribution_name(),
                         ai.url.clone(),
                         wheel_builder,
@@ -509,6 +816,7 @@ impl PackageDb {
             let metadata = if artifact_info.is_direct_url {
                 let response = super::direct_url::fetch_artifact_and_metadata_by_direct_url(
                     &self.http,
+                    &self.sources,
                     artifact_info.filename.distribution_name(),
                     artifact_info.url.clone(),
                     wheel_builder,
@@ -531,9 +839,10 @@ impl PackageDb {
                     return Ok(Some((ai, metadata)));
                 }
                 Err(err) => {
+                    let help = err.help().map(|h| format!("\n {h}")).unwrap_or_default();
                     errors.push(format!(
-                        "error while processing source distribution '{}': \n {}",
-                        artifact_info.filename, err
+                        "error while processing source distribution '{}': \n {}{}",
+                        artifact_info.filename, err, help
                     ));
                     continue;
                 }
@@ -573,6 +882,7 @@ impl PackageDb {
                 });
             let response = super::direct_url::fetch_artifact_and_metadata_by_direct_url(
                 &self.http,
+                &self.sources,
                 stree_name.distribution.clone(),
                 artifact_info.url.clone(),
                 wheel_builder,
@@ -587,9 +897,10 @@ impl PackageDb {
                     return Ok(Some((ai, metadata_and_bytes.1)));
                 }
                 Err(err) => {
+                    let help = err.help().map(|h| format!("\n {h}")).unwrap_or_default();
                     errors.push(format!(
-                        "error while processing source tree '{}': \n {}",
-                        artifact_info.filename, err
+                        "error while processing source tree '{}': \n {}{}",
+                        artifact_info.filename, err, help
                     ));
                     continue;
                 }
@@ -615,7 +926,7 @@ impl PackageDb {
 
         if let Ok((mut reader, _)) = AsyncHttpRangeReader::new(
             self.http.client.clone(),
-            artifact_info.url.clone(),
+            self.sources.rewrite_url(&artifact_info.url),
             CheckSupportMethod::Head,
         )
         .await
@@ -648,7 +959,7 @@ impl PackageDb {
             .expect("the specified artifact does not refer to type requested to read");
 
         // Turn into PEP658 compliant URL
-        let mut url = ai.url.clone();
+        let mut url = self.sources.rewrite_url(&ai.url);
         url.set_path(&url.path().replace(".whl", ".whl.metadata"));
 
         let mut bytes = Vec::new();
@@ -706,7 +1017,7 @@ impl PackageDb {
         let artifact_bytes = self
             .http
             .request(
-                artifact_info.url.clone(),
+                self.sources.rewrite_url(&artifact_info.url),
                 Method::GET,
                 HeaderMap::default(),
                 cache_mode,
@@ -783,10 +1094,13 @@ mod test {
     use tokio::task::JoinHandle;
 
     use crate::index::package_sources::PackageSourcesBuilder;
+    use crate::python_env::Pep508EnvMakers;
+    use crate::resolve::solve_options::ResolveOptions;
     use axum::response::{Html, IntoResponse};
     use axum::routing::get;
     use axum::Router;
     use insta::assert_debug_snapshot;
+    use std::collections::HashMap;
     use std::future::IntoFuture;
     use std::net::SocketAddr;
     use tower_http::add_extension::AddExtensionLayer;
@@ -974,6 +1288,230 @@ mod test {
 
         let (_artifact, _metadata) = package_db.get_pep658_metadata(artifact_info).await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_prune_cache() {
+        let (_cache_dir, package_db) = make_package_db();
+
+        for (key, metadata) in [("old", b"old metadata"), ("new", b"new metadata")] {
+            // Mark the existing entries as not used for a long time
+            for entry in package_db.cache_entries(CacheStore::Metadata).unwrap() {
+                let CacheEntry::File(_, entry) = entry else {
+                    unreachable!()
+                };
+                filetime::set_file_mtime(&entry.path, filetime::FileTime::from_unix_time(0, 0))
+                    .unwrap();
+            }
+            package_db
+                .put_sdist_metadata_in_cache(&WheelCacheKey::new("test", key), metadata)
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            package_db.cache_usage(CacheStore::Metadata).unwrap(),
+            CacheUsage {
+                entries: 2,
+                size: 112
+            }
+        );
+
+        // Nothing is removed if the cache is within budget
+        let removed = package_db.prune_cache(None, Some(200)).await.unwrap();
+        assert_eq!(removed, CacheUsage::default());
+
+        let removed = package_db.prune_cache(None, Some(100)).await.unwrap();
+        assert_eq!(
+            removed,
+            CacheUsage {
+                entries: 1,
+                size: 56
+            }
+        );
+        assert!(package_db
+            .sdist_metadata_from_cache(&WheelCacheKey::new("test", "old"))
+            .await
+            .is_none());
+        assert!(package_db
+            .sdist_metadata_from_cache(&WheelCacheKey::new("test", "new"))
+            .await
+            .is_some());
+
+        let removed = package_db.clean_cache(CacheStore::Metadata).await.unwrap();
+        assert_eq!(removed.entries, 1);
+        assert_eq!(
+            package_db.cache_usage(CacheStore::Metadata).unwrap(),
+            CacheUsage::default()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_build_environments_cache() {
+        let (cache_dir, package_db) = make_package_db();
+        let build_envs_dir = cache_dir
+            .path()
+            .join(CacheStore::BuildEnvironments.dir_name());
+        for key in ["complete", "interrupted"] {
+            std::fs::create_dir_all(build_envs_dir.join(key).join("bin")).unwrap();
+            std::fs::write(build_envs_dir.join(key).join("bin/python"), b"python").unwrap();
+        }
+        std::fs::write(build_envs_dir.join("complete.complete"), b"").unwrap();
+
+        assert_eq!(
+            package_db
+                .cache_usage(CacheStore::BuildEnvironments)
+                .unwrap(),
+            CacheUsage {
+                entries: 2,
+                size: 12
+            }
+        );
+
+        // Only the environment that was not completely created is removed
+        let removed = package_db
+            .verify_cache(CacheStore::BuildEnvironments)
+            .await
+            .unwrap();
+        assert_eq!(removed.entries, 1);
+        assert!(!build_envs_dir.join("interrupted").exists());
+        assert!(build_envs_dir.join("complete").exists());
+
+        let removed = package_db
+            .clean_cache(CacheStore::BuildEnvironments)
+            .await
+            .unwrap();
+        assert_eq!(removed.entries, 1);
+        assert!(!build_envs_dir.join("complete").exists());
+        assert!(!build_envs_dir.join("complete.complete").exists());
+    }
+
+    #[tokio::test]
+    async fn test_url_rewrite() -> anyhow::Result<()> {
+        // The index refers to a host that does not exist, the files are served by a "proxy"
+        async fn get_package() -> impl IntoResponse {
+            Html(
+                r#"<html><body>
+                <a href="https://files.example.invalid/files/rewritten-1.0-py3-none-any.whl" data-dist-info-metadata="true">rewritten-1.0-py3-none-any.whl</a>
+                </body></html>"#,
+            )
+        }
+        async fn get_metadata() -> impl IntoResponse {
+            "Metadata-Version: 2.1\nName: rewritten\nVersion: 1.0\n"
+        }
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let router = Router::new()
+            .route("/simple/rewritten/", get(get_package))
+            .route(
+                "/proxy/files/rewritten-1.0-py3-none-any.whl.metadata",
+                get(get_metadata),
+            );
+        tokio::spawn(axum::serve(listener, router).into_future());
+
+        let cache_dir = TempDir::new()?;
+        let sources = PackageSourcesBuilder::new(format!("http://{address}/simple/").parse()?)
+            .with_url_rewrite(
+                &"https://files.example.invalid/".parse()?,
+                &format!("http://{address}/proxy/").parse()?,
+            )
+            .build()?;
+        let package_db = PackageDb::new(
+            sources,
+            ClientWithMiddleware::from(Client::new()),
+            cache_dir.path(),
+        )
+        .unwrap();
+
+        let artifacts = package_db
+            .available_artifacts(ArtifactRequest::FromIndex("rewritten".parse()?))
+            .await
+            .unwrap();
+        let artifact_info = artifacts.values().flatten().cloned().collect::<Vec<_>>();
+        let (artifact_info, metadata) = package_db
+            .get_metadata(&artifact_info, None)
+            .await
+            .unwrap()
+            .unwrap();
+
+        // The artifact still refers to the original location
+        assert_eq!(
+            artifact_info.url.as_str(),
+            "https://files.example.invalid/files/rewritten-1.0-py3-none-any.whl"
+        );
+        assert_eq!(metadata.version, "1.0".parse().unwrap());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_url_rewrite_direct_url() -> anyhow::Result<()> {
+        async fn get_wheel() -> impl IntoResponse {
+            std::fs::read(
+                Path::new(env!("CARGO_MANIFEST_DIR"))
+                    .join("../../test-data/wheels/miniblack-23.1.0-py3-none-any.whl"),
+            )
+            .unwrap()
+        }
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let router = Router::new().route(
+            "/proxy/files/miniblack-23.1.0-py3-none-any.whl",
+            get(get_wheel),
+        );
+        tokio::spawn(axum::serve(listener, router).into_future());
+
+        let cache_dir = TempDir::new()?;
+        let sources = PackageSourcesBuilder::new("https://pypi.org/simple/".parse()?)
+            .with_url_rewrite(
+                &"https://files.example.invalid/".parse()?,
+                &format!("http://{address}/proxy/").parse()?,
+            )
+            .build()?;
+        let package_db = Arc::new(
+            PackageDb::new(
+                sources,
+                ClientWithMiddleware::from(Client::new()),
+                cache_dir.path(),
+            )
+            .unwrap(),
+        );
+        let env_markers = Arc::new(Pep508EnvMakers::from_env().await.unwrap().0);
+        let wheel_builder = Arc::new(
+            WheelBuilder::new(
+                package_db.clone(),
+                env_markers,
+                None,
+                ResolveOptions::default(),
+                HashMap::default(),
+            )
+            .unwrap(),
+        );
+
+        // The direct URL refers to a host that does not exist, the wheel is served by a "proxy"
+        let url: Url =
+            "https://files.example.invalid/files/miniblack-23.1.0-py3-none-any.whl".parse()?;
+        let artifacts = package_db
+            .available_artifacts(ArtifactRequest::DirectUrl {
+                name: "miniblack".parse()?,
+                url: url.clone(),
+                wheel_builder: wheel_builder.clone(),
+            })
+            .await
+            .unwrap();
+        let artifact_info = artifacts.values().flatten().next().unwrap();
+
+        // The artifact and the installed `direct_url.json` still refer to the original location
+        assert_eq!(artifact_info.url, url);
+        let (wheel, direct_url_json) = package_db
+            .get_wheel(artifact_info, Some(&wheel_builder))
+            .await
+            .unwrap();
+        assert_eq!(direct_url_json.unwrap().url, url);
+        assert_eq!(wheel.name.version, "23.1.0".parse().unwrap());
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, Diagnostic)]
@@ -19,11 +19,19 @@ pub enum PackageSourceError {
     DuplicatePackageSource(NormalizedPackageName),
 }
 
+/// Replaces the `prefix` of a URL with `replacement`
+#[derive(Debug, Clone)]
+struct UrlRewrite {
+    prefix: String,
+    replacement: String,
+}
+
 /// "Builder" pattern for creating a [`PackageSources`] instance
 pub struct PackageSourcesBuilder {
     base_source: Url,
     extra_sources: Vec<PackageSource>,
     overrides: BTreeMap<NormalizedPackageName, String>,
+    url_rewrites: Vec<UrlRewrite>,
 }
 
 impl PackageSourcesBuilder {
@@ -34,6 +42,7 @@ impl PackageSourcesBuilder {
             base_source: base_index_url,
             extra_sources: Default::default(),
             overrides: Default::default(),
+            url_rewrites: Default::default(),
         }
     }
 
@@ -53,6 +62,18 @@ impl PackageSourcesBuilder {
         self
     }
 
+    /// Add a rule that rewrites the URLs of artifacts and metadata that start with `prefix` to
+    /// start with `replacement` instead, e.g. to download the artifacts through a caching proxy.
+    /// The URLs are only rewritten when they are downloaded, the original URLs are still reported
+    /// for the artifacts. If multiple rules match, the rule with the longest prefix is used.
+    pub fn with_url_rewrite(mut self, prefix: &Url, replacement: &Url) -> Self {
+        self.url_rewrites.push(UrlRewrite {
+            prefix: prefix.to_string(),
+            replacement: replacement.to_string(),
+        });
+        self
+    }
+
     /// Finalize the builder and create a `PackageSources` instance
     pub fn build(&self) -> Result<PackageSources, PackageSourceError> {
         let mut extra_sources_map = BTreeMap::new();
@@ -91,6 +112,7 @@ impl PackageSourcesBuilder {
         Ok(PackageSources {
             index_urls: (index_url, extra_index_urls),
             artifact_to_index,
+            url_rewrites: self.url_rewrites.clone(),
         })
     }
 }
@@ -100,6 +122,7 @@ impl PackageSourcesBuilder {
 pub struct PackageSources {
     index_urls: (Url, Vec<Url>),
     artifact_to_index: BTreeMap<NormalizedPackageName, usize>,
+    url_rewrites: Vec<UrlRewrite>,
 }
 
 impl PackageSources {
@@ -123,6 +146,47 @@ impl PackageSources {
     pub fn default_index_url(&self) -> Url {
         self.index_urls.0.clone()
     }
+
+    /// Returns the URL from which an artifact or its metadata should be downloaded, see
+    /// [`PackageSourcesBuilder::with_url_rewrite`].
+    pub fn rewrite_url(&self, url: &Url) -> Url {
+        let Some((rewrite, rest)) = self
+            .url_rewrites
+            .iter()
+            .filter_map(|rewrite| {
+                url.as_str()
+                    .strip_prefix(&rewrite.prefix)
+                    // Only whole path segments match, `https://host/pypi` is not a prefix of
+                    // `https://host/pypix`
+                    .filter(|rest| {
+                        rewrite.prefix.ends_with('/')
+                            || rest.is_empty()
+                            || rest.starts_with(['/', '?', '#'])
+                    })
+                    .map(|rest| (rewrite, rest))
+            })
+            .max_by_key(|(rewrite, _)| rewrite.prefix.len())
+        else {
+            return url.clone();
+        };
+
+        // Don't introduce an empty path segment when the replacement ends with a slash
+        let rest = if rewrite.replacement.ends_with('/') {
+            rest.strip_prefix('/').unwrap_or(rest)
+        } else {
+            rest
+        };
+        match Url::parse(&format!("{}{}", rewrite.replacement, rest)) {
+            Ok(rewritten) => rewritten,
+            Err(err) => {
+                tracing::warn!(
+                    "failed to rewrite url '{url}' with prefix '{}': {err}",
+                    rewrite.prefix
+                );
+                url.clone()
+            }
+        }
+    }
 }
 
 impl From<Url> for PackageSources {
@@ -130,6 +194,7 @@ impl From<Url> for PackageSources {
         PackageSources {
             index_urls: (url, vec![]),
             artifact_to_index: Default::default(),
+            url_rewrites: Default::default(),
         }
     }
 }
@@ -171,4 +236,60 @@ mod tests {
             vec![&base_url, &foo_url, &bar_url]
         );
     }
+
+    #[test]
+    fn test_rewrite_url() {
+        let url = |url: &str| Url::parse(url).unwrap();
+
+        let sources = PackageSourcesBuilder::new(url("https://pypi.org/simple/"))
+            .with_url_rewrite(
+                &url("https://files.pythonhosted.org/"),
+                &url("https://proxy.example.com/pypi/"),
+            )
+            .with_url_rewrite(
+                &url("https://files.pythonhosted.org/packages/internal/"),
+                &url("https://internal.example.com/"),
+            )
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            sources.rewrite_url(&url(
+                "https://files.pythonhosted.org/packages/ab/cd/foo-1.0-py3-none-any.whl#sha256=1234"
+            )),
+            url("https://proxy.example.com/pypi/packages/ab/cd/foo-1.0-py3-none-any.whl#sha256=1234")
+        );
+        assert_eq!(
+            sources.rewrite_url(&url(
+                "https://files.pythonhosted.org/packages/internal/foo-1.0.tar.gz"
+            )),
+            url("https://internal.example.com/foo-1.0.tar.gz")
+        );
+        assert_eq!(
+            sources.rewrite_url(&url("https://example.com/foo-1.0.tar.gz")),
+            url("https://example.com/foo-1.0.tar.gz")
+        );
+    }
+
+    #[test]
+    fn test_rewrite_url_matches_whole_segments() {
+        let url = |url: &str| Url::parse(url).unwrap();
+
+        let sources = PackageSourcesBuilder::new(url("https://pypi.org/simple/"))
+            .with_url_rewrite(
+                &url("https://example.com/pypi"),
+                &url("https://mirror.example.com/"),
+            )
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            sources.rewrite_url(&url("https://example.com/pypi/foo-1.0.tar.gz")),
+            url("https://mirror.example.com/foo-1.0.tar.gz")
+        );
+        assert_eq!(
+            sources.rewrite_url(&url("https://example.com/pypix/foo-1.0.tar.gz")),
+            url("https://example.com/pypix/foo-1.0.tar.gz")
+        );
+    }
 }
@@ -0,0 +1,298 @@
+//! Checks out sources from Mercurial, Subversion and Bazaar repositories. Unlike git, these are
+//! accessed through their command line tools, which have to be installed.
+
+use crate::index::git_interop::{
+    cache_name, classify_message, local_repository_path, lock_exclusive, subdirectory_location,
+    FailureKind, GitSource, GitUrl, SourceError, CHECKOUT_OK_MARKER,
+};
+use crate::types::DirectUrlVcs;
+use fs_err as fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use url::Url;
+
+/// Returns the command line tool that is used for the VCS
+fn program(vcs: DirectUrlVcs) -> &'static str {
+    match vcs {
+        DirectUrlVcs::Git => "git",
+        DirectUrlVcs::Mercurial => "hg",
+        DirectUrlVcs::Svn => "svn",
+        DirectUrlVcs::Bazaar => "bzr",
+    }
+}
+
+/// Returns the arguments that check out the revision of the repository at `location` into `dest`.
+fn checkout_args(
+    vcs: DirectUrlVcs,
+    location: &str,
+    rev: Option<&str>,
+    dest: &Path,
+) -> Vec<Vec<String>> {
+    let dest = dest.to_string_lossy().to_string();
+    let rev_args = |flag: &str| {
+        rev.map(|rev| vec![flag.to_owned(), rev.to_owned()])
+            .unwrap_or_default()
+    };
+    let args = |args: &[&str]| args.iter().map(ToString::to_string).collect::<Vec<_>>();
+    match vcs {
+        DirectUrlVcs::Mercurial => vec![
+            args(&[
+                "clone",
+                "--noninteractive",
+                "--noupdate",
+                "--quiet",
+                location,
+                &dest,
+            ]),
+            [
+                args(&[
+                    "update",
+                    "--noninteractive",
+                    "--quiet",
+                    "--repository",
+                    &dest,
+                ]),
+                rev_args("--rev"),
+            ]
+            .concat(),
+        ],
+        DirectUrlVcs::Svn => vec![[
+            args(&["checkout", "--non-interactive", "--quiet"]),
+            rev_args("--revision"),
+            args(&[location, &dest]),
+        ]
+        .concat()],
+        DirectUrlVcs::Bazaar => vec![[
+            args(&["branch", "--quiet"]),
+            rev_args("--revision"),
+            args(&[location, &dest]),
+        ]
+        .concat()],
+        DirectUrlVcs::Git => unreachable!("git repositories are checked out with libgit2"),
+    }
+}
+
+/// Returns the arguments that print the commit id of the checkout in `dest`.
+fn commit_id_args(vcs: DirectUrlVcs, dest: &Path) -> Vec<String> {
+    let dest = dest.to_string_lossy().to_string();
+    let args = match vcs {
+        DirectUrlVcs::Mercurial => vec![
+            "log",
+            "--repository",
+            &dest,
+            "--rev",
+            ".",
+            "--template",
+            "{node}",
+        ],
+        DirectUrlVcs::Svn => vec!["info", "--show-item", "revision", &dest],
+        DirectUrlVcs::Bazaar => vec!["revno", &dest],
+        DirectUrlVcs::Git => unreachable!("git repositories are checked out with libgit2"),
+    };
+    args.into_iter().map(ToString::to_string).collect()
+}
+
+/// Runs the command line tool of the VCS with the given arguments and returns its output.
+fn run(vcs: DirectUrlVcs, args: &[String], location: &str) -> Result<String, SourceError> {
+    let program = program(vcs);
+    let output = Command::new(program)
+        .args(args)
+        .env("HGPLAIN", "1")
+        .output()
+        .map_err(|err| match err.kind() {
+            io::ErrorKind::NotFound => SourceError::MissingVcsTool(program),
+            _ => SourceError::Io(err),
+        })?;
+
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+        return Err(match classify_message(&message) {
+            Some(FailureKind::Authentication) => SourceError::AuthenticationFailed {
+                url: location.to_owned(),
+                message,
+            },
+            Some(FailureKind::NotFound) => SourceError::RepositoryNotFound {
+                url: location.to_owned(),
+                message,
+            },
+            Some(FailureKind::Network) => SourceError::NetworkError {
+                url: location.to_owned(),
+                message,
+            },
+            None => SourceError::VcsCommandFailed {
+                command: format!("{program} {}", args.join(" ")),
+                message,
+            },
+        });
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+/// Checks out the source from a Mercurial, Subversion or Bazaar repository into the cache
+/// directory.
+///
+/// Every commit that is used is checked out in its own directory in
+/// `cache_dir/<vcs>/checkouts`. A commit that is requested by its commit id (the full node for
+/// Mercurial, the revision number for Subversion and Bazaar) and that was checked out before is
+/// used without accessing the remote.
+///
+/// Returns the location of the checkout, or of the requested subdirectory within it, and the id
+/// of the commit that was checked out.
+pub fn vcs_checkout(
+    vcs: DirectUrlVcs,
+    source: &GitSource,
+    cache_dir: &Path,
+) -> Result<(PathBuf, String), SourceError> {
+    let location = match source.url() {
+        GitUrl::Url(url) => url.to_string(),
+        GitUrl::Path(path) => Url::from_file_path(local_repository_path(path)?)
+            .map_err(|_| SourceError::RepositoryNotFound {
+                url: path.display().to_string(),
+                message: "not an absolute path".to_owned(),
+            })?
+            .to_string(),
+    };
+    let checkouts_dir = cache_dir
+        .join(vcs.as_str())
+        .join("checkouts")
+        .join(cache_name(source.url())?);
+    fs::create_dir_all(&checkouts_dir)?;
+
+    // Make sure that other processes don't check out the same repository at the same time
+    let mut lock_name = checkouts_dir.file_name().unwrap_or_default().to_owned();
+    lock_name.push(".lock");
+    let _lock = lock_exclusive(&checkouts_dir.with_file_name(lock_name))?;
+
+    let rev = source.rev.as_deref();
+    if let Some(commit) = rev.filter(|rev| vcs.is_commit_id(rev)) {
+        let checkout = checkouts_dir.join(commit);
+        if checkout.join(CHECKOUT_OK_MARKER).is_file() {
+            let location = subdirectory_location(checkout, source.subdirectory.as_deref())?;
+            return Ok((location, commit.to_owned()));
+        }
+    }
+
+    // Check out into a temporary directory first, the commit is only known afterwards
+    let tmp_dir = tempfile::tempdir_in(&checkouts_dir)?;
+    let tmp_checkout = tmp_dir.path().join("checkout");
+    for args in checkout_args(vcs, &location, rev, &tmp_checkout) {
+        run(vcs, &args, &location)?;
+    }
+    let commit = run(vcs, &commit_id_args(vcs, &tmp_checkout), &location)?;
+    tracing::debug!("checked out {location} at {commit}");
+
+    let checkout = checkouts_dir.join(&commit);
+    if !checkout.join(CHECKOUT_OK_MARKER).is_file() {
+        if checkout.exists() {
+            fs::remove_dir_all(&checkout)?;
+        }
+        fs::rename(&tmp_checkout, &checkout)?;
+        fs::write(checkout.join(CHECKOUT_OK_MARKER), "")?;
+    }
+
+    let location = subdirectory_location(checkout, source.subdirectory.as_deref())?;
+    Ok((location, commit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checkout_commands() {
+        let dest = Path::new("/cache/checkout");
+        let commands = |vcs, rev| {
+            checkout_args(vcs, "https://example.com/repo", rev, dest)
+                .into_iter()
+                .map(|args| format!("{} {}", program(vcs), args.join(" ")))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            commands(DirectUrlVcs::Mercurial, Some("1.0")),
+            vec![
+                "hg clone --noninteractive --noupdate --quiet https://example.com/repo /cache/checkout",
+                "hg update --noninteractive --quiet --repository /cache/checkout --rev 1.0",
+            ]
+        );
+        assert_eq!(
+            commands(DirectUrlVcs::Mercurial, None)[1],
+            "hg update --noninteractive --quiet --repository /cache/checkout"
+        );
+        assert_eq!(
+            commands(DirectUrlVcs::Svn, Some("1234")),
+            vec!["svn checkout --non-interactive --quiet --revision 1234 https://example.com/repo /cache/checkout"]
+        );
+        assert_eq!(
+            commands(DirectUrlVcs::Bazaar, None),
+            vec!["bzr branch --quiet https://example.com/repo /cache/checkout"]
+        );
+    }
+
+    #[test]
+    fn checkout_is_reused_for_commit_ids() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = GitSource {
+            url: GitUrl::Url("https://hg.example.com/repo.v2".parse().unwrap()),
+            rev: Some("0123456789abcdef0123456789abcdef01234567".to_owned()),
+            subdirectory: Some("pkg".to_owned()),
+        };
+
+        // A checkout that was made before, the remote doesn't exist and hg is not needed
+        let checkout = dir
+            .path()
+            .join("hg/checkouts")
+            .join(cache_name(source.url()).unwrap())
+            .join(source.rev.as_ref().unwrap());
+        fs::create_dir_all(checkout.join("pkg")).unwrap();
+        fs::write(checkout.join(CHECKOUT_OK_MARKER), "").unwrap();
+
+        let (location, commit) =
+            vcs_checkout(DirectUrlVcs::Mercurial, &source, dir.path()).unwrap();
+        assert_eq!(location, checkout.join("pkg"));
+        assert_eq!(&commit, source.rev.as_ref().unwrap());
+
+        // The lock is stored next to the checkouts, a dot in the name is kept
+        let name = cache_name(source.url()).unwrap();
+        assert!(name.starts_with("repo.v2-"));
+        assert!(dir
+            .path()
+            .join("hg/checkouts")
+            .join(format!("{name}.lock"))
+            .is_file());
+    }
+
+    #[test]
+    fn classify_failures() {
+        let cases = [
+            ("abort: authorization failed", Some(FailureKind::Authentication)),
+            ("abort: HTTP Error 404: Not Found", Some(FailureKind::NotFound)),
+            ("abort: repository /tmp/missing not found", Some(FailureKind::NotFound)),
+            (
+                "svn: E170013: Unable to connect to a repository at URL 'https://svn.example.com/repo'\nsvn: E215004: Authentication failed and interactive prompting is disabled",
+                Some(FailureKind::Authentication),
+            ),
+            ("bzr: ERROR: Not a branch: \"/tmp/missing/\".", Some(FailureKind::NotFound)),
+            ("git@github.com: Permission denied (publickey).", Some(FailureKind::Authentication)),
+            (
+                "svn: E170013: Unable to connect to a repository at URL 'https://svn.example.com/repo'\nsvn: E670002: Name or service not known",
+                Some(FailureKind::Network),
+            ),
+            (
+                "svn: E170000: URL 'https://svn.example.com/repo/trunk' doesn't exist",
+                Some(FailureKind::NotFound),
+            ),
+            ("abort: error: Connection refused", Some(FailureKind::Network)),
+            ("remote: Repository not found.", Some(FailureKind::NotFound)),
+            // Only a missing repository is reported as not found
+            ("abort: unknown revision 'nope'", None),
+            ("abort: file not found: setup.py", None),
+            ("svn: E155007: '/tmp/x' is not a working copy", None),
+        ];
+        for (message, expected) in cases {
+            assert_eq!(classify_message(message), expected, "{message}");
+        }
+    }
+}
@@ -15,6 +15,8 @@ mod utils;
 
 pub mod resolve;
 
+pub mod requirements;
+
 pub mod wheel_builder;
 
 mod win;
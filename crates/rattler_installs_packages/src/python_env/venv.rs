@@ -9,6 +9,7 @@ use crate::python_env::{
     PythonInterpreterVersion,
 };
 use fs_err as fs;
+use std::collections::HashMap;
 use std::ffi::OsStr;
 use std::fmt::Debug;
 use std::io::ErrorKind;
@@ -79,6 +80,8 @@ pub struct VEnv {
     location: PathBuf,
     /// Install paths for this virtual environment
     install_paths: InstallPaths,
+    /// The python executable if it is not located in the scripts directory of the environment
+    python_executable: Option<PathBuf>,
 }
 
 impl VEnv {
@@ -86,9 +89,90 @@ impl VEnv {
         Self {
             location,
             install_paths,
+            python_executable: None,
         }
     }
 
+    /// Constructs the environment at `location` without inspecting it, the environment must have
+    /// been created with the given python version.
+    pub(crate) fn from_location(
+        location: PathBuf,
+        python_version: PythonInterpreterVersion,
+    ) -> Self {
+        Self::new(
+            location,
+            InstallPaths::for_venv(python_version, cfg!(windows)),
+        )
+    }
+
+    /// Open an existing environment. `path` is either the root directory of a virtual environment
+    /// or the path to a python interpreter, in which case the root of the environment is
+    /// determined from the `sys.prefix` of the interpreter.
+    pub fn open(path: &Path) -> Result<VEnv, VEnvError> {
+        let python_executable = if path.is_dir() && cfg!(windows) {
+            path.join("Scripts").join("python.exe")
+        } else if path.is_dir() {
+            path.join("bin").join("python")
+        } else {
+            path.to_path_buf()
+        };
+
+        // Ask the interpreter where packages are installed, the interpreter is not necessarily
+        // part of a virtual environment
+        let output = Command::new(&python_executable)
+            .arg("-c")
+            .arg("import json, sys, sysconfig; print(json.dumps({'prefix': sys.prefix, 'paths': sysconfig.get_paths()}))")
+            .output()?;
+        let error = |message: String| {
+            VEnvError::FailedToCreate(std::io::Error::new(
+                ErrorKind::Other,
+                format!(
+                    "could not determine the install paths of {}: {message}",
+                    python_executable.display(),
+                ),
+            ))
+        };
+        if !output.status.success() {
+            return Err(error(String::from_utf8_lossy(&output.stderr).into_owned()));
+        }
+
+        #[derive(serde::Deserialize)]
+        struct InterpreterPaths {
+            prefix: PathBuf,
+            paths: HashMap<String, PathBuf>,
+        }
+        let InterpreterPaths { prefix, paths } =
+            serde_json::from_slice(&output.stdout).map_err(|err| error(err.to_string()))?;
+        let install_paths = InstallPaths::from_sysconfig(&prefix, &paths, cfg!(windows))
+            .ok_or_else(|| error(format!("incomplete installation scheme {paths:?}")))?;
+
+        Ok(Self {
+            location: prefix,
+            install_paths,
+            python_executable: Some(python_executable),
+        })
+    }
+
+    /// Creates a copy of this environment at `target`. Files are hardlinked where possible, which
+    /// makes the copy cheap but means that files in the copy must be replaced instead of modified
+    /// in place. Scripts that refer to this environment are rewritten to refer to the copy.
+    pub fn clone_to(&self, target: &Path) -> Result<VEnv, VEnvError> {
+        let scripts = self.location.join(self.install_paths.scripts());
+        clone_dir(&self.location, target, &scripts, (&self.location, target))?;
+
+        let python_executable = self.python_executable.as_ref().map(|python| {
+            python
+                .strip_prefix(&self.location)
+                .map(|relative| target.join(relative))
+                .unwrap_or_else(|_| python.clone())
+        });
+        Ok(Self {
+            location: target.to_path_buf(),
+            install_paths: self.install_paths.clone(),
+            python_executable,
+        })
+    }
+
     /// Install a wheel into this virtual environment
     pub fn install_wheel(
         &self,
@@ -130,6 +214,9 @@ impl VEnv {
 
     /// Path to python executable in venv
     pub fn python_executable(&self) -> PathBuf {
+        if let Some(python_executable) = &self.python_executable {
+            return python_executable.clone();
+        }
         let executable = if self.install_paths.is_windows() {
             "python.exe"
         } else {
@@ -340,6 +427,75 @@ prompt = {}"#,
     }
 }
 
+/// Recursively copies `source` to `target`, see [`VEnv::clone_to`]. `environments` contains the
+/// roots of the original environment and of the copy, scripts that contain the path of the
+/// original are rewritten to contain the path of the copy instead.
+fn clone_dir(
+    source: &Path,
+    target: &Path,
+    scripts: &Path,
+    environments: (&Path, &Path),
+) -> std::io::Result<()> {
+    fs::create_dir_all(target)?;
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let path = entry.path();
+        let destination = target.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() {
+            #[cfg(unix)]
+            fs::os::unix::fs::symlink(fs::read_link(&path)?, &destination)?;
+            #[cfg(windows)]
+            fs::copy(&path, &destination)?;
+        } else if file_type.is_dir() {
+            clone_dir(&path, &destination, scripts, environments)?;
+        } else if source == scripts {
+            clone_script(&path, &destination, environments)?;
+        } else {
+            hard_link_or_copy(&path, &destination)?;
+        }
+    }
+    Ok(())
+}
+
+/// Copies a script, replacing the path of the environment in its shebang, or in the `activate*`
+/// scripts that set `VIRTUAL_ENV`, with the path of the copy. Other scripts are linked like every
+/// other file.
+fn clone_script(
+    path: &Path,
+    destination: &Path,
+    (environment, clone): (&Path, &Path),
+) -> std::io::Result<()> {
+    let (Some(environment), Some(clone)) = (environment.to_str(), clone.to_str()) else {
+        return hard_link_or_copy(path, destination);
+    };
+    let is_activate_script = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with("activate"));
+    let contents = fs::read(path)?;
+    // The interpreter might also be on the second line if its path is too long for a shebang
+    let script = (is_activate_script || contents.starts_with(b"#!"))
+        .then(|| std::str::from_utf8(&contents).ok())
+        .flatten();
+    match script {
+        Some(script) if script.contains(environment) => {
+            fs::write(destination, script.replace(environment, clone))?;
+            fs::set_permissions(destination, fs::metadata(path)?.permissions())
+        }
+        _ => hard_link_or_copy(path, destination),
+    }
+}
+
+/// Hardlinks `path` to `destination`, or copies it if that is not possible (e.g. because the
+/// destination is on another file system).
+fn hard_link_or_copy(path: &Path, destination: &Path) -> std::io::Result<()> {
+    if fs::hard_link(path, destination).is_err() {
+        fs::copy(path, destination)?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::VEnv;
@@ -430,4 +586,131 @@ mod tests {
             "same venv was not created in same location"
         )
     }
+
+    #[test]
+    pub fn test_open_existing_venv() {
+        let venv_dir = tempfile::tempdir().unwrap();
+        let venv = VEnv::create(venv_dir.path(), PythonLocation::System).unwrap();
+
+        let opened = VEnv::open(venv_dir.path()).unwrap();
+        assert_eq!(
+            opened.location.canonicalize().unwrap(),
+            venv.location.canonicalize().unwrap()
+        );
+
+        let opened = VEnv::open(&venv.python_executable()).unwrap();
+        assert_eq!(
+            opened.location.canonicalize().unwrap(),
+            venv.location.canonicalize().unwrap()
+        );
+        assert_eq!(
+            opened.install_paths.site_packages(),
+            venv.install_paths.site_packages()
+        );
+        assert_eq!(opened.install_paths.scripts(), venv.install_paths.scripts());
+    }
+
+    #[test]
+    pub fn test_open_system_interpreter() {
+        // The system interpreter is not a virtual environment, its paths come from sysconfig
+        let python = crate::python_env::system_python_executable().unwrap();
+        let opened = VEnv::open(python).unwrap();
+        assert!(opened
+            .root()
+            .join(opened.install_paths().site_packages())
+            .is_dir());
+        assert_eq!(&opened.python_executable(), python);
+    }
+
+    #[test]
+    pub fn test_clone_venv() {
+        let venv_dir = tempfile::tempdir().unwrap();
+        let venv = VEnv::create(&venv_dir.path().join("template"), PythonLocation::System).unwrap();
+        let wheel = crate::artifacts::Wheel::from_path(
+            &Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("../../test-data/wheels/wordle_python-2.3.32-py3-none-any.whl"),
+            &NormalizedPackageName::from_str("wordle_python").unwrap(),
+        )
+        .unwrap();
+        venv.install_wheel(&wheel, &Default::default()).unwrap();
+        let script = venv
+            .root()
+            .join(venv.install_paths.scripts())
+            .join("wordle");
+        std::fs::write(
+            script,
+            format!("#!{}\nimport wordle\n", venv.python_executable().display()),
+        )
+        .unwrap();
+        std::fs::write(
+            venv.root()
+                .join(venv.install_paths.scripts())
+                .join("activate"),
+            format!(
+                "VIRTUAL_ENV=\"{}\"\nexport VIRTUAL_ENV\n",
+                venv.root().display()
+            ),
+        )
+        .unwrap();
+
+        let clone = venv.clone_to(&venv_dir.path().join("clone")).unwrap();
+        assert_eq!(clone.root(), venv_dir.path().join("clone"));
+
+        // The installed package can be used from the clone
+        let output = clone
+            .execute_script(
+                &Path::new(env!("CARGO_MANIFEST_DIR"))
+                    .join("../../test-data/scripts/test_wordle.py"),
+            )
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap().trim(),
+            "('A   d   i   E   u   ', False)"
+        );
+
+        // Scripts refer to the python interpreter of the clone
+        let cloned_script = std::fs::read_to_string(
+            clone
+                .root()
+                .join(clone.install_paths.scripts())
+                .join("wordle"),
+        )
+        .unwrap();
+        assert_eq!(
+            cloned_script,
+            format!("#!{}\nimport wordle\n", clone.python_executable().display())
+        );
+
+        // Activation scripts set `VIRTUAL_ENV` to the clone
+        let activate = std::fs::read_to_string(
+            clone
+                .root()
+                .join(clone.install_paths.scripts())
+                .join("activate"),
+        )
+        .unwrap();
+        assert_eq!(
+            activate,
+            format!(
+                "VIRTUAL_ENV=\"{}\"\nexport VIRTUAL_ENV\n",
+                clone.root().display()
+            )
+        );
+
+        // Other files are shared with the template
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let inode = |venv: &VEnv| {
+                std::fs::metadata(
+                    venv.root()
+                        .join(venv.install_paths.site_packages())
+                        .join("wordle_python-2.3.32.dist-info/METADATA"),
+                )
+                .unwrap()
+                .ino()
+            };
+            assert_eq!(inode(&venv), inode(&clone));
+        }
+    }
 }
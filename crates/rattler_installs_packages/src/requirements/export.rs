@@ -0,0 +1,209 @@
+use crate::resolve::{Dependency, PinnedPackage, Resolution};
+use itertools::Itertools;
+use pep508_rs::{MarkerTree, MarkerValue};
+use std::fmt::Write;
+
+/// Render a [`Resolution`] as a requirements.txt file that can be installed with `pip install -r`.
+///
+/// Every package is pinned to the selected version (or URL) and lists the sha256 hashes of its
+/// artifacts that are known, editable packages are written as `-e <url>`. Each entry is annotated
+/// with the packages that required it. Packages without a known hash, like direct URLs or
+/// packages from an index that doesn't provide hashes, are written without `--hash` options, so
+/// the file can only be installed with `--require-hashes` if every package has a hash.
+pub fn to_requirements_txt(resolution: &Resolution) -> String {
+    let mut output = String::from("# This file was generated by rip\n");
+    for package in resolution
+        .packages
+        .iter()
+        .sorted_by(|a, b| a.name.cmp(&b.name))
+    {
+        let dependents = resolution.dependents(&package.name).collect::<Vec<_>>();
+
+        match &package.url {
+            // Editable source trees cannot be pinned or hashed
+            Some(url) if package.editable => writeln!(output, "-e {url}").unwrap(),
+            _ => {
+                output.push_str(&pinned_requirement(package));
+                if let Some(marker) = combined_marker(&dependents) {
+                    write!(output, " ; {marker}").unwrap();
+                }
+
+                let hashes = package
+                    .artifacts
+                    .iter()
+                    .filter_map(|a| a.hashes.as_ref()?.sha256)
+                    .map(|hash| format!("{hash:x}"))
+                    .sorted()
+                    .dedup();
+                for hash in hashes {
+                    write!(output, " \\\n    --hash=sha256:{hash}").unwrap();
+                }
+                output.push('\n');
+            }
+        }
+
+        let parents = dependents
+            .iter()
+            .filter_map(|d| {
+                let parent = d.parent.as_ref()?;
+                Some(match &d.parent_extra {
+                    Some(extra) => format!("{parent}[{}]", extra.as_str()),
+                    None => parent.to_string(),
+                })
+            })
+            .sorted()
+            .dedup()
+            .collect::<Vec<_>>();
+        if !parents.is_empty() {
+            writeln!(output, "    # via {}", parents.join(", ")).unwrap();
+        }
+    }
+    output
+}
+
+/// Formats the package as `name[extras]==version` or `name[extras] @ url`.
+fn pinned_requirement(package: &PinnedPackage) -> String {
+    let mut requirement = package.name.to_string();
+    if !package.extras.is_empty() {
+        write!(
+            requirement,
+            "[{}]",
+            package.extras.iter().map(|e| e.as_str()).sorted().join(",")
+        )
+        .unwrap();
+    }
+    match &package.url {
+        Some(url) => write!(requirement, " @ {url}").unwrap(),
+        None => write!(requirement, "=={}", package.version).unwrap(),
+    }
+    requirement
+}
+
+/// If the package is only required through requirements with environment markers, returns the
+/// markers under which it is required. Markers that refer to extras cannot be used outside of the
+/// metadata of a package and are therefore not taken into account.
+fn combined_marker(dependents: &[&Dependency]) -> Option<String> {
+    let markers = dependents
+        .iter()
+        .map(|d| d.marker.as_ref().filter(|m| !references_extra(m)))
+        .collect::<Option<Vec<_>>>()?;
+    match markers.as_slice() {
+        [] => None,
+        [marker] => Some(marker.to_string()),
+        markers => Some(
+            markers
+                .iter()
+                .map(|m| m.to_string())
+                .sorted()
+                .dedup()
+                .map(|m| format!("({m})"))
+                .join(" or "),
+        ),
+    }
+}
+
+fn references_extra(marker: &MarkerTree) -> bool {
+    match marker {
+        MarkerTree::Expression(expression) => {
+            expression.l_value == MarkerValue::Extra || expression.r_value == MarkerValue::Extra
+        }
+        MarkerTree::And(markers) | MarkerTree::Or(markers) => markers.iter().any(references_extra),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::{
+        ArtifactHashes, ArtifactInfo, ArtifactName, DistInfoMetadata, NormalizedPackageName, Yanked,
+    };
+    use std::str::FromStr;
+    use std::sync::Arc;
+
+    fn package(name: &str, version: &str, hash: Option<&str>) -> PinnedPackage {
+        let normalized = NormalizedPackageName::from_str(name).unwrap();
+        let filename = format!("{name}-{version}-py3-none-any.whl");
+        PinnedPackage {
+            name: normalized.clone(),
+            version: version.parse().unwrap(),
+            url: None,
+            extras: Default::default(),
+            editable: false,
+            artifacts: vec![Arc::new(ArtifactInfo {
+                filename: ArtifactName::from_filename(&filename, None, &normalized).unwrap(),
+                url: format!("https://example.com/{filename}").parse().unwrap(),
+                is_direct_url: false,
+                hashes: hash.map(|hash| ArtifactHashes {
+                    sha256: rattler_digest::parse_digest_from_hex::<rattler_digest::Sha256>(hash),
+                }),
+                requires_python: None,
+                dist_info_metadata: DistInfoMetadata::default(),
+                yanked: Yanked::default(),
+            })],
+        }
+    }
+
+    fn dependency(parent: Option<&str>, name: &str, marker: Option<&str>) -> Dependency {
+        Dependency {
+            parent: parent.map(|p| p.parse().unwrap()),
+            parent_extra: None,
+            name: name.parse().unwrap(),
+            extras: Vec::new(),
+            version_or_url: None,
+            marker: marker.map(|m| MarkerTree::from_str(m).unwrap()),
+        }
+    }
+
+    #[test]
+    fn test_to_requirements_txt() {
+        let mut editable = package("myproject", "0.1.0", None);
+        editable.url = Some("file:///home/user/myproject".parse().unwrap());
+        editable.editable = true;
+
+        // Direct urls have no known hash
+        let mut direct_url = package("flask", "3.0.0", None);
+        direct_url.url = Some(
+            "git+https://github.com/pallets/flask.git@735a4701d6d5e848241e7d7535db898efb62d400"
+                .parse()
+                .unwrap(),
+        );
+
+        let resolution = Resolution {
+            packages: vec![
+                editable,
+                package(
+                    "requests",
+                    "2.31.0",
+                    Some("58cd2187c01e70e6e26505bca751777aa9f2ee0b7f4300988b709f44e013003f"),
+                ),
+                package("urllib3", "2.2.0", None),
+                package("colorama", "0.4.6", None),
+                direct_url,
+            ],
+            dependencies: vec![
+                dependency(None, "myproject", None),
+                dependency(None, "requests", None),
+                dependency(None, "flask", None),
+                dependency(Some("requests"), "urllib3", None),
+                dependency(
+                    Some("requests"),
+                    "colorama",
+                    Some("sys_platform == 'win32'"),
+                ),
+            ],
+            candidates: Default::default(),
+        };
+
+        insta::assert_snapshot!(to_requirements_txt(&resolution), @r###"
+        # This file was generated by rip
+        colorama==0.4.6 ; sys_platform == 'win32'
+            # via requests
+        flask @ git+https://github.com/pallets/flask.git@735a4701d6d5e848241e7d7535db898efb62d400
+        -e file:///home/user/myproject
+        requests==2.31.0 \
+            --hash=sha256:58cd2187c01e70e6e26505bca751777aa9f2ee0b7f4300988b709f44e013003f
+        urllib3==2.2.0
+            # via requests
+        "###);
+    }
+}
@@ -0,0 +1,230 @@
+//! This module contains functionality to collect requirements from the files that are commonly used
+//! to declare the dependencies of a Python project:
+//!
+//! - `requirements.txt` files (see [`CollectedRequirements::from_requirements_txt`]), including
+//!   `-r`/`-c` includes, index options, hashes and editable entries.
+//! - local source trees that are installed in editable mode (see
+//!   [`CollectedRequirements::from_editable`]).
+//! - the `[project]` table of a `pyproject.toml` (see [`CollectedRequirements::from_pyproject_toml`]).
+//! - the `install_requires` option of a `setup.cfg` (see [`CollectedRequirements::from_setup_cfg`]).
+//!
+//! The collected requirements can be passed to [`crate::resolve::resolve`] and the collected index
+//! settings can be turned into a [`PackageSourcesBuilder`]. The result of a resolution can be
+//! written back as a requirements.txt file with [`to_requirements_txt`].
+
+mod export;
+mod pyproject;
+mod requirements_txt;
+mod setup_cfg;
+
+pub use export::to_requirements_txt;
+
+use crate::index::PackageSourcesBuilder;
+use crate::types::{ArtifactHashes, Extra, NormalizedPackageName, PackageName};
+use crate::utils::normalize_index_url;
+use miette::Diagnostic;
+use pep508_rs::Requirement;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use thiserror::Error;
+use url::Url;
+
+/// Requirements and index settings that have been collected from one or more requirement sources.
+#[derive(Debug, Clone, Default)]
+pub struct CollectedRequirements {
+    /// The requirements that should be part of the environment.
+    pub requirements: Vec<Requirement>,
+
+    /// Constraints (`-c` files) that restrict the versions of packages without requiring them.
+    pub constraints: Vec<Requirement>,
+
+    /// The names of the requirements that were specified as editable (`-e`).
+    pub editables: HashSet<NormalizedPackageName>,
+
+    /// The hashes (`--hash`) that were specified for a package.
+    pub hashes: HashMap<NormalizedPackageName, Vec<ArtifactHashes>>,
+
+    /// The index URL (`--index-url`) that should replace the default index.
+    pub index_url: Option<Url>,
+
+    /// Additional index URLs (`--extra-index-url`).
+    pub extra_index_urls: Vec<Url>,
+
+    /// Locations that should be searched for archives (`--find-links`).
+    pub find_links: Vec<String>,
+
+    /// True if `--no-index` was specified.
+    pub no_index: bool,
+}
+
+/// An error that can occur when collecting requirements from a file.
+#[derive(Debug, Error, Diagnostic)]
+#[allow(missing_docs)]
+pub enum RequirementsSourceError {
+    #[error("failed to read '{}'", .0.display())]
+    Io(PathBuf, #[source] std::io::Error),
+
+    #[error("{}:{line}: {message}", path.display())]
+    InvalidLine {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+
+    #[error("'{}' is included recursively", .0.display())]
+    RecursiveInclude(PathBuf),
+
+    #[error("failed to parse '{}': {1}", .0.display())]
+    PyProjectToml(PathBuf, String),
+
+    #[error("failed to parse '{}': {1}", .0.display())]
+    SetupCfg(PathBuf, String),
+
+    #[error("could not determine the project name of '{}', add an '#egg=<name>' fragment", .0.display())]
+    UnknownProjectName(PathBuf),
+
+    #[error("'{}' is not a requirements.txt, pyproject.toml or setup.cfg file", .0.display())]
+    UnsupportedFile(PathBuf),
+}
+
+impl CollectedRequirements {
+    /// Collect the requirements from the file at the given path. The type of the file is
+    /// determined from its name: `pyproject.toml` and `setup.cfg` are read as project files, any
+    /// other `.txt` or `.in` file is read as a requirements.txt file.
+    ///
+    /// `extras` selects the optional dependencies that are included from project files, it is
+    /// ignored for requirements.txt files.
+    pub fn from_path(path: &Path, extras: &[Extra]) -> Result<Self, RequirementsSourceError> {
+        match path.file_name().and_then(|name| name.to_str()) {
+            Some("pyproject.toml") => Self::from_pyproject_toml(path, extras),
+            Some("setup.cfg") => Self::from_setup_cfg(path, extras),
+            Some(name) if name.ends_with(".txt") || name.ends_with(".in") => {
+                Self::from_requirements_txt(path)
+            }
+            _ => Err(RequirementsSourceError::UnsupportedFile(path.to_path_buf())),
+        }
+    }
+
+    /// Collect the requirements from a requirements.txt file. Files that are included with `-r` or
+    /// `-c` are resolved relative to the file that includes them.
+    pub fn from_requirements_txt(path: &Path) -> Result<Self, RequirementsSourceError> {
+        let mut result = Self::default();
+        requirements_txt::parse_file(path, false, &mut result, &mut Vec::new())?;
+        Ok(result)
+    }
+
+    /// Collect a local source tree that should be installed in editable mode (`-e <path>`). The
+    /// name of the project is read from the `pyproject.toml` or `setup.cfg` in the directory.
+    pub fn from_editable(path: &Path) -> Result<Self, RequirementsSourceError> {
+        let path =
+            dunce::canonicalize(path).map_err(|e| RequirementsSourceError::Io(path.into(), e))?;
+        let name = project_name_from_source_tree(&path)
+            .ok_or_else(|| RequirementsSourceError::UnknownProjectName(path.clone()))?;
+        let url = Url::from_directory_path(&path)
+            .map_err(|_| RequirementsSourceError::UnknownProjectName(path.clone()))?;
+        let requirement = Requirement::from_str(&format!("{} @ {url}", name.as_source_str()))
+            .expect("a name and an url always form a valid requirement");
+
+        Ok(Self {
+            requirements: vec![requirement],
+            editables: HashSet::from([name.into()]),
+            ..Default::default()
+        })
+    }
+
+    /// Collect the `[project.dependencies]` and the `[project.optional-dependencies]` of the given
+    /// `extras` from a pyproject.toml file.
+    pub fn from_pyproject_toml(
+        path: &Path,
+        extras: &[Extra],
+    ) -> Result<Self, RequirementsSourceError> {
+        Ok(Self {
+            requirements: pyproject::read_requirements(path, extras)?,
+            ..Default::default()
+        })
+    }
+
+    /// Collect the `install_requires` and the `extras_require` of the given `extras` from a
+    /// setup.cfg file.
+    pub fn from_setup_cfg(path: &Path, extras: &[Extra]) -> Result<Self, RequirementsSourceError> {
+        Ok(Self {
+            requirements: setup_cfg::read_requirements(path, extras)?,
+            ..Default::default()
+        })
+    }
+
+    /// Merge the requirements and settings of `other` into this instance. An index URL specified
+    /// in `other` replaces the index URL of this instance.
+    pub fn extend(&mut self, other: Self) {
+        self.requirements.extend(other.requirements);
+        self.constraints.extend(other.constraints);
+        self.editables.extend(other.editables);
+        for (name, hashes) in other.hashes {
+            self.hashes.entry(name).or_default().extend(hashes);
+        }
+        if other.index_url.is_some() {
+            self.index_url = other.index_url;
+        }
+        for url in other.extra_index_urls {
+            if !self.extra_index_urls.contains(&url) {
+                self.extra_index_urls.push(url);
+            }
+        }
+        self.find_links.extend(other.find_links);
+        self.no_index |= other.no_index;
+    }
+
+    /// Returns a [`PackageSourcesBuilder`] that uses the collected index URL (or
+    /// `default_index_url` if none was specified) and all extra index URLs. All index URLs are
+    /// normalized with [`normalize_index_url`].
+    pub fn package_sources_builder(&self, default_index_url: Url) -> PackageSourcesBuilder {
+        let index_url = normalize_index_url(self.index_url.clone().unwrap_or(default_index_url));
+        self.extra_index_urls
+            .iter()
+            .map(|url| normalize_index_url(url.clone()))
+            .fold(PackageSourcesBuilder::new(index_url), |builder, url| {
+                builder.with_index(url.as_str(), &url)
+            })
+    }
+}
+
+/// Determine the name of the project in a source tree by reading the `[project]` table of the
+/// `pyproject.toml` or the `[metadata]` section of the `setup.cfg`.
+pub fn project_name_from_source_tree(dir: &Path) -> Option<PackageName> {
+    pyproject::read_project_name(&dir.join("pyproject.toml"))
+        .or_else(|| setup_cfg::read_project_name(&dir.join("setup.cfg")))
+        .and_then(|name| PackageName::from_str(&name).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn index_urls_are_normalized() {
+        let requirements = CollectedRequirements {
+            index_url: Some("https://example.com/simple".parse().unwrap()),
+            extra_index_urls: vec!["https://extra.example.com/simple".parse().unwrap()],
+            ..Default::default()
+        };
+        let sources = requirements
+            .package_sources_builder("https://pypi.org/simple/".parse().unwrap())
+            .build()
+            .unwrap();
+
+        let name = "foo".parse().unwrap();
+        let urls = sources
+            .index_url(&name)
+            .into_iter()
+            .map(Url::as_str)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            urls,
+            vec![
+                "https://example.com/simple/",
+                "https://extra.example.com/simple/"
+            ]
+        );
+    }
+}
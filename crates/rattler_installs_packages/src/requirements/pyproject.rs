@@ -0,0 +1,82 @@
+use super::RequirementsSourceError;
+use crate::types::Extra;
+use fs_err as fs;
+use pep508_rs::Requirement;
+use pyproject_toml::PyProjectToml;
+use std::path::Path;
+use std::str::FromStr;
+
+fn read(path: &Path) -> Result<PyProjectToml, RequirementsSourceError> {
+    let source =
+        fs::read_to_string(path).map_err(|e| RequirementsSourceError::Io(path.to_path_buf(), e))?;
+    PyProjectToml::new(&source)
+        .map_err(|e| RequirementsSourceError::PyProjectToml(path.to_path_buf(), e.to_string()))
+}
+
+/// Read the dependencies and the optional dependencies of the given extras from the `[project]`
+/// table.
+pub(super) fn read_requirements(
+    path: &Path,
+    extras: &[Extra],
+) -> Result<Vec<Requirement>, RequirementsSourceError> {
+    let project = read(path)?.project.ok_or_else(|| {
+        RequirementsSourceError::PyProjectToml(
+            path.to_path_buf(),
+            "missing [project] table".to_string(),
+        )
+    })?;
+
+    let mut requirements = project.dependencies.unwrap_or_default();
+    let optional_dependencies = project.optional_dependencies.unwrap_or_default();
+    for extra in extras {
+        let group = optional_dependencies
+            .iter()
+            .find(|(name, _)| Extra::from_str(name).ok().as_ref() == Some(extra))
+            .map(|(_, group)| group)
+            .ok_or_else(|| {
+                RequirementsSourceError::PyProjectToml(
+                    path.to_path_buf(),
+                    format!("unknown optional dependency group '{}'", extra.as_str()),
+                )
+            })?;
+        requirements.extend(group.iter().cloned());
+    }
+
+    Ok(requirements)
+}
+
+/// Returns the name of the project if the file exists and contains a `[project]` table.
+pub(super) fn read_project_name(path: &Path) -> Option<String> {
+    read(path).ok()?.project.map(|project| project.name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_read_pyproject_requirements() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pyproject.toml");
+        fs::write(
+            &path,
+            r#"
+[project]
+name = "my-project"
+dependencies = ["requests>=2", "tomli; python_version < '3.11'"]
+
+[project.optional-dependencies]
+Test = ["pytest"]
+docs = ["sphinx"]
+"#,
+        )
+        .unwrap();
+
+        let requirements = read_requirements(&path, &["test".parse().unwrap()]).unwrap();
+        let names: Vec<_> = requirements.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["requests", "tomli", "pytest"]);
+
+        assert!(read_requirements(&path, &["unknown".parse().unwrap()]).is_err());
+        assert_eq!(read_project_name(&path).as_deref(), Some("my-project"));
+    }
+}
@@ -0,0 +1,489 @@
+//! Parsing of `requirements.txt` files, following the format that `pip` accepts:
+//! <https://pip.pypa.io/en/stable/reference/requirements-file-format/>
+
+use super::{project_name_from_source_tree, CollectedRequirements, RequirementsSourceError};
+use crate::index::parse_hash;
+use crate::types::{NormalizedPackageName, PackageName};
+use fs_err as fs;
+use pep508_rs::Requirement;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use url::Url;
+
+/// Global options that `pip` accepts in requirements files but that do not influence the
+/// requirements themselves. These are ignored with a warning.
+const IGNORED_OPTIONS: &[&str] = &[
+    "--pre",
+    "--prefer-binary",
+    "--require-hashes",
+    "--trusted-host",
+    "--only-binary",
+    "--no-binary",
+    "--use-feature",
+];
+
+/// Parse the requirements.txt file at `path` and add its contents to `result`. `stack` contains the
+/// files that are currently being parsed and is used to detect recursive includes.
+pub(super) fn parse_file(
+    path: &Path,
+    is_constraint: bool,
+    result: &mut CollectedRequirements,
+    stack: &mut Vec<PathBuf>,
+) -> Result<(), RequirementsSourceError> {
+    let path = dunce::canonicalize(path)
+        .map_err(|e| RequirementsSourceError::Io(path.to_path_buf(), e))?;
+    if stack.contains(&path) {
+        return Err(RequirementsSourceError::RecursiveInclude(path));
+    }
+
+    let content =
+        fs::read_to_string(&path).map_err(|e| RequirementsSourceError::Io(path.clone(), e))?;
+
+    stack.push(path.clone());
+    let parser = Parser {
+        path: &path,
+        base_dir: path.parent().unwrap_or(Path::new(".")),
+        is_constraint,
+    };
+    for (line_number, line) in logical_lines(&content) {
+        parser
+            .parse_line(&line, result, stack)
+            .map_err(|message| match message {
+                LineError::Message(message) => RequirementsSourceError::InvalidLine {
+                    path: path.clone(),
+                    line: line_number,
+                    message,
+                },
+                LineError::Source(err) => err,
+            })?;
+    }
+    stack.pop();
+
+    Ok(())
+}
+
+/// Joins lines that end with a backslash, strips comments and expands `${VAR}` environment
+/// variables. Returns the (1-based) number of the first physical line of every non-empty logical
+/// line.
+fn logical_lines(content: &str) -> Vec<(usize, String)> {
+    static COMMENT: OnceLock<Regex> = OnceLock::new();
+    let comment = COMMENT.get_or_init(|| Regex::new(r"(^|\s+)#.*$").unwrap());
+
+    let mut lines = Vec::new();
+    let mut current: Option<(usize, String)> = None;
+    for (index, line) in content.lines().enumerate() {
+        let (start, mut logical) = current.take().unwrap_or((index + 1, String::new()));
+        match line.strip_suffix('\\') {
+            Some(line) => {
+                logical.push_str(line);
+                current = Some((start, logical));
+            }
+            None => {
+                logical.push_str(line);
+                lines.push((start, logical));
+            }
+        }
+    }
+    lines.extend(current);
+
+    lines
+        .into_iter()
+        .map(|(number, line)| (number, expand_env_vars(&comment.replace(&line, ""))))
+        .filter(|(_, line)| !line.trim().is_empty())
+        .collect()
+}
+
+/// Expands `${VAR}` references to environment variables, unknown variables are left as is.
+fn expand_env_vars(line: &str) -> String {
+    static ENV_VAR: OnceLock<Regex> = OnceLock::new();
+    let env_var = ENV_VAR.get_or_init(|| Regex::new(r"\$\{([A-Z0-9_]+)\}").unwrap());
+    env_var
+        .replace_all(line, |captures: &regex::Captures| {
+            std::env::var(&captures[1]).unwrap_or_else(|_| captures[0].to_string())
+        })
+        .into_owned()
+}
+
+enum LineError {
+    Message(String),
+    Source(RequirementsSourceError),
+}
+
+impl From<String> for LineError {
+    fn from(message: String) -> Self {
+        LineError::Message(message)
+    }
+}
+
+struct Parser<'a> {
+    path: &'a Path,
+    base_dir: &'a Path,
+    is_constraint: bool,
+}
+
+impl<'a> Parser<'a> {
+    fn parse_line(
+        &self,
+        line: &str,
+        result: &mut CollectedRequirements,
+        stack: &mut Vec<PathBuf>,
+    ) -> Result<(), LineError> {
+        let line = line.trim();
+        if line.starts_with('-') {
+            return self.parse_option_line(line, result, stack);
+        }
+
+        let (requirement, options) = split_requirement_options(line);
+        let requirement = self.parse_requirement(requirement)?;
+        let name = NormalizedPackageName::from(
+            PackageName::from_str(&requirement.name).map_err(|e| e.to_string())?,
+        );
+
+        let mut options = options.into_iter();
+        while let Some(option) = options.next() {
+            let (option, value) = match option.split_once('=') {
+                Some((option, value)) => (option, Some(value.to_string())),
+                None => (option, None),
+            };
+            match option {
+                "--hash" => {
+                    let value = value
+                        .or_else(|| options.next().map(ToString::to_string))
+                        .ok_or_else(|| "--hash requires a value".to_string())?;
+                    let hash = value
+                        .split_once(':')
+                        .and_then(|(algorithm, hex)| parse_hash(&format!("{algorithm}={hex}")))
+                        .filter(|hash| !hash.is_empty())
+                        .ok_or_else(|| format!("unsupported hash '{value}'"))?;
+                    result.hashes.entry(name.clone()).or_default().push(hash);
+                }
+                _ => {
+                    tracing::warn!(
+                        "{}: ignoring unsupported option '{option}' for '{name}'",
+                        self.path.display()
+                    );
+                }
+            }
+        }
+
+        if self.is_constraint {
+            result.constraints.push(requirement);
+        } else {
+            result.requirements.push(requirement);
+        }
+
+        Ok(())
+    }
+
+    fn parse_option_line(
+        &self,
+        line: &str,
+        result: &mut CollectedRequirements,
+        stack: &mut Vec<PathBuf>,
+    ) -> Result<(), LineError> {
+        let (option, value) = split_option(line);
+        let required_value = || {
+            value
+                .clone()
+                .ok_or_else(|| LineError::from(format!("{option} requires a value")))
+        };
+
+        match option {
+            "-r" | "--requirement" | "-c" | "--constraint" => {
+                let is_constraint = self.is_constraint || matches!(option, "-c" | "--constraint");
+                let path = self.base_dir.join(required_value()?);
+                parse_file(&path, is_constraint, result, stack).map_err(LineError::Source)?;
+            }
+            "-i" | "--index-url" => {
+                result.index_url = Some(parse_url(&required_value()?)?);
+            }
+            "--extra-index-url" => {
+                let url = parse_url(&required_value()?)?;
+                if !result.extra_index_urls.contains(&url) {
+                    result.extra_index_urls.push(url);
+                }
+            }
+            "-f" | "--find-links" => result.find_links.push(required_value()?),
+            "--no-index" => result.no_index = true,
+            "-e" | "--editable" => {
+                if self.is_constraint {
+                    return Err(format!("{option} is not allowed in constraint files").into());
+                }
+                let requirement = self.parse_requirement(&required_value()?)?;
+                let name = PackageName::from_str(&requirement.name).map_err(|e| e.to_string())?;
+                result.editables.insert(name.into());
+                result.requirements.push(requirement);
+            }
+            option if IGNORED_OPTIONS.contains(&option) => {
+                tracing::warn!(
+                    "{}: ignoring unsupported option '{option}'",
+                    self.path.display()
+                );
+            }
+            option => return Err(format!("unknown option '{option}'").into()),
+        }
+
+        Ok(())
+    }
+
+    /// Parses a requirement, this is either a PEP 508 requirement or a path or URL to a project.
+    fn parse_requirement(&self, requirement: &str) -> Result<Requirement, LineError> {
+        let pep508_error = match Requirement::from_str(requirement) {
+            Ok(requirement) => return Ok(requirement),
+            Err(e) => e,
+        };
+
+        // Split off the markers of a path or url.
+        let (location, markers) = match requirement.split_once(" ;") {
+            Some((location, markers)) => (location.trim(), Some(markers.trim())),
+            None => match requirement.split_once("; ") {
+                Some((location, markers)) => (location.trim(), Some(markers.trim())),
+                None => (requirement.trim(), None),
+            },
+        };
+
+        let url = if is_url(location) {
+            parse_url(location)?
+        } else if is_path(location) {
+            let path = self.base_dir.join(location);
+            let path = dunce::canonicalize(&path)
+                .map_err(|e| format!("invalid path '{}': {e}", path.display()))?;
+            Url::from_file_path(&path).map_err(|_| format!("invalid path '{}'", path.display()))?
+        } else {
+            return Err(pep508_error.to_string().into());
+        };
+
+        let (url, name) = take_egg_fragment(url);
+        let name = match name {
+            Some(name) => name,
+            None => url
+                .to_file_path()
+                .ok()
+                .filter(|path| path.is_dir())
+                .and_then(|path| project_name_from_source_tree(&path))
+                .map(|name| name.as_source_str().to_string())
+                .ok_or_else(|| {
+                    LineError::Source(RequirementsSourceError::UnknownProjectName(PathBuf::from(
+                        location,
+                    )))
+                })?,
+        };
+
+        let requirement = match markers {
+            Some(markers) => format!("{name} @ {url} ; {markers}"),
+            None => format!("{name} @ {url}"),
+        };
+        Requirement::from_str(&requirement).map_err(|e| e.to_string().into())
+    }
+}
+
+fn parse_url(url: &str) -> Result<Url, LineError> {
+    Url::parse(url).map_err(|e| LineError::Message(format!("invalid url '{url}': {e}")))
+}
+
+/// Returns true if the string is an URL (and not a windows path like `C:\foo`).
+fn is_url(s: &str) -> bool {
+    matches!(s.split_once(':'), Some((scheme, _)) if scheme.len() > 1
+        && scheme.chars().all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.')
+        && Url::parse(s).is_ok())
+}
+
+/// Returns true if the string refers to a local path instead of a package name.
+fn is_path(s: &str) -> bool {
+    s.starts_with('.')
+        || s.starts_with('/')
+        || s.starts_with('\\')
+        || s.contains(std::path::MAIN_SEPARATOR)
+        || s.contains('/')
+}
+
+/// Removes the `egg=<name>` part from the fragment of the URL and returns the name.
+fn take_egg_fragment(mut url: Url) -> (Url, Option<String>) {
+    let Some(fragment) = url.fragment().map(ToString::to_string) else {
+        return (url, None);
+    };
+
+    let mut name = None;
+    let remaining = fragment
+        .split('&')
+        .filter(|part| match part.strip_prefix("egg=") {
+            Some(egg) => {
+                name = Some(egg.to_string());
+                false
+            }
+            None => true,
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+    url.set_fragment(if remaining.is_empty() {
+        None
+    } else {
+        Some(&remaining)
+    });
+
+    (url, name)
+}
+
+/// Splits an option line like `--index-url=https://foo` or `-r requirements.txt` into the option
+/// and its value.
+fn split_option(line: &str) -> (&str, Option<String>) {
+    let (option, value) = match line.split_once(|c: char| c.is_whitespace() || c == '=') {
+        Some((option, value)) => (option, Some(value.trim())),
+        None => (line, None),
+    };
+
+    // Short options can be directly followed by their value (e.g. `-rrequirements.txt`)
+    if !option.starts_with("--") && option.len() > 2 {
+        let (option, attached) = option.split_at(2);
+        return (option, Some(attached.to_string()));
+    }
+
+    (
+        option,
+        value.filter(|v| !v.is_empty()).map(ToString::to_string),
+    )
+}
+
+/// Splits a requirement line into the requirement and the per-requirement options (e.g.
+/// `--hash=sha256:...`). Options start at the first ` --` that is not part of a quoted string.
+fn split_requirement_options(line: &str) -> (&str, Vec<&str>) {
+    let mut quote = None;
+    let mut previous_is_whitespace = false;
+    for (index, c) in line.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if q == c => quote = None,
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '-') if previous_is_whitespace && line[index..].starts_with("--") => {
+                return (
+                    line[..index].trim(),
+                    line[index..].split_whitespace().collect(),
+                );
+            }
+            _ => {}
+        }
+        previous_is_whitespace = c.is_whitespace();
+    }
+    (line, Vec::new())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(files: &[(&str, &str)]) -> Result<CollectedRequirements, RequirementsSourceError> {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, content) in files {
+            let path = dir.path().join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        CollectedRequirements::from_requirements_txt(&dir.path().join(files[0].0))
+    }
+
+    #[test]
+    fn test_requirements_and_options() {
+        let result = parse(&[(
+            "requirements.txt",
+            r#"
+# A comment
+--index-url https://example.com/simple
+--extra-index-url=https://extra.example.com/simple
+-f ./wheels
+requests>=2.0 # trailing comment
+numpy==1.26.0 ; python_version >= "3.9" \
+    --hash=sha256:2ba9b5b3af2b8ed8c0fa2c23f8d9bbb2c8bc72f2e3d9b3a4d7c9ea5b4c5c4a3b \
+    --hash sha256:f0f1b8a0c8bd9e6a6f1b5c04f7a1e5a9c7b4a2e9d8d5b1c4c4e3a2e7a6f5d4c3
+"#,
+        )])
+        .unwrap();
+
+        assert_eq!(
+            result.index_url.unwrap().as_str(),
+            "https://example.com/simple"
+        );
+        assert_eq!(
+            result.extra_index_urls[0].as_str(),
+            "https://extra.example.com/simple"
+        );
+        assert_eq!(result.find_links, vec!["./wheels"]);
+
+        let names: Vec<_> = result
+            .requirements
+            .iter()
+            .map(|r| r.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["requests", "numpy"]);
+        assert!(result.requirements[1].marker.is_some());
+        assert_eq!(
+            result.hashes[&"numpy".parse::<NormalizedPackageName>().unwrap()].len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_includes_and_constraints() {
+        let result = parse(&[
+            (
+                "requirements.txt",
+                "-r nested/base.txt\n-c constraints.txt\nflask\n",
+            ),
+            ("nested/base.txt", "click\n-r ../more.txt"),
+            ("more.txt", "rich"),
+            ("constraints.txt", "click<9"),
+        ])
+        .unwrap();
+
+        let names: Vec<_> = result
+            .requirements
+            .iter()
+            .map(|r| r.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["click", "rich", "flask"]);
+        assert_eq!(result.constraints.len(), 1);
+        assert_eq!(result.constraints[0].name, "click");
+    }
+
+    #[test]
+    fn test_recursive_include() {
+        let err = parse(&[("a.txt", "-r b.txt"), ("b.txt", "-r a.txt")]).unwrap_err();
+        assert!(matches!(err, RequirementsSourceError::RecursiveInclude(_)));
+    }
+
+    #[test]
+    fn test_editables() {
+        let result = parse(&[
+            (
+                "requirements.txt",
+                "-e ./project\n-e git+https://github.com/pallets/flask.git@3.0.0#egg=flask\n",
+            ),
+            (
+                "project/pyproject.toml",
+                "[project]\nname = \"local-project\"\n",
+            ),
+        ])
+        .unwrap();
+
+        assert_eq!(result.editables.len(), 2);
+        assert_eq!(result.requirements[0].name, "local-project");
+        assert_eq!(result.requirements[1].name, "flask");
+        assert_eq!(
+            result.requirements[1].version_or_url,
+            Some(pep508_rs::VersionOrUrl::Url(
+                "git+https://github.com/pallets/flask.git@3.0.0"
+                    .parse()
+                    .unwrap()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_invalid_line() {
+        let err = parse(&[("requirements.txt", "flask\n--unknown-option\n")]).unwrap_err();
+        assert!(
+            matches!(err, RequirementsSourceError::InvalidLine { line: 2, .. }),
+            "{err:?}"
+        );
+    }
+}
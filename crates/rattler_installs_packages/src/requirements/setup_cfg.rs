@@ -0,0 +1,123 @@
+use super::RequirementsSourceError;
+use crate::types::Extra;
+use configparser::ini::Ini;
+use fs_err as fs;
+use pep508_rs::Requirement;
+use std::path::Path;
+use std::str::FromStr;
+
+fn read(path: &Path) -> Result<Ini, RequirementsSourceError> {
+    let source =
+        fs::read_to_string(path).map_err(|e| RequirementsSourceError::Io(path.to_path_buf(), e))?;
+
+    let mut ini = Ini::new_cs();
+    ini.set_multiline(true);
+    // Requirements use `;` to separate environment markers so only `#` is a comment.
+    ini.set_comment_symbols(&['#']);
+    ini.read(source)
+        .map_err(|e| RequirementsSourceError::SetupCfg(path.to_path_buf(), e))?;
+    Ok(ini)
+}
+
+/// Parse a (multi-line) list of requirements as used by `install_requires` and `extras_require`.
+fn parse_requirements(
+    path: &Path,
+    key: &str,
+    value: &str,
+) -> Result<Vec<Requirement>, RequirementsSourceError> {
+    if value.trim_start().starts_with("file:") {
+        return Err(RequirementsSourceError::SetupCfg(
+            path.to_path_buf(),
+            format!("'{key} = file: ...' directives are not supported"),
+        ));
+    }
+
+    value
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            Requirement::from_str(line).map_err(|e| {
+                RequirementsSourceError::SetupCfg(
+                    path.to_path_buf(),
+                    format!("invalid requirement in '{key}': {e}"),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Read `[options] install_requires` and the `[options.extras_require]` of the given extras.
+pub(super) fn read_requirements(
+    path: &Path,
+    extras: &[Extra],
+) -> Result<Vec<Requirement>, RequirementsSourceError> {
+    let ini = read(path)?;
+
+    let mut requirements = match ini.get("options", "install_requires") {
+        Some(value) => parse_requirements(path, "install_requires", &value)?,
+        None => Vec::new(),
+    };
+
+    let extras_require = ini
+        .get_map_ref()
+        .get("options.extras_require")
+        .cloned()
+        .unwrap_or_default();
+    for extra in extras {
+        let value = extras_require
+            .iter()
+            .find(|(name, _)| Extra::from_str(name).ok().as_ref() == Some(extra))
+            .map(|(_, value)| value.clone().unwrap_or_default())
+            .ok_or_else(|| {
+                RequirementsSourceError::SetupCfg(
+                    path.to_path_buf(),
+                    format!("unknown extra '{}'", extra.as_str()),
+                )
+            })?;
+        requirements.extend(parse_requirements(path, extra.as_str(), &value)?);
+    }
+
+    Ok(requirements)
+}
+
+/// Returns the name of the project if the file exists and defines `[metadata] name`.
+pub(super) fn read_project_name(path: &Path) -> Option<String> {
+    read(path).ok()?.get("metadata", "name")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_read_setup_cfg_requirements() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("setup.cfg");
+        fs::write(
+            &path,
+            r#"
+[metadata]
+name = my_project
+
+[options]
+# The runtime dependencies
+install_requires =
+    requests>=2
+    importlib-metadata; python_version < "3.8"
+
+[options.extras_require]
+test =
+    pytest
+"#,
+        )
+        .unwrap();
+
+        let requirements = read_requirements(&path, &["test".parse().unwrap()]).unwrap();
+        let names: Vec<_> = requirements.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["requests", "importlib-metadata", "pytest"]);
+        assert!(requirements[1].marker.is_some());
+
+        assert_eq!(read_project_name(&path).as_deref(), Some("my_project"));
+    }
+}
@@ -1,11 +1,11 @@
 use super::{
     pypi_version_types::PypiPackageName,
     solve_options::{PreReleaseResolution, ResolveOptions, SDistResolution},
-    PinnedPackage, PypiVersion, PypiVersionSet,
+    Candidate, Dependency, PinnedPackage, PypiVersion, PypiVersionSet,
 };
 use crate::{
     artifacts::{SDist, Wheel},
-    index::{ArtifactRequest, PackageDb},
+    index::{git_interop::is_pinned_url_of, ArtifactRequest, PackageDb},
     python_env::WheelTags,
     types::{
         ArtifactFromBytes, ArtifactInfo, ArtifactName, Extra, NormalizedPackageName, PackageName,
@@ -26,6 +26,7 @@ use std::{
     any::Any, borrow::Borrow, cmp::Ordering, collections::HashMap, rc::Rc, str::FromStr, sync::Arc,
 };
 use thiserror::Error;
+use tokio_util::sync::CancellationToken;
 use url::Url;
 
 /// This is a [`DependencyProvider`] for PyPI packages
@@ -33,6 +34,12 @@ pub(crate) struct PypiDependencyProvider {
     pub pool: Rc<Pool<PypiVersionSet, PypiPackageName>>,
     pub cached_artifacts: FrozenMap<SolvableId, Vec<Arc<ArtifactInfo>>>,
     pub name_to_url: FrozenMap<NormalizedPackageName, String>,
+    /// The requirements (after evaluating markers) of every solvable for which the dependencies
+    /// have been requested. This is used to construct the dependency graph of the solution.
+    pub requirements: FrozenMap<SolvableId, Vec<Dependency>>,
+    /// All the versions of a package that were considered and why they were excluded (if they
+    /// were excluded).
+    pub considered_candidates: FrozenMap<NormalizedPackageName, Vec<Candidate>>,
     package_db: Arc<PackageDb>,
     wheel_builder: Arc<WheelBuilder>,
     markers: Arc<MarkerEnvironment>,
@@ -59,6 +66,7 @@ impl PypiDependencyProvider {
         name_to_url: FrozenMap<NormalizedPackageName, String>,
         options: ResolveOptions,
         env_variables: HashMap<String, String>,
+        cancellation_token: CancellationToken,
     ) -> miette::Result<Self> {
         let wheel_builder = Arc::new(
             WheelBuilder::new(
@@ -68,7 +76,8 @@ impl PypiDependencyProvider {
                 options.clone(),
                 env_variables,
             )
-            .into_diagnostic()?,
+            .into_diagnostic()?
+            .with_cancellation_token(cancellation_token),
         );
 
         Ok(Self {
@@ -78,6 +87,8 @@ impl PypiDependencyProvider {
             markers,
             compatible_tags,
             cached_artifacts: Default::default(),
+            requirements: Default::default(),
+            considered_candidates: Default::default(),
             favored_packages,
             locked_packages,
             name_to_url,
@@ -89,6 +100,7 @@ impl PypiDependencyProvider {
     fn filter_candidates<'a, A: Borrow<ArtifactInfo>>(
         &self,
         artifacts: &'a [A],
+        sdist_resolution: SDistResolution,
     ) -> Result<Vec<&'a A>, &'static str> {
         // Filter only artifacts we can work with
         if artifacts.is_empty() {
@@ -105,14 +117,14 @@ impl PypiDependencyProvider {
         }
 
         // This should keep only the wheels
-        let mut wheels = if self.options.sdist_resolution.allow_wheels() {
+        let mut wheels = if sdist_resolution.allow_wheels() {
             let wheels = artifacts
                 .iter()
                 .copied()
                 .filter(|a| (*a).borrow().is::<Wheel>())
                 .collect::<Vec<_>>();
 
-            if !self.options.sdist_resolution.allow_sdists() && wheels.is_empty() {
+            if !sdist_resolution.allow_sdists() && wheels.is_empty() {
                 return Err("there are no wheels available");
             }
 
@@ -122,7 +134,7 @@ impl PypiDependencyProvider {
         };
 
         // Extract sdists
-        let mut sdists = if self.options.sdist_resolution.allow_sdists() {
+        let mut sdists = if sdist_resolution.allow_sdists() {
             let mut sdists = artifacts
                 .iter()
                 .copied()
@@ -132,7 +144,7 @@ impl PypiDependencyProvider {
                 .collect::<Vec<_>>();
 
             if wheels.is_empty() && sdists.is_empty() {
-                if self.options.sdist_resolution.allow_wheels() {
+                if sdist_resolution.allow_wheels() {
                     return Err("there are no wheels or sdists");
                 } else {
                     return Err("there are no sdists");
@@ -157,7 +169,7 @@ impl PypiDependencyProvider {
         };
 
         // Filter based on compatibility
-        if self.options.sdist_resolution.allow_wheels() {
+        if sdist_resolution.allow_wheels() {
             if let Some(compatible_tags) = &self.compatible_tags {
                 wheels.retain(|artifact| match &(*artifact).borrow().filename {
                     ArtifactName::Wheel(wheel_name) => wheel_name
@@ -183,7 +195,7 @@ impl PypiDependencyProvider {
                 });
             }
 
-            if !self.options.sdist_resolution.allow_sdists() && wheels.is_empty() {
+            if !sdist_resolution.allow_sdists() && wheels.is_empty() {
                 return Err(
                     "none of the artifacts are compatible with the Python interpreter or glibc version",
                 );
@@ -230,6 +242,9 @@ pub(crate) enum MetadataError {
     #[error("Extraction of metadata in case of wheels or building in case of sdists returned no results for following artifacts:\n{0}")]
     NoMetadata(String),
 
+    #[error("the resolution was cancelled")]
+    Cancelled,
+
     #[error("No metadata could be extracted for the following available artifacts:\n{artifacts}")]
     ExtractionFailure {
         artifacts: String,
@@ -244,6 +259,11 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName> for &'p PypiDepende
     }
 
     fn should_cancel_with_value(&self) -> Option<Box<dyn Any>> {
+        // Stop solving when nobody is waiting for the result anymore
+        if self.wheel_builder.is_cancelled() {
+            return Some(Box::new(MetadataError::Cancelled));
+        }
+
         // Supply the error message
         self.should_cancel_with_value
             .lock()
@@ -256,11 +276,21 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName> for &'p PypiDepende
         _: &SolverCache<PypiVersionSet, PypiPackageName, Self>,
         solvables: &mut [SolvableId],
     ) {
+        // All solvables that are sorted belong to the same package, so we can determine the
+        // sdist resolution for that package once.
+        let Some(&first) = solvables.first() else {
+            return;
+        };
+        let package_name = self
+            .pool
+            .resolve_package_name(self.pool.resolve_solvable(first).name_id());
+        let sdist_resolution = self.options.sdist_resolution_for(package_name.base());
+
         solvables.sort_by(|&a, &b| {
             // First sort the solvables based on the artifact types we have available for them and
             // whether some of them are preferred. If one artifact type is preferred over another
             // we sort those versions above the others even if the versions themselves are lower.
-            if matches!(self.options.sdist_resolution, SDistResolution::PreferWheels) {
+            if matches!(sdist_resolution, SDistResolution::PreferWheels) {
                 let a_has_wheels = self.solvable_has_artifact_type::<Wheel>(a);
                 let b_has_wheels = self.solvable_has_artifact_type::<Wheel>(b);
                 match (a_has_wheels, b_has_wheels) {
@@ -268,7 +298,7 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName> for &'p PypiDepende
                     (false, true) => return Ordering::Greater,
                     _ => {}
                 }
-            } else if matches!(self.options.sdist_resolution, SDistResolution::PreferSDists) {
+            } else if matches!(sdist_resolution, SDistResolution::PreferSDists) {
                 let a_has_sdists = self.solvable_has_artifact_type::<SDist>(a);
                 let b_has_sdists = self.solvable_has_artifact_type::<SDist>(b);
                 match (a_has_sdists, b_has_sdists) {
@@ -341,29 +371,54 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName> for &'p PypiDepende
         let locked_package = self.locked_packages.get(package_name.base());
         let favored_package = self.favored_packages.get(package_name.base());
 
-        let should_package_allow_prerelease = match &self.options.pre_release_resolution {
-            PreReleaseResolution::Disallow => false,
-            PreReleaseResolution::AllowIfNoOtherVersionsOrEnabled { allow_names } => {
-                if allow_names.contains(&package_name.base().to_string()) {
-                    true
-                } else {
-                    // check if we _only_ have prereleases for this name (if yes, also allow them)
-                    artifacts
-                        .iter()
-                        .all(|(version, _)| version.any_prerelease())
+        let should_package_allow_prerelease =
+            match self.options.pre_release_resolution_for(package_name.base()) {
+                PreReleaseResolution::Disallow => false,
+                PreReleaseResolution::AllowIfNoOtherVersionsOrEnabled { allow_names } => {
+                    if allow_names.contains(&package_name.base().to_string()) {
+                        true
+                    } else {
+                        // check if we _only_ have prereleases for this name (if yes, also allow them)
+                        artifacts
+                            .iter()
+                            .all(|(version, _)| version.any_prerelease())
+                    }
                 }
-            }
-            PreReleaseResolution::Allow => true,
-        };
+                PreReleaseResolution::Allow => true,
+            };
+
+        // Collect the version constraints that apply to this package
+        let constraints = self
+            .options
+            .constraints
+            .iter()
+            .filter(|c| {
+                PackageName::from_str(&c.name)
+                    .is_ok_and(|name| package_name.base() == &NormalizedPackageName::from(name))
+                    && c.marker
+                        .as_ref()
+                        .map_or(true, |m| m.evaluate(&self.markers, &[]))
+            })
+            .filter_map(|c| match &c.version_or_url {
+                Some(VersionOrUrl::VersionSpecifier(specifiers)) => Some(specifiers),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
 
+        let sdist_resolution = self.options.sdist_resolution_for(package_name.base());
         for (artifact_version, artifacts) in artifacts.iter() {
             // Skip this version if a locked or favored version exists for this version. It will be
             // added below.
 
             match artifact_version {
                 PypiVersion::Url(url) => {
-                    if locked_package.map(|p| &p.url) == Some(&Some(url.clone()))
-                        || favored_package.map(|p| &p.url) == Some(&Some(url.clone()))
+                    let is_same_url = |package: &PinnedPackage| {
+                        package.url.as_ref().map_or(false, |package_url| {
+                            package_url == url || is_pinned_url_of(url, package_url)
+                        })
+                    };
+                    if locked_package.map_or(false, is_same_url)
+                        || favored_package.map_or(false, is_same_url)
                     {
                         continue;
                     }
@@ -391,8 +446,21 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName> for &'p PypiDepende
             let solvable_id = self.pool.intern_solvable(name, internable_version);
             candidates.candidates.push(solvable_id);
 
+            // Exclude versions that do not satisfy the constraints
+            if let PypiVersion::Version { version, .. } = artifact_version {
+                if let Some(constraint) = constraints.iter().find(|c| !c.contains(version)) {
+                    candidates.excluded.push((
+                        solvable_id,
+                        self.pool.intern_string(format!(
+                            "it is excluded by the constraint {constraint}"
+                        )),
+                    ));
+                    continue;
+                }
+            }
+
             // Determine the candidates
-            match self.filter_candidates(artifacts) {
+            match self.filter_candidates(artifacts, sdist_resolution) {
                 Ok(artifacts) => {
                     self.cached_artifacts
                         .insert(solvable_id, artifacts.into_iter().cloned().collect());
@@ -439,6 +507,24 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName> for &'p PypiDepende
                 .insert(solvable_id, favored.artifacts.clone());
         }
 
+        // Remember the candidates so we can later explain why a version was not selected
+        if package_name.extra().is_none() {
+            let considered = candidates
+                .candidates
+                .iter()
+                .map(|&solvable_id| Candidate {
+                    version: self.pool.resolve_solvable(solvable_id).inner().clone(),
+                    excluded: candidates
+                        .excluded
+                        .iter()
+                        .find(|(id, _)| *id == solvable_id)
+                        .map(|(_, reason)| self.pool.resolve_string(*reason).to_string()),
+                })
+                .collect();
+            self.considered_candidates
+                .insert(package_name.base().clone(), considered);
+        }
+
         Some(candidates)
     }
 
@@ -474,7 +560,10 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName> for &'p PypiDepende
 
             let version_set_id = self.pool.intern_version_set(
                 base_name_id,
-                PypiVersionSet::from_spec(Some(specifiers), &self.options.pre_release_resolution),
+                PypiVersionSet::from_spec(
+                    Some(specifiers),
+                    self.options.pre_release_resolution_for(package_name),
+                ),
             );
             dependencies.requirements.push(version_set_id);
         }
@@ -587,7 +676,7 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName> for &'p PypiDepende
                     extra_name_id,
                     PypiVersionSet::from_spec(
                         Some(specifiers),
-                        &self.options.pre_release_resolution,
+                        self.options.pre_release_resolution_for(package_name),
                     ),
                 );
                 dependencies.constrains.push(version_set_id);
@@ -599,6 +688,7 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName> for &'p PypiDepende
             .into_iter()
             .map(|e| e.as_str())
             .collect::<Vec<_>>();
+        let mut requirements = Vec::new();
         for requirement in metadata.requires_dist {
             // Evaluate environment markers
             if let Some(markers) = requirement.marker.as_ref() {
@@ -607,6 +697,31 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName> for &'p PypiDepende
                 }
             }
 
+            // Record the requirement for the dependency graph. The requirements that do not depend
+            // on the extra are recorded for the base package.
+            let introduced_by_extra = package_name.extra().is_some()
+                && requirement
+                    .marker
+                    .as_ref()
+                    .is_some_and(|m| !m.evaluate(&self.markers, &[]));
+            if package_name.extra().is_none() || introduced_by_extra {
+                requirements.push(Dependency {
+                    parent: Some(package_name.base().clone()),
+                    parent_extra: package_name.extra().cloned(),
+                    name: PackageName::from_str(&requirement.name)
+                        .expect("invalid package name")
+                        .into(),
+                    extras: requirement
+                        .extras
+                        .iter()
+                        .flatten()
+                        .map(|e| Extra::from_str(e).expect("invalid extra name"))
+                        .collect(),
+                    version_or_url: requirement.version_or_url.clone(),
+                    marker: requirement.marker.clone(),
+                });
+            }
+
             // Add the dependency to the pool
             let Requirement {
                 name,
@@ -615,16 +730,15 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName> for &'p PypiDepende
                 ..
             } = requirement;
             let name = PackageName::from_str(&name).expect("invalid package name");
+            let normalized_name: NormalizedPackageName = name.clone().into();
+            let pre_release_resolution = self.options.pre_release_resolution_for(&normalized_name);
             let dependency_name_id = self
                 .pool
                 .intern_package_name(PypiPackageName::Base(name.clone().into()));
 
             let version_set_id = self.pool.intern_version_set(
                 dependency_name_id,
-                PypiVersionSet::from_spec(
-                    version_or_url.clone(),
-                    &self.options.pre_release_resolution,
-                ),
+                PypiVersionSet::from_spec(version_or_url.clone(), pre_release_resolution),
             );
 
             if let Some(VersionOrUrl::Url(url)) = version_or_url.clone() {
@@ -642,15 +756,14 @@ impl<'p> DependencyProvider<PypiVersionSet, PypiPackageName> for &'p PypiDepende
                     .intern_package_name(PypiPackageName::Extra(name.clone().into(), extra));
                 let version_set_id = self.pool.intern_version_set(
                     dependency_name_id,
-                    PypiVersionSet::from_spec(
-                        version_or_url.clone(),
-                        &self.options.pre_release_resolution,
-                    ),
+                    PypiVersionSet::from_spec(version_or_url.clone(), pre_release_resolution),
                 );
                 dependencies.requirements.push(version_set_id);
             }
         }
 
+        self.requirements.insert(solvable_id, requirements);
+
         Dependencies::Known(dependencies)
     }
 }
@@ -10,10 +10,12 @@
 
 mod dependency_provider;
 mod pypi_version_types;
+mod resolution;
 mod solve;
 pub mod solve_options;
 mod solve_types;
 
 pub use pypi_version_types::PypiVersion;
 pub use pypi_version_types::PypiVersionSet;
+pub use resolution::{Candidate, Dependency, DependencyPaths, Resolution, MAX_OMITTED_PATHS};
 pub use solve::{resolve, PinnedPackage};
@@ -2,6 +2,7 @@
 //! these are used by the [`resolvo`] crate to resolve dependencies.
 //! This module, in combination with the [`super::dependency_provider`] modules is used to make the PyPI ecosystem compatible with the [`resolvo`] crate.
 
+use crate::index::git_interop::is_pinned_url_of;
 use crate::resolve::solve_options::PreReleaseResolution;
 use crate::types::{Extra, NormalizedPackageName};
 use pep440_rs::Version;
@@ -104,7 +105,7 @@ impl VersionSet for PypiVersionSet {
 
     fn contains(&self, v: &Self::V) -> bool {
         match (self.spec.as_ref(), v) {
-            (Some(VersionOrUrl::Url(a)), PypiVersion::Url(b)) => a == b,
+            (Some(VersionOrUrl::Url(a)), PypiVersion::Url(b)) => a == b || is_pinned_url_of(a, b),
             (
                 Some(VersionOrUrl::VersionSpecifier(spec)),
                 PypiVersion::Version {
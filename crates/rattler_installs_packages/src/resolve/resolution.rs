@@ -0,0 +1,675 @@
+//! Contains the [`Resolution`] type that is returned by [`super::resolve`].
+
+use super::{PinnedPackage, PypiVersion};
+use crate::index::git_interop::is_pinned_url_of;
+use crate::types::{Extra, NormalizedPackageName};
+use itertools::Itertools;
+use pep508_rs::{MarkerTree, VersionOrUrl};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::{Display, Write};
+
+/// The number of paths that [`Resolution::paths_to`] counts beyond its limit, searching for more
+/// paths stops when this number is reached.
+pub const MAX_OMITTED_PATHS: usize = 10_000;
+
+/// The paths from the requirements of the user to a package, see [`Resolution::paths_to`].
+#[derive(Debug, Default)]
+pub struct DependencyPaths<'a> {
+    /// The paths that were found, at most the requested number.
+    pub paths: Vec<Vec<&'a Dependency>>,
+
+    /// The number of paths that were found but not returned, at most [`MAX_OMITTED_PATHS`].
+    pub omitted: usize,
+}
+
+/// A depth first search for the paths to a package, used by [`Resolution::paths_to`].
+struct PathSearch<'r, 'a> {
+    resolution: &'a Resolution,
+    target: &'r NormalizedPackageName,
+    reaches_target: &'r HashSet<&'r NormalizedPackageName>,
+    limit: usize,
+    path: Vec<&'a Dependency>,
+    visited: HashSet<&'a NormalizedPackageName>,
+    result: &'r mut DependencyPaths<'a>,
+}
+
+impl<'r, 'a> PathSearch<'r, 'a> {
+    /// Extends the current path with the edge and records or follows it. Returns false if the
+    /// search should stop because enough paths were found.
+    fn visit(&mut self, edge: &'a Dependency) -> bool {
+        if self.visited.contains(&edge.name) {
+            return true;
+        }
+        self.path.push(edge);
+        let keep_going = if &edge.name == self.target {
+            if self.result.paths.len() < self.limit {
+                self.result.paths.push(self.path.clone());
+            } else {
+                self.result.omitted += 1;
+            }
+            self.result.omitted < MAX_OMITTED_PATHS
+        } else {
+            self.visited.insert(&edge.name);
+            let mut keep_going = true;
+            for dependency in self
+                .resolution
+                .dependencies_of(&edge.name)
+                .filter(|d| self.reaches_target.contains(&d.name))
+            {
+                if !self.visit(dependency) {
+                    keep_going = false;
+                    break;
+                }
+            }
+            self.visited.remove(&edge.name);
+            keep_going
+        };
+        self.path.pop();
+        keep_going
+    }
+}
+
+/// A single edge in the dependency graph of a [`Resolution`]. It describes that `parent` (or the
+/// user if `parent` is `None`) required the package `name`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dependency {
+    /// The package that required the dependency or `None` if the dependency was requested by the
+    /// user.
+    pub parent: Option<NormalizedPackageName>,
+
+    /// The extra of `parent` through which the dependency was introduced, e.g. `socks` for a
+    /// `PySocks; extra == "socks"` requirement of `requests`.
+    pub parent_extra: Option<Extra>,
+
+    /// The name of the required package.
+    pub name: NormalizedPackageName,
+
+    /// The extras of the required package that were requested.
+    pub extras: Vec<Extra>,
+
+    /// The version specifier or URL of the requirement.
+    pub version_or_url: Option<VersionOrUrl>,
+
+    /// The environment marker of the requirement. The marker evaluated to true for the
+    /// environment that was resolved for, otherwise the edge would not be part of the graph.
+    pub marker: Option<MarkerTree>,
+}
+
+impl Display for Dependency {
+    /// Formats the requirement of the edge as `name[extras] specifier ; marker`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", format_name(&self.name, &self.extras))?;
+        match &self.version_or_url {
+            Some(VersionOrUrl::VersionSpecifier(specifiers)) if !specifiers.is_empty() => {
+                write!(f, " {specifiers}")?
+            }
+            Some(VersionOrUrl::Url(url)) => write!(f, " @ {url}")?,
+            _ => {}
+        }
+        if let Some(marker) = &self.marker {
+            write!(f, " ; {marker}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A version of a package that was considered during resolution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    /// The version (or URL) of the candidate.
+    pub version: PypiVersion,
+
+    /// The reason why this candidate could never be selected, e.g. because it is yanked or because
+    /// none of its artifacts are compatible. `None` if the candidate could have been selected.
+    pub excluded: Option<String>,
+}
+
+/// The result of [`super::resolve`]. Next to the packages that have been selected this also
+/// contains the dependency graph between them. The graph can be rendered as a tree
+/// ([`Resolution::to_tree`]), in the DOT format ([`Resolution::to_dot`]) or as JSON
+/// ([`Resolution::to_json`]).
+#[derive(Debug, Clone, Default)]
+pub struct Resolution {
+    /// The packages that have been selected.
+    pub packages: Vec<PinnedPackage>,
+
+    /// The requirements through which the packages were selected. Only edges between packages of
+    /// the resolution (or from the user to a package) are retained.
+    pub dependencies: Vec<Dependency>,
+
+    /// The versions that were considered for every package of the resolution.
+    pub candidates: HashMap<NormalizedPackageName, Vec<Candidate>>,
+}
+
+impl Resolution {
+    /// Returns the selected package with the given name.
+    pub fn package(&self, name: &NormalizedPackageName) -> Option<&PinnedPackage> {
+        self.packages.iter().find(|p| &p.name == name)
+    }
+
+    /// Returns the edges through which the package with the given name was required.
+    pub fn dependents<'a>(
+        &'a self,
+        name: &'a NormalizedPackageName,
+    ) -> impl Iterator<Item = &'a Dependency> + 'a {
+        self.dependencies.iter().filter(move |d| &d.name == name)
+    }
+
+    /// Returns the edges from the package with the given name to its dependencies.
+    pub fn dependencies_of<'a>(
+        &'a self,
+        name: &'a NormalizedPackageName,
+    ) -> impl Iterator<Item = &'a Dependency> + 'a {
+        self.dependencies
+            .iter()
+            .filter(move |d| d.parent.as_ref() == Some(name))
+    }
+
+    /// Returns the edges that were requested by the user.
+    pub fn root_dependencies(&self) -> impl Iterator<Item = &Dependency> + '_ {
+        self.dependencies.iter().filter(|d| d.parent.is_none())
+    }
+
+    /// Returns every path from a requirement of the user to the package with the given name. Each
+    /// path starts with an edge from the user and ends with an edge to the package, paths never
+    /// visit a package twice.
+    ///
+    /// The number of paths can grow exponentially with the number of shared dependencies, so at
+    /// most `limit` paths are returned. The paths that are left out are counted in
+    /// [`DependencyPaths::omitted`].
+    pub fn paths_to(&self, name: &NormalizedPackageName, limit: usize) -> DependencyPaths<'_> {
+        // Only packages from which the package can be reached have to be visited
+        let mut reaches_target = HashSet::from([name]);
+        let mut queue = VecDeque::from([name]);
+        while let Some(current) = queue.pop_front() {
+            for edge in self.dependencies.iter().filter(|d| &d.name == current) {
+                if let Some(parent) = &edge.parent {
+                    if reaches_target.insert(parent) {
+                        queue.push_back(parent);
+                    }
+                }
+            }
+        }
+
+        let mut result = DependencyPaths {
+            paths: Vec::new(),
+            omitted: 0,
+        };
+        let mut search = PathSearch {
+            resolution: self,
+            target: name,
+            reaches_target: &reaches_target,
+            limit,
+            path: Vec::new(),
+            visited: HashSet::new(),
+            result: &mut result,
+        };
+        for root in self
+            .root_dependencies()
+            .filter(|d| reaches_target.contains(&d.name))
+        {
+            if !search.visit(root) {
+                break;
+            }
+        }
+
+        let root_index =
+            |edge: &Dependency| self.dependencies.iter().position(|d| std::ptr::eq(d, edge));
+        result
+            .paths
+            .sort_by_key(|path| (root_index(path[0]), path.len()));
+        result
+    }
+
+    /// Returns the candidates of the package with the given name that were not selected together
+    /// with the reason why. Candidates that could have been selected but that are not allowed by
+    /// one of the requirements on the package are reported with that requirement, other candidates
+    /// were not selected because a higher version was preferred.
+    pub fn rejected_candidates(&self, name: &NormalizedPackageName) -> Vec<(&Candidate, String)> {
+        let Some(package) = self.package(name) else {
+            return Vec::new();
+        };
+        let selected = match &package.url {
+            Some(url) => PypiVersion::Url(url.clone()),
+            None => PypiVersion::Version {
+                version: package.version.clone(),
+                package_allows_prerelease: false,
+            },
+        };
+
+        self.candidates
+            .get(name)
+            .into_iter()
+            .flatten()
+            .filter(|candidate| !is_same_version(&candidate.version, &selected))
+            .map(|candidate| {
+                let reason = if let Some(reason) = &candidate.excluded {
+                    reason.clone()
+                } else if let Some(dependency) = self
+                    .dependents(name)
+                    .find(|d| !requirement_allows(d, &candidate.version))
+                {
+                    let required_by = match &dependency.parent {
+                        Some(parent) => parent.to_string(),
+                        None => "the user".to_string(),
+                    };
+                    format!(
+                        "{required_by} requires {} {}",
+                        name,
+                        format_specifier(dependency)
+                    )
+                } else if candidate.version.any_prerelease() {
+                    "pre-releases are not allowed".to_string()
+                } else {
+                    format!("version {} was preferred", selected)
+                };
+                (candidate, reason)
+            })
+            .collect()
+    }
+}
+
+impl IntoIterator for Resolution {
+    type Item = PinnedPackage;
+    type IntoIter = std::vec::IntoIter<PinnedPackage>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.packages.into_iter()
+    }
+}
+
+fn is_same_version(a: &PypiVersion, b: &PypiVersion) -> bool {
+    match (a, b) {
+        (PypiVersion::Version { version: a, .. }, PypiVersion::Version { version: b, .. }) => {
+            a == b
+        }
+        (PypiVersion::Url(a), PypiVersion::Url(b)) => {
+            a == b || is_pinned_url_of(a, b) || is_pinned_url_of(b, a)
+        }
+        _ => false,
+    }
+}
+
+/// Returns true if the requirement of the edge allows the given version, ignoring pre-release
+/// semantics.
+fn requirement_allows(dependency: &Dependency, version: &PypiVersion) -> bool {
+    match (&dependency.version_or_url, version) {
+        (None, _) => true,
+        (
+            Some(VersionOrUrl::VersionSpecifier(specifiers)),
+            PypiVersion::Version { version, .. },
+        ) => specifiers.contains(version),
+        (Some(VersionOrUrl::Url(a)), PypiVersion::Url(b)) => a == b || is_pinned_url_of(a, b),
+        _ => false,
+    }
+}
+
+/// Formats the requirement of an edge, e.g. `>=2,<3` or `*` if no version was specified.
+fn format_specifier(dependency: &Dependency) -> String {
+    match &dependency.version_or_url {
+        None => "*".to_string(),
+        Some(VersionOrUrl::VersionSpecifier(specifiers)) if specifiers.is_empty() => {
+            "*".to_string()
+        }
+        Some(VersionOrUrl::VersionSpecifier(specifiers)) => specifiers.to_string(),
+        Some(VersionOrUrl::Url(url)) => url.to_string(),
+    }
+}
+
+/// Formats a package as `name[extras]`.
+fn format_name(name: &NormalizedPackageName, extras: &[Extra]) -> String {
+    if extras.is_empty() {
+        name.to_string()
+    } else {
+        format!(
+            "{name}[{}]",
+            extras.iter().map(|e| e.as_str()).sorted().join(",")
+        )
+    }
+}
+
+#[derive(Serialize)]
+struct JsonPackage<'a> {
+    name: &'a NormalizedPackageName,
+    version: String,
+    url: Option<String>,
+    extras: Vec<&'a str>,
+}
+
+#[derive(Serialize)]
+struct JsonDependency<'a> {
+    parent: Option<&'a NormalizedPackageName>,
+    parent_extra: Option<&'a str>,
+    name: &'a NormalizedPackageName,
+    extras: Vec<&'a str>,
+    specifier: String,
+    marker: Option<String>,
+}
+
+#[derive(Serialize)]
+struct JsonResolution<'a> {
+    packages: Vec<JsonPackage<'a>>,
+    dependencies: Vec<JsonDependency<'a>>,
+}
+
+impl Resolution {
+    /// Renders the dependency graph as a tree, similar to `pipdeptree`. Every requirement of the
+    /// user is the root of a tree, packages that occur more than once are only expanded the first
+    /// time.
+    ///
+    /// ```txt
+    /// requests==2.31.0
+    /// ├── certifi [required: >=2017.4.17, installed: 2024.2.2]
+    /// └── urllib3 [required: >=1.21.1, <3, installed: 2.2.0]
+    /// ```
+    pub fn to_tree(&self) -> String {
+        let mut output = String::new();
+        let mut expanded = HashSet::new();
+        for root in self
+            .root_dependencies()
+            .sorted_by(|a, b| a.name.cmp(&b.name))
+        {
+            let Some(package) = self.package(&root.name) else {
+                continue;
+            };
+            writeln!(
+                output,
+                "{}=={}",
+                format_name(&root.name, &root.extras),
+                package.version
+            )
+            .unwrap();
+            if expanded.insert(root.name.clone()) {
+                self.write_subtree(&mut output, &root.name, "", &mut expanded);
+            }
+        }
+        output
+    }
+
+    fn write_subtree(
+        &self,
+        output: &mut String,
+        name: &NormalizedPackageName,
+        prefix: &str,
+        expanded: &mut HashSet<NormalizedPackageName>,
+    ) {
+        let children = self
+            .dependencies_of(name)
+            .sorted_by(|a, b| a.name.cmp(&b.name))
+            .collect::<Vec<_>>();
+        for (index, child) in children.iter().enumerate() {
+            let is_last = index + 1 == children.len();
+            let version = self
+                .package(&child.name)
+                .map(|p| p.version.to_string())
+                .unwrap_or_default();
+            write!(
+                output,
+                "{prefix}{} {} [required: {}, installed: {version}",
+                if is_last { "└──" } else { "├──" },
+                format_name(&child.name, &child.extras),
+                format_specifier(child),
+            )
+            .unwrap();
+            if let Some(marker) = &child.marker {
+                write!(output, ", marker: {marker}").unwrap();
+            }
+            if let Some(extra) = &child.parent_extra {
+                write!(output, ", via extra: {}", extra.as_str()).unwrap();
+            }
+            output.push_str("]\n");
+
+            if expanded.insert(child.name.clone()) {
+                let prefix = format!("{prefix}{}", if is_last { "    " } else { "│   " });
+                self.write_subtree(output, &child.name, &prefix, expanded);
+            }
+        }
+    }
+
+    /// Renders the dependency graph in the DOT format of Graphviz. Edges are labeled with the
+    /// specifier (and marker) of the requirement.
+    pub fn to_dot(&self) -> String {
+        let mut output = String::from("digraph {\n");
+        for package in self.packages.iter().sorted_by(|a, b| a.name.cmp(&b.name)) {
+            writeln!(
+                output,
+                "    \"{}\" [label=\"{}=={}\"]",
+                package.name, package.name, package.version
+            )
+            .unwrap();
+        }
+        for dependency in self.dependencies.iter() {
+            let Some(parent) = &dependency.parent else {
+                continue;
+            };
+            let mut label = format_specifier(dependency);
+            if let Some(extra) = &dependency.parent_extra {
+                label = format!("[{}] {label}", extra.as_str());
+            }
+            if let Some(marker) = &dependency.marker {
+                write!(label, "; {marker}").unwrap();
+            }
+            writeln!(
+                output,
+                "    \"{parent}\" -> \"{}\" [label=\"{}\"]",
+                dependency.name,
+                label.replace('"', "\\\"")
+            )
+            .unwrap();
+        }
+        output.push_str("}\n");
+        output
+    }
+
+    /// Serializes the packages and the dependency graph as a JSON document.
+    pub fn to_json(&self) -> String {
+        let resolution = JsonResolution {
+            packages: self
+                .packages
+                .iter()
+                .sorted_by(|a, b| a.name.cmp(&b.name))
+                .map(|p| JsonPackage {
+                    name: &p.name,
+                    version: p.version.to_string(),
+                    url: p.url.as_ref().map(|u| u.to_string()),
+                    extras: p.extras.iter().map(|e| e.as_str()).sorted().collect(),
+                })
+                .collect(),
+            dependencies: self
+                .dependencies
+                .iter()
+                .map(|d| JsonDependency {
+                    parent: d.parent.as_ref(),
+                    parent_extra: d.parent_extra.as_ref().map(|e| e.as_str()),
+                    name: &d.name,
+                    extras: d.extras.iter().map(|e| e.as_str()).collect(),
+                    specifier: format_specifier(d),
+                    marker: d.marker.as_ref().map(|m| m.to_string()),
+                })
+                .collect(),
+        };
+        serde_json::to_string_pretty(&resolution).expect("failed to serialize resolution")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    fn package(name: &str, version: &str) -> PinnedPackage {
+        PinnedPackage {
+            name: name.parse().unwrap(),
+            version: version.parse().unwrap(),
+            url: None,
+            extras: Default::default(),
+            editable: false,
+            artifacts: Vec::new(),
+        }
+    }
+
+    fn dependency(parent: Option<&str>, name: &str, specifier: &str) -> Dependency {
+        Dependency {
+            parent: parent.map(|p| p.parse().unwrap()),
+            parent_extra: None,
+            name: name.parse().unwrap(),
+            extras: Vec::new(),
+            version_or_url: Some(VersionOrUrl::VersionSpecifier(specifier.parse().unwrap())),
+            marker: None,
+        }
+    }
+
+    fn resolution() -> Resolution {
+        let mut socks = dependency(Some("requests"), "pysocks", ">=1.5.6");
+        socks.parent_extra = Some("socks".parse().unwrap());
+        socks.marker = Some(MarkerTree::from_str("extra == 'socks'").unwrap());
+
+        Resolution {
+            packages: vec![
+                package("requests", "2.31.0"),
+                package("urllib3", "2.2.0"),
+                package("certifi", "2024.2.2"),
+                package("pysocks", "1.7.1"),
+            ],
+            dependencies: vec![
+                dependency(None, "requests", ">=2"),
+                dependency(None, "certifi", ""),
+                dependency(Some("requests"), "urllib3", ">=1.21.1,<3"),
+                dependency(Some("requests"), "certifi", ">=2017.4.17"),
+                socks,
+            ],
+            candidates: HashMap::from([(
+                "urllib3".parse().unwrap(),
+                vec![
+                    candidate("1.26.18", None),
+                    candidate("2.2.0", None),
+                    candidate("2.2.1", Some("it is yanked")),
+                    candidate("3.0.0", None),
+                ],
+            )]),
+        }
+    }
+
+    fn candidate(version: &str, excluded: Option<&str>) -> Candidate {
+        Candidate {
+            version: PypiVersion::Version {
+                version: version.parse().unwrap(),
+                package_allows_prerelease: false,
+            },
+            excluded: excluded.map(ToString::to_string),
+        }
+    }
+
+    #[test]
+    fn test_paths_to() {
+        let resolution = resolution();
+        let paths = resolution
+            .paths_to(&"certifi".parse().unwrap(), 10)
+            .paths
+            .into_iter()
+            .map(|path| path.iter().map(|d| d.name.to_string()).join(" -> "))
+            .collect::<Vec<_>>();
+        assert_eq!(paths, vec!["requests -> certifi", "certifi"]);
+    }
+
+    #[test]
+    fn test_paths_to_diamonds() {
+        // A chain of 40 diamonds has 2^40 paths from the top to the bottom
+        let mut dependencies = vec![dependency(None, "p0", "")];
+        for i in 0..40 {
+            for side in ["a", "b"] {
+                dependencies.push(dependency(
+                    Some(&format!("p{i}")),
+                    &format!("{side}{i}"),
+                    "",
+                ));
+                dependencies.push(dependency(
+                    Some(&format!("{side}{i}")),
+                    &format!("p{}", i + 1),
+                    "",
+                ));
+            }
+        }
+        let resolution = Resolution {
+            dependencies,
+            ..Default::default()
+        };
+
+        let paths = resolution.paths_to(&"p40".parse().unwrap(), 5);
+        assert_eq!(paths.paths.len(), 5);
+        assert!(paths.paths.iter().all(|path| path.len() == 81));
+        assert_eq!(paths.omitted, MAX_OMITTED_PATHS);
+
+        // Every path through a small diamond is returned
+        let paths = resolution.paths_to(&"p2".parse().unwrap(), 10);
+        let paths = paths
+            .paths
+            .iter()
+            .map(|path| path.iter().map(|d| d.name.to_string()).join(" -> "))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec![
+                "p0 -> a0 -> p1 -> a1 -> p2",
+                "p0 -> a0 -> p1 -> b1 -> p2",
+                "p0 -> b0 -> p1 -> a1 -> p2",
+                "p0 -> b0 -> p1 -> b1 -> p2",
+            ]
+        );
+        assert_eq!(resolution.paths_to(&"p2".parse().unwrap(), 3).omitted, 1);
+    }
+
+    #[test]
+    fn test_format_dependency() {
+        let resolution = resolution();
+        let edges = resolution
+            .dependencies
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            edges,
+            vec![
+                "requests >=2",
+                "certifi",
+                "urllib3 >=1.21.1, <3",
+                "certifi >=2017.4.17",
+                "pysocks >=1.5.6 ; extra == 'socks'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rejected_candidates() {
+        let resolution = resolution();
+        let rejected = resolution
+            .rejected_candidates(&"urllib3".parse().unwrap())
+            .into_iter()
+            .map(|(candidate, reason)| format!("{}: {reason}", candidate.version))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rejected,
+            vec![
+                "1.26.18: version 2.2.0 was preferred",
+                "2.2.1: it is yanked",
+                "3.0.0: requests requires urllib3 >=1.21.1, <3",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_to_tree() {
+        insta::assert_snapshot!(resolution().to_tree());
+    }
+
+    #[test]
+    fn test_to_dot() {
+        insta::assert_snapshot!(resolution().to_dot());
+    }
+
+    #[test]
+    fn test_to_json() {
+        insta::assert_snapshot!(resolution().to_json());
+    }
+}
@@ -2,6 +2,7 @@ use crate::index::PackageDb;
 use crate::python_env::WheelTags;
 use crate::resolve::dependency_provider::PypiDependencyProvider;
 use crate::resolve::pypi_version_types::PypiVersion;
+use crate::resolve::{Dependency, Resolution};
 use crate::types::PackageName;
 use crate::{types::ArtifactInfo, types::Extra, types::NormalizedPackageName};
 use elsa::FrozenMap;
@@ -18,6 +19,7 @@ use std::collections::HashSet;
 use std::convert::identity;
 use std::ops::Deref;
 use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
 
 /// Represents a single locked down distribution (python package) after calling [`resolve`].
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -39,6 +41,10 @@ pub struct PinnedPackage {
     ///
     /// This list may be empty if the package was locked or favored.
     pub artifacts: Vec<Arc<ArtifactInfo>>,
+
+    /// True if the package should be installed in editable mode (PEP 660). Only packages that
+    /// refer to a local source tree can be editable.
+    pub editable: bool,
 }
 
 /// Resolves an environment that contains the given requirements and all dependencies of those
@@ -62,8 +68,14 @@ pub async fn resolve(
     favored_packages: HashMap<NormalizedPackageName, PinnedPackage>,
     options: ResolveOptions,
     env_variables: HashMap<String, String>,
-) -> miette::Result<Vec<PinnedPackage>> {
+) -> miette::Result<Resolution> {
     let requirements: Vec<_> = requirements.into_iter().cloned().collect();
+
+    // The resolution runs on a blocking thread which cannot be aborted, when this future is dropped
+    // the token is cancelled instead which stops the solver and kills any running builds.
+    let cancellation_token = CancellationToken::new();
+    let _cancel_on_drop = cancellation_token.clone().drop_guard();
+
     tokio::task::spawn_blocking(move || {
         resolve_inner(
             package_db,
@@ -74,6 +86,7 @@ pub async fn resolve(
             favored_packages,
             options,
             env_variables,
+            cancellation_token,
         )
     })
     .await
@@ -96,7 +109,8 @@ fn resolve_inner<'r>(
     favored_packages: HashMap<NormalizedPackageName, PinnedPackage>,
     options: ResolveOptions,
     env_variables: HashMap<String, String>,
-) -> miette::Result<Vec<PinnedPackage>> {
+    cancellation_token: CancellationToken,
+) -> miette::Result<Resolution> {
     // Construct the pool
     let pool = Pool::new();
 
@@ -109,19 +123,33 @@ fn resolve_inner<'r>(
     let mut root_requirements =
         Vec::with_capacity(requirement_count.1.unwrap_or(requirement_count.0));
 
+    let mut root_dependencies = Vec::new();
     for Requirement {
         name,
         version_or_url,
         extras,
-        ..
+        marker,
     } in requirements
     {
         let name = PackageName::from_str(name).expect("invalid package name");
+        root_dependencies.push(Dependency {
+            parent: None,
+            parent_extra: None,
+            name: name.clone().into(),
+            extras: extras
+                .iter()
+                .flatten()
+                .map(|e| e.parse().expect("invalid extra"))
+                .collect(),
+            version_or_url: version_or_url.clone(),
+            marker: marker.clone(),
+        });
         let pypi_name = PypiPackageName::Base(name.clone().into());
+        let pre_release_resolution = options.pre_release_resolution_for(pypi_name.base());
         let dependency_package_name = pool.intern_package_name(pypi_name.clone());
         let version_set_id = pool.intern_version_set(
             dependency_package_name,
-            PypiVersionSet::from_spec(version_or_url.clone(), &options.pre_release_resolution),
+            PypiVersionSet::from_spec(version_or_url.clone(), pre_release_resolution),
         );
         root_requirements.push(version_set_id);
 
@@ -135,12 +163,22 @@ fn resolve_inner<'r>(
                 .intern_package_name(PypiPackageName::Extra(name.clone().into(), extra.clone()));
             let version_set_id = pool.intern_version_set(
                 dependency_package_name,
-                PypiVersionSet::from_spec(version_or_url.clone(), &options.pre_release_resolution),
+                PypiVersionSet::from_spec(version_or_url.clone(), pre_release_resolution),
             );
             root_requirements.push(version_set_id);
         }
     }
 
+    // Constraints that refer to a URL restrict the package to that URL
+    for constraint in options.constraints.iter() {
+        if let Some(VersionOrUrl::Url(url)) = &constraint.version_or_url {
+            let name = PackageName::from_str(&constraint.name).expect("invalid package name");
+            name_to_url.insert(name.into(), url.as_str().to_owned());
+        }
+    }
+
+    let editables = options.editables.clone();
+
     // Construct the provider
     let provider = PypiDependencyProvider::new(
         pool,
@@ -152,6 +190,7 @@ fn resolve_inner<'r>(
         name_to_url,
         options,
         env_variables,
+        cancellation_token,
     )?;
 
     // Invoke the solver to get a solution to the requirements
@@ -180,6 +219,17 @@ fn resolve_inner<'r>(
         }
     };
     let mut result: HashMap<NormalizedPackageName, PinnedPackage> = HashMap::new();
+    let mut dependencies = root_dependencies;
+    for &solvable_id in solvables.iter() {
+        dependencies.extend(
+            provider
+                .requirements
+                .get(&solvable_id)
+                .into_iter()
+                .flatten()
+                .cloned(),
+        );
+    }
     for solvable_id in solvables {
         let solvable = solver.pool.resolve_solvable(solvable_id);
         let name = solver.pool.resolve_package_name(solvable.name_id());
@@ -195,14 +245,29 @@ fn resolve_inner<'r>(
 
         let (version, url) = match version {
             PypiVersion::Version { version, .. } => (version.clone(), None),
-            PypiVersion::Url(url) => {
-                // artifacts retrieved by url have only one artifact and one possible version
+            PypiVersion::Url(_) => {
+                // artifacts retrieved by url have only one artifact and one possible version. The
+                // url of the artifact can be more specific than the requested url, e.g. a git url
+                // that is pinned to the commit that was checked out.
                 let info = artifacts
                     .first()
                     .expect("no artifacts found for direct_url artifact");
-                (info.filename.version(), Some(url.clone()))
+                (info.filename.version(), Some(info.url.clone()))
             }
         };
+        // Only local source trees can be installed in editable mode, other editable requirements
+        // (e.g. `-e git+https://...`) are installed as regular packages.
+        let editable = editables.contains(name.base())
+            && artifacts.first().is_some_and(|info| {
+                info.filename.as_stree().is_some() && info.url.scheme() == "file"
+            });
+        if editables.contains(name.base()) && !editable && matches!(name, PypiPackageName::Base(_))
+        {
+            tracing::warn!(
+                "editable installs are only supported for local source trees, {} will be installed as a regular package",
+                name.base()
+            );
+        }
 
         // Get the entry in the result
         let entry = result
@@ -213,6 +278,7 @@ fn resolve_inner<'r>(
                 url,
                 artifacts,
                 extras: Default::default(),
+                editable,
             });
 
         // Add the extra if selected
@@ -221,7 +287,21 @@ fn resolve_inner<'r>(
         }
     }
 
-    Ok(result.into_values().collect())
+    // Only keep the edges to packages that are part of the solution
+    dependencies.retain(|d| result.contains_key(&d.name));
+
+    let candidates = provider
+        .considered_candidates
+        .into_tuple_vec()
+        .into_iter()
+        .filter(|(name, _)| result.contains_key(name))
+        .collect();
+
+    Ok(Resolution {
+        packages: result.into_values().collect(),
+        dependencies,
+        candidates,
+    })
 }
 
 #[cfg(test)]
@@ -2,11 +2,14 @@
 
 use crate::python_env::PythonLocation;
 use pep508_rs::{Requirement, VersionOrUrl};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Semaphore;
 
-use crate::types::PackageName;
+use crate::types::{NormalizedPackageName, PackageName};
 
 /// Defines how to handle sdists during resolution.
 #[derive(Default, Debug, Clone, Copy, Eq, PartialOrd, PartialEq)]
@@ -177,6 +180,23 @@ impl SDistResolution {
     }
 }
 
+/// Resolution options that only apply to a single package. Any option that is `None` falls back to
+/// the global value defined in [`ResolveOptions`].
+///
+/// This makes it possible to, for example, only allow pre-releases for `jax`, or to only allow
+/// sdists for `psycopg2` while wheels are required for all other packages.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct PackageResolveOptions {
+    /// Overrides [`ResolveOptions::sdist_resolution`] for this package.
+    pub sdist_resolution: Option<SDistResolution>,
+
+    /// Overrides [`ResolveOptions::pre_release_resolution`] for this package.
+    pub pre_release_resolution: Option<PreReleaseResolution>,
+
+    /// Overrides [`ResolveOptions::config_settings`] for this package.
+    pub config_settings: Option<HashMap<String, Vec<String>>>,
+}
+
 /// Specifies what to do with failed build environments
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub enum OnWheelBuildFailure {
@@ -187,6 +207,36 @@ pub enum OnWheelBuildFailure {
     DeleteBuildEnv,
 }
 
+/// Defines in which environment sdists are built.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum BuildIsolation {
+    /// Build every sdist in a new virtual environment into which the build requirements of the
+    /// sdist are installed.
+    #[default]
+    Isolated,
+
+    /// Build sdists in an existing environment, similar to pip's `--no-build-isolation`. The path
+    /// refers to a python interpreter or to the root of a virtual environment. The build
+    /// requirements are not resolved or installed, instead they must already be installed in the
+    /// environment.
+    NoIsolation(PathBuf),
+}
+
+/// Restrictions that are applied to the build backend while it runs. Sandboxing is only
+/// supported on Linux where it uses unprivileged user, mount and network namespaces.
+///
+/// The build requirements are installed before the sandbox is entered, the build backend itself
+/// has no network access. The source tree is mounted read-only, only the directory into which the
+/// backend writes its output is writable.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BuildSandbox {
+    /// The maximum amount of CPU time a single build backend hook may use
+    pub cpu_time_limit: Option<Duration>,
+
+    /// The maximum size of the address space of the build backend in bytes
+    pub memory_limit: Option<u64>,
+}
+
 /// Additional options that may influence the solver. In general passing [`Default::default`] to
 /// the [`super::resolve`] function should provide sane defaults, however if you want to fine tune the
 /// resolver you can do so via this struct.
@@ -203,6 +253,22 @@ pub struct ResolveOptions {
     /// Defines if we should inherit env variables during build process of wheel files
     pub clean_env: bool,
 
+    /// Defines whether sdists are built in a new virtual environment or in an existing
+    /// environment.
+    pub build_isolation: BuildIsolation,
+
+    /// Run the build backend in a sandbox, by default the build backend runs with the privileges
+    /// of the current user.
+    pub build_sandbox: Option<BuildSandbox>,
+
+    /// The maximum amount of time a single build backend hook may run before it is killed. By
+    /// default there is no limit.
+    pub build_timeout: Option<Duration>,
+
+    /// Keep the environments with the build requirements of sdists in the cache directory so
+    /// they can be reused by later runs. By default they are only shared within a single run.
+    pub persist_build_environments: bool,
+
     /// Defines what to do with failed build environments
     /// by default these are deleted but can also be saved for debugging purposes
     pub on_wheel_build_failure: OnWheelBuildFailure,
@@ -211,6 +277,24 @@ pub struct ResolveOptions {
     /// pre-releases are not allowed (only if there are no other versions available for a given dependency).
     pub pre_release_resolution: PreReleaseResolution,
 
+    /// The PEP 517 `config_settings` that are passed to every hook of the build backend when an
+    /// sdist is built. A key with a single value is passed as a string, a key with multiple
+    /// values as a list of strings.
+    pub config_settings: HashMap<String, Vec<String>>,
+
+    /// Per-package overrides of `sdist_resolution`, `pre_release_resolution` and
+    /// `config_settings`.
+    pub package_options: HashMap<NormalizedPackageName, PackageResolveOptions>,
+
+    /// Constraints on the versions of packages (e.g. from a `-c constraints.txt` file). A
+    /// constraint does not add the package to the environment but if the package is required,
+    /// only versions that satisfy the constraint can be selected.
+    pub constraints: Vec<Requirement>,
+
+    /// The names of the packages that are installed in editable mode (PEP 660). This only
+    /// applies to packages that are required through a `file://` URL of a local source tree.
+    pub editables: HashSet<NormalizedPackageName>,
+
     /// Limits the amount of concurrent tasks when resolving.
     pub max_concurrent_tasks: Arc<Semaphore>,
 }
@@ -223,6 +307,39 @@ impl ResolveOptions {
             ..Default::default()
         }
     }
+
+    /// Returns the [`SDistResolution`] that applies to the package with the given name. This is
+    /// the per-package override if one exists, otherwise the global value.
+    pub fn sdist_resolution_for(&self, name: &NormalizedPackageName) -> SDistResolution {
+        self.package_options
+            .get(name)
+            .and_then(|o| o.sdist_resolution)
+            .unwrap_or(self.sdist_resolution)
+    }
+
+    /// Returns the [`PreReleaseResolution`] that applies to the package with the given name. This
+    /// is the per-package override if one exists, otherwise the global value.
+    pub fn pre_release_resolution_for(
+        &self,
+        name: &NormalizedPackageName,
+    ) -> &PreReleaseResolution {
+        self.package_options
+            .get(name)
+            .and_then(|o| o.pre_release_resolution.as_ref())
+            .unwrap_or(&self.pre_release_resolution)
+    }
+
+    /// Returns the `config_settings` that are passed to the build backend of the package with the
+    /// given name. This is the per-package override if one exists, otherwise the global value.
+    pub fn config_settings_for(
+        &self,
+        name: &NormalizedPackageName,
+    ) -> &HashMap<String, Vec<String>> {
+        self.package_options
+            .get(name)
+            .and_then(|o| o.config_settings.as_ref())
+            .unwrap_or(&self.config_settings)
+    }
 }
 
 impl Default for ResolveOptions {
@@ -231,9 +348,76 @@ impl Default for ResolveOptions {
             sdist_resolution: SDistResolution::default(),
             python_location: PythonLocation::default(),
             clean_env: false,
+            build_isolation: BuildIsolation::default(),
+            build_sandbox: None,
+            build_timeout: None,
+            persist_build_environments: false,
             on_wheel_build_failure: OnWheelBuildFailure::default(),
             pre_release_resolution: PreReleaseResolution::default(),
+            config_settings: HashMap::default(),
+            package_options: HashMap::default(),
+            constraints: Vec::new(),
+            editables: HashSet::default(),
             max_concurrent_tasks: Arc::new(Semaphore::new(30)),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_package_options_fall_back_to_global() {
+        let jax: NormalizedPackageName = "jax".parse().unwrap();
+        let psycopg2: NormalizedPackageName = "psycopg2".parse().unwrap();
+        let numpy: NormalizedPackageName = "numpy".parse().unwrap();
+
+        let options = ResolveOptions {
+            sdist_resolution: SDistResolution::OnlyWheels,
+            pre_release_resolution: PreReleaseResolution::Disallow,
+            package_options: HashMap::from_iter([
+                (
+                    jax.clone(),
+                    PackageResolveOptions {
+                        pre_release_resolution: Some(PreReleaseResolution::Allow),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    psycopg2.clone(),
+                    PackageResolveOptions {
+                        sdist_resolution: Some(SDistResolution::OnlySDists),
+                        ..Default::default()
+                    },
+                ),
+            ]),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            options.sdist_resolution_for(&jax),
+            SDistResolution::OnlyWheels
+        );
+        assert_eq!(
+            options.pre_release_resolution_for(&jax),
+            &PreReleaseResolution::Allow
+        );
+        assert_eq!(
+            options.sdist_resolution_for(&psycopg2),
+            SDistResolution::OnlySDists
+        );
+        assert_eq!(
+            options.pre_release_resolution_for(&psycopg2),
+            &PreReleaseResolution::Disallow
+        );
+        assert_eq!(
+            options.sdist_resolution_for(&numpy),
+            SDistResolution::OnlyWheels
+        );
+        assert_eq!(
+            options.pre_release_resolution_for(&numpy),
+            &PreReleaseResolution::Disallow
+        );
+    }
+}
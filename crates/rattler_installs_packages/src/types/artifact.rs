@@ -2,7 +2,7 @@ use super::artifact_name::InnerAsArtifactName;
 use crate::resolve::PypiVersion;
 use crate::types::SourceArtifactName;
 use crate::utils::ReadAndSeek;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 /// Trait to implement if it is a type that has an [`super::artifact_name::ArtifactName`]
 /// this is then used by the [`crate::index::PackageDb`] to make a difference
@@ -64,4 +64,10 @@ pub trait ArtifactFromSource: HasArtifactName + Sync {
     /// for stree we move it
     /// as example this method is used by install_build_files
     fn extract_to(&self, work_dir: &Path) -> std::io::Result<()>;
+
+    /// The location of the source tree of the user that the artifact refers to, if any. The build
+    /// backend runs on a copy of the artifact and should not modify this directory.
+    fn source_tree(&self) -> Option<PathBuf> {
+        None
+    }
 }
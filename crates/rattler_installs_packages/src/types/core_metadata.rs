@@ -9,7 +9,7 @@ use crate::{
 use once_cell::sync::Lazy;
 use pep440_rs::Pep440Error;
 use pep508_rs::Requirement;
-use std::{collections::HashSet, str::FromStr};
+use std::{collections::HashSet, fmt::Display, str::FromStr};
 use thiserror::Error;
 
 /// Holds the parsed PKG-INFO file.
@@ -52,12 +52,297 @@ pub struct WheelCoreMetadata {
     pub extras: HashSet<Extra>,
 }
 
+/// The complete core metadata of a distribution as stored in the METADATA file of a wheel or the
+/// PKG-INFO file of an sdist. See the
+/// [Core metadata specifications](https://packaging.python.org/en/latest/specifications/core-metadata/)
+/// for the meaning of the fields.
+///
+/// Unlike [`WheelCoreMetadata`], which only holds what is needed to resolve a package, this keeps
+/// all the fields. It can be written back in the METADATA format through its [`Display`]
+/// implementation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoreMetadata {
+    /// Version of the metadata
+    pub metadata_version: MetadataVersion,
+    /// The name of the package
+    pub name: PackageName,
+    /// Version w.r.t to PEP440
+    pub version: Version,
+    /// Fields that are computed by the build backend when building a wheel (since 2.2)
+    pub dynamic: Vec<String>,
+    /// Operating systems supported by the package
+    pub platforms: Vec<String>,
+    /// Platforms the binary package was compiled for (since 1.1)
+    pub supported_platforms: Vec<String>,
+    /// One line summary of the package
+    pub summary: Option<String>,
+    /// Longer description of the package, stored in the body of the file since 2.1
+    pub description: Option<String>,
+    /// Markup of the description, e.g. `text/markdown` (since 2.1)
+    pub description_content_type: Option<String>,
+    /// Comma separated list of keywords
+    pub keywords: Option<String>,
+    /// URL of the homepage of the package
+    pub home_page: Option<String>,
+    /// URL from which the package can be downloaded (since 1.1)
+    pub download_url: Option<String>,
+    /// Name of the author
+    pub author: Option<String>,
+    /// Email address of the author
+    pub author_email: Option<String>,
+    /// Name of the maintainer (since 1.2)
+    pub maintainer: Option<String>,
+    /// Email address of the maintainer (since 1.2)
+    pub maintainer_email: Option<String>,
+    /// Free form license text, superseded by `license_expression`
+    pub license: Option<String>,
+    /// SPDX license expression (since 2.4, see PEP 639)
+    pub license_expression: Option<String>,
+    /// Paths of the license files, relative to the `licenses` directory in .dist-info (since 2.4)
+    pub license_files: Vec<String>,
+    /// Trove classifiers (since 1.1)
+    pub classifiers: Vec<String>,
+    /// Requirements for this distribution (since 1.2)
+    /// Matches the Requires-Dist field
+    pub requires_dist: Vec<Requirement>,
+    /// Python requirement (since 1.2)
+    pub requires_python: Option<VersionSpecifiers>,
+    /// Dependencies outside of python, e.g. C libraries (since 1.2)
+    pub requires_external: Vec<String>,
+    /// Labeled URLs of the project, e.g. `("Documentation", "https://...")` (since 1.2)
+    pub project_urls: Vec<(String, String)>,
+    /// Extras provided by this distribution (since 2.1)
+    pub provides_extra: Vec<Extra>,
+    /// Distributions contained in this distribution (since 1.2)
+    pub provides_dist: Vec<String>,
+    /// Distributions that this distribution makes obsolete (since 1.2)
+    pub obsoletes_dist: Vec<String>,
+    /// Deprecated Requires field (1.1)
+    pub requires: Vec<String>,
+    /// Deprecated Provides field (1.1)
+    pub provides: Vec<String>,
+    /// Deprecated Obsoletes field (1.1)
+    pub obsoletes: Vec<String>,
+}
+
+/// All the fields of the core metadata and the Metadata-Version they were introduced in, in the
+/// order in which they are written.
+const FIELDS: &[(&str, &str)] = &[
+    ("Metadata-Version", "1.0"),
+    ("Name", "1.0"),
+    ("Version", "1.0"),
+    ("Dynamic", "2.2"),
+    ("Platform", "1.0"),
+    ("Supported-Platform", "1.1"),
+    ("Summary", "1.0"),
+    ("Description-Content-Type", "2.1"),
+    ("Keywords", "1.0"),
+    ("Home-page", "1.0"),
+    ("Download-URL", "1.1"),
+    ("Author", "1.0"),
+    ("Author-email", "1.0"),
+    ("Maintainer", "1.2"),
+    ("Maintainer-email", "1.2"),
+    ("License", "1.0"),
+    ("License-Expression", "2.4"),
+    ("License-File", "2.4"),
+    ("Classifier", "1.1"),
+    ("Requires-Dist", "1.2"),
+    ("Requires-Python", "1.2"),
+    ("Requires-External", "1.2"),
+    ("Project-URL", "1.2"),
+    ("Provides-Extra", "2.1"),
+    ("Provides-Dist", "1.2"),
+    ("Obsoletes-Dist", "1.2"),
+    ("Requires", "1.1"),
+    ("Provides", "1.1"),
+    ("Obsoletes", "1.1"),
+    ("Description", "1.0"),
+];
+
+impl CoreMetadata {
+    /// Returns the name and value of all fields that are set, in the order of [`FIELDS`]. The
+    /// description is not included because it is written to the body of the file.
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        let single = |name, value: &Option<String>| {
+            value.iter().map(|v| (name, v.clone())).collect::<Vec<_>>()
+        };
+        let multiple =
+            |name, values: &[String]| values.iter().map(|v| (name, v.clone())).collect::<Vec<_>>();
+
+        let mut fields = vec![
+            ("Metadata-Version", self.metadata_version.0.to_string()),
+            ("Name", self.name.as_source_str().to_owned()),
+            ("Version", self.version.to_string()),
+        ];
+        fields.extend(multiple("Dynamic", &self.dynamic));
+        fields.extend(multiple("Platform", &self.platforms));
+        fields.extend(multiple("Supported-Platform", &self.supported_platforms));
+        fields.extend(single("Summary", &self.summary));
+        fields.extend(single(
+            "Description-Content-Type",
+            &self.description_content_type,
+        ));
+        fields.extend(single("Keywords", &self.keywords));
+        fields.extend(single("Home-page", &self.home_page));
+        fields.extend(single("Download-URL", &self.download_url));
+        fields.extend(single("Author", &self.author));
+        fields.extend(single("Author-email", &self.author_email));
+        fields.extend(single("Maintainer", &self.maintainer));
+        fields.extend(single("Maintainer-email", &self.maintainer_email));
+        fields.extend(single("License", &self.license));
+        fields.extend(single("License-Expression", &self.license_expression));
+        fields.extend(multiple("License-File", &self.license_files));
+        fields.extend(multiple("Classifier", &self.classifiers));
+        fields.extend(
+            self.requires_dist
+                .iter()
+                .map(|req| ("Requires-Dist", req.to_string())),
+        );
+        fields.extend(
+            self.requires_python
+                .iter()
+                .map(|specifiers| ("Requires-Python", specifiers.to_string())),
+        );
+        fields.extend(multiple("Requires-External", &self.requires_external));
+        fields.extend(
+            self.project_urls
+                .iter()
+                .map(|(label, url)| ("Project-URL", format!("{label}, {url}"))),
+        );
+        fields.extend(
+            self.provides_extra
+                .iter()
+                .map(|extra| ("Provides-Extra", extra.as_source_str().to_owned())),
+        );
+        fields.extend(multiple("Provides-Dist", &self.provides_dist));
+        fields.extend(multiple("Obsoletes-Dist", &self.obsoletes_dist));
+        fields.extend(multiple("Requires", &self.requires));
+        fields.extend(multiple("Provides", &self.provides));
+        fields.extend(multiple("Obsoletes", &self.obsoletes));
+        fields
+    }
+
+    /// Checks that the metadata is valid for its Metadata-Version. Fields must not be newer than
+    /// the Metadata-Version, `Dynamic` may only contain known fields other than `Name` and
+    /// `Version`, since 2.3 extras must be normalized and license files must be relative paths.
+    pub fn validate(&self) -> Result<(), WheelCoreMetaDataError> {
+        let mut fields = self.fields();
+        if self.description.is_some() {
+            fields.push(("Description", String::new()));
+        }
+        for (field, _) in fields {
+            let introduced = FIELDS
+                .iter()
+                .find_map(|(name, introduced)| (*name == field).then_some(*introduced))
+                .expect("all fields are known");
+            if self.metadata_version < MetadataVersion::from_static(introduced) {
+                return Err(WheelCoreMetaDataError::UnsupportedField {
+                    field: field.to_owned(),
+                    introduced,
+                    metadata_version: self.metadata_version.0.to_string(),
+                });
+            }
+        }
+
+        for field in &self.dynamic {
+            match FIELDS
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(field))
+            {
+                None | Some(("Metadata-Version" | "Name" | "Version", _)) => {
+                    return Err(WheelCoreMetaDataError::InvalidDynamic(field.clone()))
+                }
+                Some(_) => {}
+            }
+        }
+
+        if self.metadata_version >= MetadataVersion::from_static("2.3") {
+            if let Some(extra) = self
+                .provides_extra
+                .iter()
+                .find(|extra| extra.as_source_str() != extra.as_str())
+            {
+                return Err(WheelCoreMetaDataError::UnnormalizedExtra(
+                    extra.as_source_str().to_owned(),
+                ));
+            }
+        }
+
+        if let Some(path) = self.license_files.iter().find(|path| {
+            path.starts_with('/')
+                || path.contains(['\\', ':'])
+                || path.split('/').any(|c| c == "..")
+        }) {
+            return Err(WheelCoreMetaDataError::InvalidLicenseFile(path.clone()));
+        }
+
+        Ok(())
+    }
+}
+
+/// Indentation of the continuation lines of a multi-line field value. The description uses the
+/// `|` variant so that empty and indented lines are preserved by other parsers too.
+const CONTINUATION_INDENT: &str = "        ";
+const DESCRIPTION_CONTINUATION_INDENT: &str = "       |";
+
+/// Removes the indentation of the continuation lines of a multi-line field value, the inverse of
+/// how multi-line values are written by [`CoreMetadata`]'s `Display` implementation.
+fn unfold(value: &str) -> String {
+    let mut lines = value.split('\n');
+    let mut unfolded = lines.next().unwrap_or_default().to_owned();
+    for line in lines {
+        let line = line
+            .strip_prefix(DESCRIPTION_CONTINUATION_INDENT)
+            .or_else(|| line.strip_prefix(CONTINUATION_INDENT))
+            .unwrap_or_else(|| line.trim_start());
+        unfolded.push('\n');
+        unfolded.push_str(line);
+    }
+    unfolded
+}
+
+impl Display for CoreMetadata {
+    /// Writes the metadata in the METADATA format. Since Metadata-Version 2.1 the description is
+    /// written to the body, before that it is written to the `Description` field.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let description_in_body = self.metadata_version >= MetadataVersion::from_static("2.1");
+        let description_field = self
+            .description
+            .as_ref()
+            .filter(|_| !description_in_body)
+            .map(|description| ("Description", description.clone()));
+        for (field, value) in self.fields().into_iter().chain(description_field) {
+            // Continuation lines have to be indented, otherwise they are parsed as a new field
+            let indent = if field == "Description" {
+                DESCRIPTION_CONTINUATION_INDENT
+            } else {
+                CONTINUATION_INDENT
+            };
+            let mut lines = value.split('\n');
+            write!(f, "{field}: {}", lines.next().unwrap_or_default())?;
+            for line in lines {
+                write!(f, "\n{indent}{line}")?;
+            }
+            writeln!(f)?;
+        }
+        if let Some(description) = self.description.as_ref().filter(|_| description_in_body) {
+            write!(f, "\n{description}")?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 /// Wrapper around a PEP440 version
 /// specifically for the metadata version
 pub struct MetadataVersion(pub Version);
 
 impl MetadataVersion {
+    fn from_static(version: &'static str) -> Self {
+        MetadataVersion(Version::from_str(version).expect("invalid metadata version"))
+    }
+
     /// We consider that this implements PEP643
     /// if the version is 2.3 or higher.
     pub fn implements_pep643(&self) -> bool {
@@ -104,6 +389,24 @@ pub enum WheelCoreMetaDataError {
 
     #[error("{0}")]
     FailedToParse(String),
+
+    #[error("{field} requires Metadata-Version {introduced} but the metadata has version {metadata_version}")]
+    UnsupportedField {
+        field: String,
+        introduced: &'static str,
+        metadata_version: String,
+    },
+
+    #[error("'{0}' cannot be marked as Dynamic")]
+    InvalidDynamic(String),
+
+    #[error(
+        "Provides-Extra '{0}' is not normalized, which is required since Metadata-Version 2.3"
+    )]
+    UnnormalizedExtra(String),
+
+    #[error("invalid License-File '{0}', license files must be relative paths")]
+    InvalidLicenseFile(String),
 }
 
 impl TryFrom<&[u8]> for WheelCoreMetadata {
@@ -117,8 +420,40 @@ impl TryFrom<&[u8]> for WheelCoreMetadata {
 impl TryFrom<PackageInfo> for WheelCoreMetadata {
     type Error = WheelCoreMetaDataError;
 
+    fn try_from(value: PackageInfo) -> Result<Self, Self::Error> {
+        CoreMetadata::try_from(value).map(Into::into)
+    }
+}
+
+impl From<CoreMetadata> for WheelCoreMetadata {
+    fn from(value: CoreMetadata) -> Self {
+        WheelCoreMetadata {
+            name: value.name,
+            version: value.version,
+            metadata_version: value.metadata_version,
+            requires_dist: value.requires_dist,
+            requires_python: value.requires_python,
+            extras: value.provides_extra.into_iter().collect(),
+        }
+    }
+}
+
+impl TryFrom<&[u8]> for CoreMetadata {
+    type Error = WheelCoreMetaDataError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        PackageInfo::from_bytes(value)?.try_into()
+    }
+}
+
+impl TryFrom<PackageInfo> for CoreMetadata {
+    type Error = WheelCoreMetaDataError;
+
     fn try_from(value: PackageInfo) -> Result<Self, Self::Error> {
         let (name, version, metadata_version, mut parsed) = parse_common(value)?;
+        for value in parsed.fields.values_mut().flatten() {
+            *value = unfold(value);
+        }
 
         let mut requires_dist = Vec::new();
         for req_str in parsed.take_all("Requires-Dist").into_iter() {
@@ -138,26 +473,77 @@ impl TryFrom<PackageInfo> for WheelCoreMetadata {
             .transpose()
             .map_err(WheelCoreMetaDataError::InvalidRequiresPython)?;
 
-        let mut extras: HashSet<Extra> = HashSet::new();
+        let mut provides_extra = Vec::new();
         for extra in parsed.take_all("Provides-Extra").drain(..) {
-            extras.insert(
-                extra
-                    .parse()
-                    .map_err(|e| WheelCoreMetaDataError::InvalidExtra(extra, e))?,
-            );
+            let extra: Extra = extra
+                .parse()
+                .map_err(|e| WheelCoreMetaDataError::InvalidExtra(extra, e))?;
+            if !provides_extra.contains(&extra) {
+                provides_extra.push(extra);
+            }
         }
 
-        Ok(WheelCoreMetadata {
+        // Since Metadata-Version 2.1 the description is stored in the body
+        let description = parsed
+            .body
+            .take()
+            .filter(|body| !body.trim().is_empty())
+            .or_else(|| take_first(&mut parsed, "Description"));
+
+        let project_urls = parsed
+            .take_all("Project-URL")
+            .into_iter()
+            .map(|value| match value.split_once(',') {
+                Some((label, url)) => (label.trim().to_owned(), url.trim().to_owned()),
+                None => (String::new(), value.trim().to_owned()),
+            })
+            .collect();
+
+        Ok(CoreMetadata {
+            metadata_version,
             name,
             version,
-            metadata_version,
+            dynamic: parsed.take_all("Dynamic"),
+            platforms: parsed.take_all("Platform"),
+            supported_platforms: parsed.take_all("Supported-Platform"),
+            summary: take_first(&mut parsed, "Summary"),
+            description,
+            description_content_type: take_first(&mut parsed, "Description-Content-Type"),
+            keywords: take_first(&mut parsed, "Keywords"),
+            home_page: take_first(&mut parsed, "Home-page"),
+            download_url: take_first(&mut parsed, "Download-URL"),
+            author: take_first(&mut parsed, "Author"),
+            author_email: take_first(&mut parsed, "Author-email"),
+            maintainer: take_first(&mut parsed, "Maintainer"),
+            maintainer_email: take_first(&mut parsed, "Maintainer-email"),
+            license: take_first(&mut parsed, "License"),
+            license_expression: take_first(&mut parsed, "License-Expression"),
+            license_files: parsed.take_all("License-File"),
+            classifiers: parsed.take_all("Classifier"),
             requires_dist,
             requires_python,
-            extras,
+            requires_external: parsed.take_all("Requires-External"),
+            project_urls,
+            provides_extra,
+            provides_dist: parsed.take_all("Provides-Dist"),
+            obsoletes_dist: parsed.take_all("Obsoletes-Dist"),
+            requires: parsed.take_all("Requires"),
+            provides: parsed.take_all("Provides"),
+            obsoletes: parsed.take_all("Obsoletes"),
         })
     }
 }
 
+/// Takes the value of a field that should only occur once. Real world metadata doesn't always
+/// follow that rule, so instead of failing we use the first value.
+fn take_first(parsed: &mut RFC822ish, key: &str) -> Option<String> {
+    let mut values = parsed.take_all(key);
+    if values.len() > 1 {
+        tracing::warn!("multiple values for {key} in METADATA, using the first one");
+    }
+    (!values.is_empty()).then(|| values.swap_remove(0))
+}
+
 fn parse_common(
     input: PackageInfo,
 ) -> Result<(PackageName, Version, MetadataVersion, RFC822ish), WheelCoreMetaDataError> {
@@ -209,3 +595,184 @@ fn parse_common(
         parsed,
     ))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const METADATA: &str = r#"Metadata-Version: 2.4
+Name: Example-Package
+Version: 1.2.0
+Dynamic: Classifier
+Summary: An example package
+Description-Content-Type: text/markdown
+Keywords: example,metadata
+Author: Jane Doe
+Author-email: Jane Doe <jane@example.com>
+Maintainer-email: John Doe <john@example.com>
+License-Expression: MIT OR Apache-2.0
+License-File: LICENSE-MIT
+License-File: licenses/LICENSE-APACHE
+Classifier: Programming Language :: Python :: 3
+Classifier: Operating System :: OS Independent
+Requires-Dist: requests>=2.0
+Requires-Dist: pytest; extra == "test"
+Requires-Python: >=3.8
+Requires-External: libpng (>=1.5)
+Project-URL: Documentation, https://example.com/docs
+Project-URL: Source, https://example.com/source
+Provides-Extra: test
+Provides-Dist: example_package
+Obsoletes-Dist: old-example
+
+# Example
+
+A long description
+        with an indented line.
+"#;
+
+    #[test]
+    fn test_parse_core_metadata() {
+        let metadata = CoreMetadata::try_from(METADATA.as_bytes()).unwrap();
+        assert_eq!(
+            metadata.metadata_version,
+            MetadataVersion::from_static("2.4")
+        );
+        assert_eq!(metadata.name.as_source_str(), "Example-Package");
+        assert_eq!(metadata.dynamic, vec!["Classifier"]);
+        assert_eq!(metadata.summary.as_deref(), Some("An example package"));
+        assert_eq!(
+            metadata.description.as_deref(),
+            Some("# Example\n\nA long description\n        with an indented line.\n")
+        );
+        assert_eq!(metadata.maintainer, None);
+        assert_eq!(
+            metadata.license_expression.as_deref(),
+            Some("MIT OR Apache-2.0")
+        );
+        assert_eq!(
+            metadata.license_files,
+            vec!["LICENSE-MIT", "licenses/LICENSE-APACHE"]
+        );
+        assert_eq!(metadata.classifiers.len(), 2);
+        assert_eq!(metadata.requires_dist.len(), 2);
+        assert_eq!(metadata.requires_external, vec!["libpng (>=1.5)"]);
+        assert_eq!(
+            metadata.project_urls,
+            vec![
+                (
+                    String::from("Documentation"),
+                    String::from("https://example.com/docs")
+                ),
+                (
+                    String::from("Source"),
+                    String::from("https://example.com/source")
+                ),
+            ]
+        );
+        assert_eq!(metadata.provides_extra, vec!["test".parse().unwrap()]);
+        assert_eq!(metadata.obsoletes_dist, vec!["old-example"]);
+        metadata.validate().unwrap();
+
+        // The wheel metadata is a subset of the core metadata
+        let wheel_metadata = WheelCoreMetadata::from(metadata.clone());
+        assert_eq!(wheel_metadata.requires_dist, metadata.requires_dist);
+        assert_eq!(
+            wheel_metadata.extras,
+            HashSet::from(["test".parse().unwrap()])
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let metadata = CoreMetadata::try_from(METADATA.as_bytes()).unwrap();
+        let written = metadata.to_string();
+        assert_eq!(
+            CoreMetadata::try_from(written.as_bytes()).unwrap(),
+            metadata
+        );
+
+        // Old style metadata with the description in a field and multi-line values
+        let old = "Metadata-Version: 1.1\nName: old\nVersion: 0.1\nLicense: Some license\n        spanning lines\nDescription: A description\n       |with a second line\nPlatform: any\n";
+        let metadata = CoreMetadata::try_from(old.as_bytes()).unwrap();
+        assert_eq!(
+            metadata.description.as_deref(),
+            Some("A description\nwith a second line")
+        );
+        assert_eq!(
+            CoreMetadata::try_from(metadata.to_string().as_bytes()).unwrap(),
+            metadata
+        );
+
+        assert!(metadata
+            .to_string()
+            .contains("Description: A description\n       |with a second line\n"));
+
+        // Multi-line values, including lines that are empty or indented themselves
+        let mut metadata = metadata;
+        metadata.license = Some(String::from("first\n    indented\n\nlast"));
+        metadata.description = Some(String::from("A description\n\n  indented\nlast line"));
+        let written = metadata.to_string();
+        assert!(written.contains("License: first\n            indented\n        \n        last\n"));
+        assert_eq!(
+            CoreMetadata::try_from(written.as_bytes()).unwrap(),
+            metadata
+        );
+
+        // Since 2.1 the description is written to the body
+        metadata.metadata_version = MetadataVersion::from_static("2.1");
+        let written = metadata.to_string();
+        assert!(!written.contains("Description:"));
+        assert!(written.ends_with("\n\nA description\n\n  indented\nlast line"));
+        assert_eq!(
+            CoreMetadata::try_from(written.as_bytes()).unwrap(),
+            metadata
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let metadata = |contents: &str| {
+            CoreMetadata::try_from(format!("Name: example\nVersion: 1.0\n{contents}").as_bytes())
+                .unwrap()
+                .validate()
+        };
+
+        assert!(metadata("Metadata-Version: 2.3\nLicense: MIT\n").is_ok());
+        assert!(matches!(
+            metadata("Metadata-Version: 2.3\nLicense-Expression: MIT\n"),
+            Err(WheelCoreMetaDataError::UnsupportedField { field, introduced: "2.4", .. }) if field == "License-Expression"
+        ));
+        assert!(matches!(
+            metadata("Metadata-Version: 2.1\nDynamic: Requires-Dist\n"),
+            Err(WheelCoreMetaDataError::UnsupportedField {
+                introduced: "2.2",
+                ..
+            })
+        ));
+        assert!(matches!(
+            metadata("Metadata-Version: 1.0\nRequires-Dist: requests\n"),
+            Err(WheelCoreMetaDataError::UnsupportedField {
+                introduced: "1.2",
+                ..
+            })
+        ));
+        assert!(matches!(
+            metadata("Metadata-Version: 2.2\nDynamic: Version\n"),
+            Err(WheelCoreMetaDataError::InvalidDynamic(_))
+        ));
+        assert!(matches!(
+            metadata("Metadata-Version: 2.2\nDynamic: Unknown-Field\n"),
+            Err(WheelCoreMetaDataError::InvalidDynamic(_))
+        ));
+        assert!(metadata("Metadata-Version: 2.2\nProvides-Extra: Foo_Bar\n").is_ok());
+        assert!(matches!(
+            metadata("Metadata-Version: 2.3\nProvides-Extra: Foo_Bar\n"),
+            Err(WheelCoreMetaDataError::UnnormalizedExtra(_))
+        ));
+        assert!(matches!(
+            metadata("Metadata-Version: 2.4\nLicense-File: ../LICENSE\n"),
+            Err(WheelCoreMetaDataError::InvalidLicenseFile(_))
+        ));
+    }
+}
@@ -4,8 +4,8 @@ use url::Url;
 /// Specifies the PyPa `direct_url.json` format.
 /// See: <https://packaging.python.org/en/latest/specifications/direct-url-data-structure/>
 ///
-#[derive(Debug, Serialize, Deserialize)]
 #[serde_with::skip_serializing_none]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct DirectUrlJson {
     /// Url to the source.
     pub url: Url,
@@ -17,6 +17,7 @@ pub struct DirectUrlJson {
 /// Specifies the source of a direct url.
 ///
 /// currently we do not support the deprecated `hash` field
+#[serde_with::skip_serializing_none]
 #[derive(Debug, Serialize, Deserialize)]
 pub enum DirectUrlSource {
     #[serde(rename = "archive_info")]
@@ -53,7 +54,7 @@ pub struct DirectUrlHashes {
 }
 
 /// Name of the VCS in a DirectUrlSource
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[allow(missing_docs)]
 pub enum DirectUrlVcs {
     #[serde(rename = "git")]
@@ -66,9 +67,42 @@ pub enum DirectUrlVcs {
     Mercurial,
 }
 
+impl DirectUrlVcs {
+    /// Returns the VCS of a PEP 440 direct reference url, e.g. `hg+https://...`
+    pub fn from_url(url: &Url) -> Option<Self> {
+        match url.scheme().split_once('+')?.0 {
+            "git" => Some(Self::Git),
+            "svn" => Some(Self::Svn),
+            "bzr" => Some(Self::Bazaar),
+            "hg" => Some(Self::Mercurial),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the VCS as used in urls, e.g. `hg`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Git => "git",
+            Self::Svn => "svn",
+            Self::Bazaar => "bzr",
+            Self::Mercurial => "hg",
+        }
+    }
+
+    /// Returns true if the revision uniquely identifies a commit of a repository of this VCS.
+    pub fn is_commit_id(&self, rev: &str) -> bool {
+        match self {
+            Self::Git | Self::Mercurial => {
+                rev.len() == 40 && rev.chars().all(|c| c.is_ascii_hexdigit())
+            }
+            Self::Svn | Self::Bazaar => !rev.is_empty() && rev.chars().all(|c| c.is_ascii_digit()),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::types::direct_url_json::DirectUrlJson;
+    use crate::types::direct_url_json::{DirectUrlJson, DirectUrlSource};
 
     /// Tests if json outputs aligns with the examples at:
     /// https://packaging.python.org/en/latest/specifications/direct-url-data-structure/
@@ -121,4 +155,21 @@ mod tests {
         "#;
         serde_json::from_str::<DirectUrlJson>(example).unwrap();
     }
+
+    #[test]
+    pub fn test_serialize_dir_info() {
+        let direct_url = DirectUrlJson {
+            url: "file:///home/user/project".parse().unwrap(),
+            source: DirectUrlSource::Dir {
+                editable: Some(true),
+            },
+        };
+        insta::assert_snapshot!(serde_json::to_string(&direct_url).unwrap(), @r###"{"url":"file:///home/user/project","dir_info":{"editable":true}}"###);
+
+        let direct_url = DirectUrlJson {
+            url: "file:///home/user/project".parse().unwrap(),
+            source: DirectUrlSource::Dir { editable: None },
+        };
+        insta::assert_snapshot!(serde_json::to_string(&direct_url).unwrap(), @r###"{"url":"file:///home/user/project","dir_info":{}}"###);
+    }
 }
@@ -28,7 +28,9 @@ pub use artifact_name::{
 
 pub use direct_url_json::{DirectUrlHashes, DirectUrlJson, DirectUrlSource, DirectUrlVcs};
 
-pub use core_metadata::{MetadataVersion, PackageInfo, WheelCoreMetaDataError, WheelCoreMetadata};
+pub use core_metadata::{
+    CoreMetadata, MetadataVersion, PackageInfo, WheelCoreMetaDataError, WheelCoreMetadata,
+};
 
 pub use record::{Record, RecordEntry};
 
@@ -1,21 +1,29 @@
 use crate::artifacts::wheel::UnpackWheelOptions;
-use crate::types::ArtifactFromSource;
+use crate::types::{ArtifactFromSource, SourceArtifactName};
 
-use crate::python_env::{PythonLocation, VEnv};
+use crate::python_env::{find_distributions_in_venv, Distribution, PythonLocation, VEnv};
+use crate::resolve::solve_options::{BuildIsolation, BuildSandbox};
 use crate::resolve::{resolve, PinnedPackage};
+use crate::types::NormalizedPackageName;
 use crate::utils::normalize_path;
-use crate::wheel_builder::{WheelBuildError, WheelBuilder};
+use crate::wheel_builder::build_log::{
+    stream_output, BuildLog, BUILD_LOG_CAPACITY, BUILD_LOG_TAIL_LINES,
+};
+use crate::wheel_builder::sandbox;
+use crate::wheel_builder::{BuildBackendError, WheelBuildError, WheelBuilder};
 use fs_err as fs;
 use fs_err::read_dir;
-use parking_lot::RwLock;
-use pep508_rs::Requirement;
+use parking_lot::{Mutex, RwLock};
+use pep508_rs::{MarkerEnvironment, Requirement, VersionOrUrl};
 use std::collections::{HashMap, HashSet};
 use std::ffi::OsString;
 
 use std::ops::DerefMut;
 use std::path::{Path, PathBuf};
-use std::process::{Command, Output};
+use std::process::{Command, ExitStatus, Stdio};
 use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
 
 #[derive(Debug)]
 enum DeleteOrPersist {
@@ -80,6 +88,36 @@ impl TempBuildEnvironment {
     }
 }
 
+/// The result of running a build backend hook
+pub(crate) struct BuildOutput {
+    pub status: ExitStatus,
+    pub log: BuildLog,
+}
+
+/// Kills the process group of a build backend when it is dropped before the backend exited, e.g.
+/// because it timed out or the build was cancelled. This also kills any processes that were started
+/// by the backend, like compilers.
+struct ProcessGroupGuard {
+    pid: Option<u32>,
+}
+
+impl ProcessGroupGuard {
+    /// The backend exited, there is nothing left to kill
+    fn disarm(&mut self) {
+        self.pid = None;
+    }
+}
+
+impl Drop for ProcessGroupGuard {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(pid) = self.pid {
+            // The process group id is the pid of the build backend, see `process_group(0)`
+            unsafe { libc::killpg(pid as libc::pid_t, libc::SIGKILL) };
+        }
+    }
+}
+
 // include static build_frontend.py string
 const BUILD_FRONTEND_PY: &str = include_str!("./wheel_builder_frontend.py");
 /// A build environment for building wheels
@@ -95,7 +133,18 @@ pub(crate) struct BuildEnvironment {
     build_requirements: Vec<Requirement>,
     resolved_wheels: Vec<PinnedPackage>,
     venv: VEnv,
+    /// The source tree of the user that is protected from the build backend, if any
+    source_tree: Option<PathBuf>,
+    /// False if the build requirements are expected to be installed in `venv` already
+    isolated: bool,
     env_variables: HashMap<String, String>,
+    config_settings: HashMap<String, Vec<String>>,
+    /// Restrictions applied to the build backend, if any
+    sandbox: Option<BuildSandbox>,
+    /// The maximum amount of time a single hook may run
+    timeout: Option<Duration>,
+    /// Cancels running hooks when the build is no longer needed
+    cancellation_token: CancellationToken,
     clean_env: bool,
     #[allow(dead_code)]
     python_location: PythonLocation,
@@ -127,6 +176,34 @@ fn normalize_backend_path(
     Ok(normed)
 }
 
+/// Returns the requirements that apply to the given environment but that are not satisfied by
+/// any of the `installed` distributions.
+fn unsatisfied_requirements<'a>(
+    requirements: impl IntoIterator<Item = &'a Requirement>,
+    installed: &[Distribution],
+    env_markers: &MarkerEnvironment,
+) -> Vec<Requirement> {
+    requirements
+        .into_iter()
+        .filter(|requirement| requirement.evaluate_markers(env_markers, Vec::new()))
+        .filter(|requirement| {
+            let Ok(name) = NormalizedPackageName::from_str(&requirement.name) else {
+                return true;
+            };
+            !installed.iter().any(|dist| {
+                dist.name == name
+                    && match &requirement.version_or_url {
+                        Some(VersionOrUrl::VersionSpecifier(specifiers)) => {
+                            specifiers.contains(&dist.version)
+                        }
+                        _ => true,
+                    }
+            })
+        })
+        .cloned()
+        .collect()
+}
+
 impl BuildEnvironment {
     /// Extract the wheel and write the build_frontend.py to the work folder
     pub(crate) fn install_build_files(
@@ -146,11 +223,11 @@ impl BuildEnvironment {
         // which is not actually true
         // so after extracting or moving
         // we map correct package location
-        // when URL is actually a git version
-        // it is extracted in work_dir
+        // when the artifact is a source tree (a git checkout or a local directory)
+        // it is copied into work_dir
         // so we map package_dir to work_dir
 
-        if sdist.version().is_git() {
+        if matches!(sdist.artifact_name(), SourceArtifactName::STree(_)) {
             self.package_dir = self.work_dir.path();
         } else if let Some(package_dir_name) = self.package_dir.file_name() {
             let actual_package_dir = work_dir.join(package_dir_name);
@@ -183,14 +260,14 @@ impl BuildEnvironment {
     /// This uses the `GetRequiresForBuildWheel` entry point of the build backend.
     /// this might not be available for all build backends.
     /// and it can also return an empty list of requirements.
-    fn get_extra_requirements(
+    async fn get_extra_requirements(
         &self,
+        stage: &str,
         output_dir: &Path,
     ) -> Result<HashSet<Requirement>, WheelBuildError> {
-        let output = self.run_command("GetRequiresForBuildWheel", output_dir)?;
+        let output = self.run_command(stage, output_dir).await?;
         if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(WheelBuildError::Error(stderr.to_string()));
+            return Err(self.backend_error(stage, &output));
         }
 
         // The extra requirements are stored in a file called extra_requirements.json
@@ -215,15 +292,27 @@ impl BuildEnvironment {
     /// Install extra requirements into the venv, if any extra were found
     /// If the extra requirements are already installed, this will do nothing
     /// for that requirement.
+    ///
+    /// `stage` is one of `GetRequiresForBuildWheel`, `GetRequiresForBuildEditable` or
+    /// `GetRequiresForBuildSDist`.
     pub(crate) async fn install_extra_requirements(
         &self,
         wheel_builder: &WheelBuilder,
+        stage: &str,
     ) -> Result<(), WheelBuildError> {
         // Get extra requirements if any
         // Because we are using the build environment to get the extra requirements
         // and we should only do this once
         // its fine to use the work_dir as the output_dir
-        let extra_requirements = self.get_extra_requirements(&self.work_dir())?;
+        let extra_requirements = self.get_extra_requirements(stage, &self.work_dir()).await?;
+
+        // Without build isolation we can only check that the requirements are present
+        if !self.isolated {
+            return self.check_requirements_installed(
+                extra_requirements.iter(),
+                &wheel_builder.env_markers,
+            );
+        }
 
         // Combine previous requirements with extra requirements
         let combined_requirements = HashSet::from_iter(self.build_requirements.iter().cloned())
@@ -286,12 +375,49 @@ impl BuildEnvironment {
         Ok(())
     }
 
+    /// Checks that the requirements are satisfied by the distributions that are installed in the
+    /// environment.
+    fn check_requirements_installed<'a>(
+        &self,
+        requirements: impl IntoIterator<Item = &'a Requirement>,
+        env_markers: &MarkerEnvironment,
+    ) -> Result<(), WheelBuildError> {
+        let installed = find_distributions_in_venv(self.venv.root(), self.venv.install_paths())?;
+        let missing = unsatisfied_requirements(requirements, &installed, env_markers);
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(WheelBuildError::MissingBuildRequirements(
+                missing,
+                self.venv.root().to_path_buf(),
+            ))
+        }
+    }
+
     /// Run a command in the build environment
-    pub(crate) fn run_command(
+    pub(crate) async fn run_command(
         &self,
         stage: &str,
         output_dir: &Path,
-    ) -> Result<Output, WheelBuildError> {
+    ) -> Result<BuildOutput, WheelBuildError> {
+        self.run_command_in(stage, output_dir, &self.package_dir)
+            .await
+    }
+
+    /// Run a command in the build environment with `current_dir` as the working directory of the
+    /// build backend. Editable wheels have to be built in the original source tree instead of in
+    /// the copy in the work directory.
+    ///
+    /// The output of the build backend is streamed to `tracing` in the current span while it runs,
+    /// the last part of it is returned in the [`BuildOutput`]. The backend and all processes it
+    /// started are killed if the hook times out, the build is cancelled or the returned future is
+    /// dropped.
+    pub(crate) async fn run_command_in(
+        &self,
+        stage: &str,
+        output_dir: &Path,
+        current_dir: &Path,
+    ) -> Result<BuildOutput, WheelBuildError> {
         // We modify the environment of the user
         // so that we can use the scripts directory to run the build frontend
         // e.g maturin depends on an executable in the scripts directory
@@ -326,7 +452,7 @@ impl BuildEnvironment {
             base_command.env_clear();
         }
         base_command
-            .current_dir(&self.package_dir)
+            .current_dir(current_dir)
             // pass all env variables defined by user
             .envs(&self.env_variables)
             // even if PATH is present in self.env_variables
@@ -341,8 +467,90 @@ impl BuildEnvironment {
             .arg(&self.entry_point)
             // Building Wheel or Metadata
             .arg(stage)
-            .output()
-            .map_err(|e| WheelBuildError::CouldNotRunCommand(stage.into(), e))
+            // PEP 517 config settings passed to the build backend
+            .arg(serde_json::to_string(&self.config_settings)?)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        if let Some(build_sandbox) = &self.sandbox {
+            // Editable wheels are built in the source tree itself, backends store their build
+            // files there so it can't be read-only
+            let protected_dir = self
+                .source_tree
+                .as_deref()
+                .filter(|source_tree| *source_tree != current_dir);
+            sandbox::apply(&mut base_command, build_sandbox, protected_dir, output_dir)?;
+        }
+
+        // Run the backend in its own process group so it can be killed with its children
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut base_command, 0);
+        let mut base_command = tokio::process::Command::from(base_command);
+        base_command.kill_on_drop(true);
+
+        if self.cancellation_token.is_cancelled() {
+            return Err(WheelBuildError::Cancelled);
+        }
+        let mut child = base_command
+            .spawn()
+            .map_err(|e| WheelBuildError::CouldNotRunCommand(stage.into(), e))?;
+        let mut process_group = ProcessGroupGuard { pid: child.id() };
+
+        // Read stdout and stderr concurrently so neither pipe can fill up and block the backend
+        let log = Mutex::new(BuildLog::with_capacity(BUILD_LOG_CAPACITY));
+        let span = tracing::Span::current();
+        let stdout = child.stdout.take().expect("stdout should be piped");
+        let stderr = child.stderr.take().expect("stderr should be piped");
+        let run = async {
+            let (_, _, status) = futures::join!(
+                stream_output(stdout, &log, &span),
+                stream_output(stderr, &log, &span),
+                child.wait()
+            );
+            status.map_err(|e| WheelBuildError::CouldNotRunCommand(stage.into(), e))
+        };
+
+        let started = Instant::now();
+        let run = async {
+            match self.timeout {
+                Some(timeout) => tokio::time::timeout(timeout, run).await.map_err(|_| {
+                    WheelBuildError::Timeout {
+                        stage: stage.to_string(),
+                        elapsed: started.elapsed(),
+                    }
+                })?,
+                None => run.await,
+            }
+        };
+        let status = tokio::select! {
+            status = run => status?,
+            _ = self.cancellation_token.cancelled() => return Err(WheelBuildError::Cancelled),
+        };
+        process_group.disarm();
+
+        Ok(BuildOutput {
+            status,
+            log: log.into_inner(),
+        })
+    }
+
+    /// Constructs the error for a build backend hook that did not succeed.
+    pub(crate) fn backend_error(&self, stage: &str, output: &BuildOutput) -> WheelBuildError {
+        let error = BuildBackendError {
+            hook: stage.to_string(),
+            entry_point: self.entry_point.clone(),
+            exit_code: output.status.code(),
+            output: output.log.tail(BUILD_LOG_TAIL_LINES),
+            saved_build_env: None,
+        };
+        let violation = self
+            .sandbox
+            .as_ref()
+            .and_then(|s| sandbox::detect_violation(s, &output.status, &output.log));
+        match violation {
+            Some(violation) => WheelBuildError::SandboxViolation { violation, error },
+            None => WheelBuildError::BuildBackendError(error),
+        }
     }
 
     fn default_build_system() -> pyproject_toml::BuildSystem {
@@ -371,12 +579,8 @@ impl BuildEnvironment {
         sdist: &impl ArtifactFromSource,
         wheel_builder: &WheelBuilder,
     ) -> Result<BuildEnvironment, WheelBuildError> {
-        // Setup a work directory and a new env dir
+        // Setup a work directory
         let work_dir = tempfile::tempdir()?;
-        let venv = VEnv::create(
-            &work_dir.path().join("venv"),
-            wheel_builder.resolve_options.python_location.clone(),
-        )?;
 
         // Find the build system
         let build_system = sdist
@@ -405,44 +609,31 @@ impl BuildEnvironment {
                 .map(|r| r.to_string())
                 .collect::<Vec<_>>()
         );
-        // Resolve the build environment
-        let resolved_wheels = resolve(
-            wheel_builder.package_db.clone(),
-            build_requirements.iter(),
-            wheel_builder.env_markers.clone(),
-            wheel_builder.wheel_tags.clone(),
-            HashMap::default(),
-            HashMap::default(),
-            wheel_builder.resolve_options.clone(),
-            Default::default(),
-        )
-        .await
-        .map_err(|e| {
-            tracing::error!(
-                "could not resolve build requirements when trying to build a wheel for : {}",
-                sdist.artifact_name()
-            );
-            WheelBuildError::CouldNotResolveEnvironment(build_requirements.to_vec(), e)
-        })?;
-
-        // Install into venv
-        for package_info in resolved_wheels.iter() {
-            let artifact_info = package_info.artifacts.first().unwrap();
-
-            let (artifact, _) = wheel_builder
-                .package_db
-                .get_wheel(artifact_info, Some(wheel_builder))
-                .await
-                .map_err(WheelBuildError::CouldNotGetArtifact)?;
-
-            venv.install_wheel(
-                &artifact,
-                &UnpackWheelOptions {
-                    installer: None,
-                    ..Default::default()
-                },
-            )?;
-        }
+        let (venv, resolved_wheels) = match &wheel_builder.resolve_options.build_isolation {
+            BuildIsolation::Isolated => {
+                // Resolve the build environment, environments with the same resolved build
+                // requirements are shared and only copied for this build
+                let resolved_wheels = wheel_builder
+                    .shared_build_envs
+                    .resolve(&build_requirements, wheel_builder)
+                    .await
+                    .map_err(|e| {
+                        tracing::error!(
+                            "could not resolve build requirements when trying to build a wheel for : {}",
+                            sdist.artifact_name()
+                        );
+                        e
+                    })?;
+                let venv = wheel_builder
+                    .shared_build_envs
+                    .template(&resolved_wheels, wheel_builder)
+                    .await?
+                    .clone_to(&work_dir.path().join("venv"))?;
+
+                (venv, resolved_wheels)
+            }
+            BuildIsolation::NoIsolation(path) => (VEnv::open(path)?, Vec::new()),
+        };
 
         // Package dir for the package we need to build
         let package_dir =
@@ -464,24 +655,43 @@ impl BuildEnvironment {
             wheel_builder.env_variables.clone()
         };
 
-        Ok(BuildEnvironment {
+        let build_environment = BuildEnvironment {
             work_dir: TempBuildEnvironment::new(work_dir),
             package_dir,
             build_system,
             build_requirements,
             entry_point,
             resolved_wheels,
+            isolated: wheel_builder.resolve_options.build_isolation == BuildIsolation::Isolated,
             venv,
+            source_tree: sdist.source_tree(),
             env_variables,
+            config_settings: wheel_builder.config_settings(sdist).clone(),
+            sandbox: wheel_builder.resolve_options.build_sandbox.clone(),
+            timeout: wheel_builder.resolve_options.build_timeout,
+            cancellation_token: wheel_builder.cancellation_token.clone(),
             clean_env: wheel_builder.resolve_options.clean_env,
             python_location: wheel_builder.resolve_options.python_location.clone(),
-        })
+        };
+
+        // Without build isolation the build requirements must already be installed
+        if !build_environment.isolated {
+            build_environment.check_requirements_installed(
+                build_environment.build_requirements.iter(),
+                &wheel_builder.env_markers,
+            )?;
+        }
+
+        Ok(build_environment)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::python_env::Distribution;
+    use pep508_rs::{MarkerEnvironment, Requirement};
     use std::path::PathBuf;
+    use std::str::FromStr;
 
     #[test]
     fn test_norm_backend_path() {
@@ -511,4 +721,47 @@ mod tests {
         let backend_path = vec!["/no_absolute_allowed".to_string()];
         super::normalize_backend_path(&backend_path, &package_dir).unwrap_err();
     }
+
+    #[test]
+    fn test_unsatisfied_requirements() {
+        let env_markers = MarkerEnvironment {
+            implementation_name: "cpython".to_string(),
+            implementation_version: "3.10.4".parse().unwrap(),
+            os_name: "posix".to_string(),
+            platform_machine: "x86_64".to_string(),
+            platform_python_implementation: "CPython".to_string(),
+            platform_release: "6.0".to_string(),
+            platform_system: "Linux".to_string(),
+            platform_version: "#1 SMP".to_string(),
+            python_full_version: "3.10.4".parse().unwrap(),
+            python_version: "3.10".parse().unwrap(),
+            sys_platform: "linux".to_string(),
+        };
+        let installed = [("setuptools", "69.0.2"), ("wheel", "0.42.0")]
+            .into_iter()
+            .map(|(name, version)| Distribution {
+                name: name.parse().unwrap(),
+                version: version.parse().unwrap(),
+                installer: None,
+                dist_info: PathBuf::from(format!("{name}-{version}.dist-info")),
+                tags: None,
+            })
+            .collect::<Vec<_>>();
+        let requirements = [
+            "setuptools>=64",
+            "Wheel",
+            "setuptools_scm>=8",
+            "cython<3",
+            "pywin32; sys_platform == 'win32'",
+        ]
+        .into_iter()
+        .map(|r| Requirement::from_str(r).unwrap())
+        .collect::<Vec<_>>();
+
+        let missing = super::unsatisfied_requirements(&requirements, &installed, &env_markers);
+        assert_eq!(
+            missing.iter().map(|r| r.name.as_str()).collect::<Vec<_>>(),
+            vec!["setuptools_scm", "cython"]
+        );
+    }
 }
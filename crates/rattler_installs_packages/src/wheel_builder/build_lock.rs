@@ -0,0 +1,77 @@
+//! Locks that make sure that multiple processes that share a cache directory don't build the same
+//! sdist at the same time. The process that waits for a lock reuses the result of the process that
+//! held it.
+
+use crate::wheel_builder::{WheelBuildError, WheelBuilder, WheelCacheKey};
+use fs4::FileExt;
+use fs_err as fs;
+use rattler_digest::Sha256;
+use std::io;
+use std::time::Duration;
+
+/// How often to check if a lock that is held by another process has been released
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What is built from an sdist while a [`BuildLock`] is held
+#[derive(Debug, Copy, Clone)]
+pub(crate) enum BuildKind {
+    Wheel,
+    Metadata,
+}
+
+/// An exclusive lock on building a wheel or metadata from an sdist, the lock is released when it
+/// is dropped.
+pub(crate) struct BuildLock {
+    _file: fs::File,
+}
+
+impl BuildLock {
+    /// Acquires the lock for building from the sdist with the given key. If another process or
+    /// task holds the lock this waits until the lock is released, or until the builds of the
+    /// wheel builder are cancelled.
+    pub async fn acquire(
+        wheel_builder: &WheelBuilder,
+        key: &WheelCacheKey,
+        kind: BuildKind,
+        name: &str,
+    ) -> Result<Self, WheelBuildError> {
+        let locks_dir = wheel_builder.package_db.cache_dir().join("build-locks");
+        fs::create_dir_all(&locks_dir)?;
+        let hash = rattler_digest::compute_bytes_digest::<Sha256>(key.as_str());
+        let suffix = match kind {
+            BuildKind::Wheel => "wheel",
+            BuildKind::Metadata => "metadata",
+        };
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .open(locks_dir.join(format!("{hash:x}.{suffix}.lock")))?;
+
+        let mut waiting = false;
+        loop {
+            match file.file().try_lock_exclusive() {
+                Ok(()) => return Ok(Self { _file: file }),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) if is_contended(&err) => {}
+                Err(err) => return Err(err.into()),
+            }
+
+            if !waiting {
+                tracing::info!("waiting for another process to finish building {name}");
+                waiting = true;
+            }
+            tokio::select! {
+                _ = wheel_builder.cancellation_token.cancelled() => {
+                    return Err(WheelBuildError::Cancelled)
+                }
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+            }
+        }
+    }
+}
+
+/// Returns true if the error indicates that the lock is held by someone else
+fn is_contended(err: &io::Error) -> bool {
+    err.kind() == io::ErrorKind::WouldBlock
+        || err.raw_os_error() == fs4::lock_contended_error().raw_os_error()
+}
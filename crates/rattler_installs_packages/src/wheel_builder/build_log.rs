@@ -0,0 +1,110 @@
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+
+/// The maximum number of bytes of build output that is kept in memory per build backend hook.
+pub(crate) const BUILD_LOG_CAPACITY: usize = 256 * 1024;
+
+/// The number of lines of build output that are included in a build failure.
+pub(crate) const BUILD_LOG_TAIL_LINES: usize = 50;
+
+/// The output of a build backend hook. Only the last lines are kept, the total size of the stored
+/// lines never exceeds the capacity (except for a single line that is larger than the capacity).
+#[derive(Debug)]
+pub(crate) struct BuildLog {
+    lines: VecDeque<String>,
+    size: usize,
+    capacity: usize,
+}
+
+impl BuildLog {
+    /// Constructs an empty log that keeps at most `capacity` bytes of output.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            lines: VecDeque::new(),
+            size: 0,
+            capacity,
+        }
+    }
+
+    /// Adds a line to the log, removing the oldest lines if the log grows too large.
+    pub fn push(&mut self, line: String) {
+        self.size += line.len();
+        self.lines.push_back(line);
+        while self.size > self.capacity && self.lines.len() > 1 {
+            if let Some(removed) = self.lines.pop_front() {
+                self.size -= removed.len();
+            }
+        }
+    }
+
+    /// Returns all the lines in the log.
+    pub fn lines(&self) -> impl Iterator<Item = &str> {
+        self.lines.iter().map(String::as_str)
+    }
+
+    /// Returns the last `n` lines of the log.
+    pub fn tail(&self, n: usize) -> Vec<String> {
+        self.lines
+            .iter()
+            .skip(self.lines.len().saturating_sub(n))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Reads the output of a build backend line by line, emits every line as a tracing event in the
+/// given span and stores it in the log.
+pub(crate) async fn stream_output(
+    reader: impl AsyncRead + Unpin,
+    log: &Mutex<BuildLog>,
+    span: &tracing::Span,
+) {
+    let mut reader = BufReader::new(reader);
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {
+                let line = String::from_utf8_lossy(&buf).trim_end().to_string();
+                span.in_scope(|| tracing::debug!("{line}"));
+                log.lock().push(line);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_build_log_capacity() {
+        let mut log = BuildLog::with_capacity(10);
+        log.push("aaaa".into());
+        log.push("bbbb".into());
+        assert_eq!(log.tail(10), vec!["aaaa", "bbbb"]);
+
+        // Exceeding the capacity removes the oldest lines
+        log.push("cccc".into());
+        assert_eq!(log.tail(10), vec!["bbbb", "cccc"]);
+        assert_eq!(log.tail(1), vec!["cccc"]);
+
+        // A single line that is larger than the capacity is kept
+        log.push("dddddddddddd".into());
+        assert_eq!(log.tail(10), vec!["dddddddddddd"]);
+    }
+
+    #[tokio::test]
+    async fn test_stream_output() {
+        let log = Mutex::new(BuildLog::with_capacity(BUILD_LOG_CAPACITY));
+        stream_output(
+            "first\r\nsecond\n\nlast".as_bytes(),
+            &log,
+            &tracing::Span::none(),
+        )
+        .await;
+        assert_eq!(log.lock().tail(10), vec!["first", "second", "", "last"]);
+    }
+}
@@ -1,17 +1,26 @@
 use crate::artifacts::wheel::UnpackError;
-use crate::python_env::VEnvError;
+use crate::python_env::{FindDistributionError, VEnvError};
 use crate::types::{ParseArtifactNameError, WheelCoreMetaDataError};
+use crate::wheel_builder::sandbox::SandboxViolation;
 use crate::wheel_builder::wheel_cache;
+use itertools::Itertools;
+use miette::Diagnostic;
 use pep508_rs::Requirement;
+use std::fmt::Display;
 use std::path::PathBuf;
+use std::time::Duration;
 
 /// An error that can occur while building a wheel
 #[allow(missing_docs)]
-#[derive(thiserror::Error, Debug)]
+#[derive(thiserror::Error, Debug, Diagnostic)]
 pub enum WheelBuildError {
     #[error("could not build wheel: {0}")]
     Error(String),
 
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    BuildBackendError(#[from] BuildBackendError),
+
     #[error("could not install artifact in virtual environment: {0}")]
     UnpackError(#[from] UnpackError),
 
@@ -21,6 +30,26 @@ pub enum WheelBuildError {
     #[error("could not run command {0} to build wheel: {1}")]
     CouldNotRunCommand(String, std::io::Error),
 
+    #[error("{violation}")]
+    SandboxViolation {
+        violation: SandboxViolation,
+        #[source]
+        #[diagnostic_source]
+        error: BuildBackendError,
+    },
+
+    #[error("the `{stage}` hook of the build backend timed out after {}s", .elapsed.as_secs())]
+    Timeout { stage: String, elapsed: Duration },
+
+    #[error("the build was cancelled")]
+    Cancelled,
+
+    #[error("could not setup the build sandbox: {0}")]
+    CouldNotSetupSandbox(std::io::Error),
+
+    #[error("sandboxed builds are only supported on Linux")]
+    SandboxUnsupported,
+
     #[error("could not resolve environment for wheel building: {1:?}")]
     CouldNotResolveEnvironment(Vec<Requirement>, miette::Report),
 
@@ -50,6 +79,68 @@ pub enum WheelBuildError {
     )]
     BackendPathNotInPackageDir(PathBuf),
 
+    #[error("could not determine the installed distributions: {0}")]
+    FindDistributionError(#[from] FindDistributionError),
+
+    #[error(
+        "build isolation is disabled but the build requirements {} are not installed in {}",
+        .0.iter().format(", "),
+        .1.display()
+    )]
+    MissingBuildRequirements(Vec<Requirement>, PathBuf),
+
     #[error("could not join path: {0}")]
     CouldNotJoinPath(#[from] std::env::JoinPathsError),
 }
+
+/// A build backend hook exited with an error.
+#[derive(Debug, Clone)]
+pub struct BuildBackendError {
+    /// The hook that failed, e.g. `GetRequiresForBuildWheel`, `WheelMetadata` or `Wheel`
+    pub hook: String,
+
+    /// The entry point of the build backend, e.g. `setuptools.build_meta`
+    pub entry_point: String,
+
+    /// The exit code of the build frontend, `None` if it was terminated by a signal
+    pub exit_code: Option<i32>,
+
+    /// The last lines of the output of the build backend
+    pub output: Vec<String>,
+
+    /// The location of the build environment if it was saved for debugging
+    pub saved_build_env: Option<PathBuf>,
+}
+
+impl Display for BuildBackendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "the `{}` hook of build backend `{}` failed",
+            self.hook, self.entry_point
+        )?;
+        if let Some(code) = self.exit_code {
+            write!(f, " with exit code {code}")?;
+        }
+        if !self.output.is_empty() {
+            write!(f, ", last {} lines of output:", self.output.len())?;
+            for line in self.output.iter() {
+                write!(f, "\n  {line}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for BuildBackendError {}
+
+impl Diagnostic for BuildBackendError {
+    fn help<'a>(&'a self) -> Option<Box<dyn Display + 'a>> {
+        self.saved_build_env.as_ref().map(|path| {
+            Box::new(format!(
+                "the build environment was saved to {}",
+                path.display()
+            )) as Box<dyn Display>
+        })
+    }
+}
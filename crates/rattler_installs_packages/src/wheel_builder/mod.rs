@@ -1,7 +1,11 @@
 //! Turn an sdist into a wheel by creating a virtualenv and building the sdist in it
 
 mod build_environment;
+mod build_lock;
+mod build_log;
 mod error;
+mod sandbox;
+mod shared_environment;
 mod wheel_cache;
 
 use fs_err as fs;
@@ -15,15 +19,26 @@ use std::{collections::HashMap, path::PathBuf};
 use parking_lot::Mutex;
 use pep508_rs::MarkerEnvironment;
 
+use crate::artifacts::{SDist, STree};
 use crate::python_env::{ParsePythonInterpreterVersionError, PythonInterpreterVersion};
 use crate::resolve::solve_options::{OnWheelBuildFailure, ResolveOptions};
 use crate::types::ArtifactFromSource;
 use crate::types::{NormalizedPackageName, PackageName, SourceArtifactName, WheelFilename};
 use crate::wheel_builder::build_environment::BuildEnvironment;
-pub use crate::wheel_builder::wheel_cache::{WheelCache, WheelCacheKey};
+use crate::wheel_builder::build_lock::{BuildKind, BuildLock};
+use crate::wheel_builder::shared_environment::SharedBuildEnvironments;
+pub(crate) use crate::wheel_builder::shared_environment::{
+    persisted_environments, remove_incomplete_environments, remove_persisted_environment,
+    PersistedEnvironment,
+};
+pub use crate::wheel_builder::wheel_cache::{
+    WheelCache, WheelCacheEntry, WheelCacheError, WheelCacheKey,
+};
 use crate::{artifacts::Wheel, index::PackageDb, python_env::WheelTags, types::WheelCoreMetadata};
-pub use error::WheelBuildError;
+pub use error::{BuildBackendError, WheelBuildError};
+pub use sandbox::SandboxViolation;
 use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
 
 type BuildCache = Mutex<HashMap<SourceArtifactName, Arc<BuildEnvironment>>>;
 type OptionalBuildEnv = Option<Arc<BuildEnvironment>>;
@@ -35,6 +50,9 @@ pub struct WheelBuilder {
     /// A cache for virtualenvs that might be reused later in the process
     venv_cache: BuildCache,
 
+    /// The environments with the build requirements that are shared between sdists
+    shared_build_envs: SharedBuildEnvironments,
+
     /// A cache for in-flight virtualenvs
     in_setup_venv: Mutex<HashMap<SourceArtifactName, Weak<BuildEnvironmentSender>>>,
 
@@ -62,6 +80,9 @@ pub struct WheelBuilder {
 
     /// Python interpreter version
     python_version: PythonInterpreterVersion,
+
+    /// Stops running builds when cancelled
+    cancellation_token: CancellationToken,
 }
 
 impl WheelBuilder {
@@ -79,6 +100,7 @@ impl WheelBuilder {
 
         Ok(Self {
             venv_cache: Mutex::new(HashMap::new()),
+            shared_build_envs: SharedBuildEnvironments::default(),
             in_setup_venv: Mutex::new(HashMap::new()),
             package_db,
             env_markers,
@@ -87,14 +109,54 @@ impl WheelBuilder {
             env_variables,
             saved_build_envs: Mutex::new(HashSet::new()),
             python_version,
+            cancellation_token: CancellationToken::new(),
         })
     }
 
+    /// Use the given token to cancel the builds of this builder. Once the token is cancelled all
+    /// running build backends are killed and new builds fail with [`WheelBuildError::Cancelled`].
+    pub fn with_cancellation_token(self, cancellation_token: CancellationToken) -> Self {
+        Self {
+            cancellation_token,
+            ..self
+        }
+    }
+
+    /// Returns true if the builds of this builder have been cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation_token.is_cancelled()
+    }
+
     /// Get the python interpreter version
     pub fn python_version(&self) -> &PythonInterpreterVersion {
         &self.python_version
     }
 
+    /// Returns the package database that is used to build wheels
+    pub(crate) fn package_db(&self) -> &PackageDb {
+        &self.package_db
+    }
+
+    /// Returns the PEP 517 `config_settings` that are passed to the build backend of the sdist.
+    pub(crate) fn config_settings(
+        &self,
+        sdist: &impl ArtifactFromSource,
+    ) -> &HashMap<String, Vec<String>> {
+        match PackageName::from_str(&sdist.distribution_name()) {
+            Ok(name) => self.resolve_options.config_settings_for(&name.into()),
+            Err(_) => &self.resolve_options.config_settings,
+        }
+    }
+
+    /// Returns the key under which the wheel that is built from the sdist is cached.
+    fn wheel_cache_key(
+        &self,
+        sdist: &impl ArtifactFromSource,
+    ) -> Result<WheelCacheKey, std::io::Error> {
+        Ok(WheelCacheKey::from_sdist(sdist, &self.python_version)?
+            .with_config_settings(self.config_settings(sdist)))
+    }
+
     /// Get a prepared virtualenv for building a wheel (or extracting metadata) from an `[SDist]`
     /// This function also caches the virtualenvs, so that they can be reused later.
     async fn setup_build_venv(
@@ -184,11 +246,20 @@ impl WheelBuilder {
             let mut build_environment = BuildEnvironment::setup(sdist, self).await?;
             build_environment.install_build_files(sdist)?;
             // Install extra requirements if any
-            build_environment.install_extra_requirements(self).await?;
+            build_environment
+                .install_extra_requirements(self, "GetRequiresForBuildWheel")
+                .await?;
             Ok(build_environment)
         };
 
-        match future().await {
+        // Setting up the environment can involve resolving and building the build requirements,
+        // stop that as well when the build is cancelled
+        let result = tokio::select! {
+            result = future() => result,
+            _ = self.cancellation_token.cancelled() => Err(WheelBuildError::Cancelled),
+        };
+
+        match result {
             Ok(build_environment) => {
                 let build_environment = Arc::new(build_environment);
                 // Insert into the venv cache
@@ -224,7 +295,7 @@ impl WheelBuilder {
         if self.resolve_options.on_wheel_build_failure != OnWheelBuildFailure::SaveBuildEnv {
             return result;
         }
-        if let Err(e) = result {
+        if let Err(mut e) = result {
             // Persist the build environment
             build_environment.persist();
 
@@ -234,6 +305,13 @@ impl WheelBuilder {
             self.saved_build_envs
                 .lock()
                 .insert(build_environment.work_dir());
+
+            // Point to the saved environment from the error
+            if let WheelBuildError::BuildBackendError(e)
+            | WheelBuildError::SandboxViolation { error: e, .. } = &mut e
+            {
+                e.saved_build_env = Some(path);
+            }
             Err(e)
         } else {
             result
@@ -247,13 +325,17 @@ impl WheelBuilder {
         &self,
         sdist: &S,
     ) -> Result<(Vec<u8>, WheelCoreMetadata), WheelBuildError> {
-        // See if we have a locally built wheel for this sdist
-        // use that metadata instead
-        let key: WheelCacheKey = WheelCacheKey::from_sdist(sdist, &self.python_version)?;
-        if let Some(wheel) = self.package_db.local_wheel_cache().wheel_for_key(&key)? {
-            return wheel.metadata().map_err(|e| {
-                WheelBuildError::Error(format!("Could not parse wheel metadata: {}", e))
-            });
+        let key = self.wheel_cache_key(sdist)?;
+        if let Some(metadata) = self.cached_sdist_metadata(&key).await? {
+            return Ok(metadata);
+        }
+
+        // Another process might be generating the metadata of the same sdist, in that case wait
+        // for it and use its result
+        let _lock =
+            BuildLock::acquire(self, &key, BuildKind::Metadata, &sdist.distribution_name()).await?;
+        if let Some(metadata) = self.cached_sdist_metadata(&key).await? {
+            return Ok(metadata);
         }
 
         let build_environment = self.setup_build_venv(sdist).await?;
@@ -263,7 +345,42 @@ impl WheelBuilder {
         let result = self
             .get_sdist_metadata_internal(&build_environment, sdist)
             .await;
-        self.handle_build_failure(result, &build_environment)
+        let (metadata, wheel_metadata) = self.handle_build_failure(result, &build_environment)?;
+
+        // The metadata can be generated again if it could not be cached
+        if let Err(err) = self
+            .package_db
+            .put_sdist_metadata_in_cache(&key, &metadata)
+            .await
+        {
+            tracing::warn!("could not cache the metadata of the sdist: {err}");
+        }
+        Ok((metadata, wheel_metadata))
+    }
+
+    /// Returns the metadata of a wheel that was built from the sdist, or the metadata that was
+    /// generated from the sdist before.
+    async fn cached_sdist_metadata(
+        &self,
+        key: &WheelCacheKey,
+    ) -> Result<Option<(Vec<u8>, WheelCoreMetadata)>, WheelBuildError> {
+        // See if we have a locally built wheel for this sdist
+        // use that metadata instead
+        if let Some(wheel) = self.package_db.local_wheel_cache().wheel_for_key(key)? {
+            return wheel.metadata().map(Some).map_err(|e| {
+                WheelBuildError::Error(format!("Could not parse wheel metadata: {}", e))
+            });
+        }
+
+        // See if we have generated the metadata for this sdist before
+        if let Some(metadata) = self.package_db.sdist_metadata_from_cache(key).await {
+            match WheelCoreMetadata::try_from(metadata.as_slice()) {
+                Ok(wheel_metadata) => return Ok(Some((metadata, wheel_metadata))),
+                Err(err) => tracing::warn!("ignoring invalid cached sdist metadata: {err}"),
+            }
+        }
+
+        Ok(None)
     }
 
     async fn get_sdist_metadata_internal<S: ArtifactFromSource>(
@@ -272,7 +389,9 @@ impl WheelBuilder {
         sdist: &S,
     ) -> Result<(Vec<u8>, WheelCoreMetadata), WheelBuildError> {
         let output_dir = tempfile::tempdir()?;
-        let output = build_environment.run_command("WheelMetadata", output_dir.path())?;
+        let output = build_environment
+            .run_command("WheelMetadata", output_dir.path())
+            .await?;
         if !output.status.success() {
             if output.status.code() == Some(50) {
                 tracing::warn!("SDist build backend does not support metadata generation");
@@ -282,8 +401,7 @@ impl WheelBuilder {
                     WheelBuildError::Error(format!("Could not parse wheel metadata: {}", e))
                 });
             }
-            let stdout = String::from_utf8_lossy(&output.stderr);
-            return Err(WheelBuildError::Error(stdout.to_string()));
+            return Err(build_environment.backend_error("WheelMetadata", &output));
         }
 
         // Read the outputted file
@@ -305,11 +423,29 @@ impl WheelBuilder {
         sdist: &S,
     ) -> Result<Wheel, WheelBuildError> {
         // Check if we have already built this wheel locally and use that instead
-        let key = WheelCacheKey::from_sdist(sdist, &self.python_version)?;
+        let key = self.wheel_cache_key(sdist)?;
         if let Some(wheel) = self.package_db.local_wheel_cache().wheel_for_key(&key)? {
             return Ok(wheel);
         }
 
+        // Another process might be building the same wheel, in that case wait for it and use the
+        // wheel it built
+        let _lock =
+            BuildLock::acquire(self, &key, BuildKind::Wheel, &sdist.distribution_name()).await?;
+        if let Some(wheel) = self.package_db.local_wheel_cache().wheel_for_key(&key)? {
+            return Ok(wheel);
+        }
+
+        self.build_wheel_without_cache(sdist).await
+    }
+
+    /// Build a wheel from an sdist like [`Self::build_wheel`], but always run the build backend
+    /// instead of using a wheel that was built before. Use this when the wheel is published.
+    #[tracing::instrument(skip_all, fields(name = % sdist.distribution_name(), version = % sdist.version()))]
+    pub async fn build_wheel_without_cache<S: ArtifactFromSource>(
+        &self,
+        sdist: &S,
+    ) -> Result<Wheel, WheelBuildError> {
         // Setup a new virtualenv for building the wheel or use an existing
         let build_environment = self.setup_build_venv(sdist).await?;
         // Capture the result of the build
@@ -326,12 +462,13 @@ impl WheelBuilder {
     ) -> Result<Wheel, WheelBuildError> {
         let output_dir = tempfile::tempdir()?;
         // Run the wheel stage
-        let output = build_environment.run_command("Wheel", output_dir.path())?;
+        let output = build_environment
+            .run_command("Wheel", output_dir.path())
+            .await?;
 
         // Check for success
         if !output.status.success() {
-            let stdout = String::from_utf8_lossy(&output.stderr);
-            return Err(WheelBuildError::Error(stdout.to_string()));
+            return Err(build_environment.backend_error("Wheel", &output));
         }
 
         // This is where the wheel file is located
@@ -345,7 +482,7 @@ impl WheelBuilder {
             .into();
 
         // Save the wheel into the cache
-        let key = WheelCacheKey::from_sdist(sdist, &self.python_version)?;
+        let key = self.wheel_cache_key(sdist)?;
 
         // Reconstruction of the wheel filename
         let file_component = wheel_file
@@ -372,24 +509,110 @@ impl WheelBuilder {
 
         Ok(wheel)
     }
+
+    /// Build an sdist from a source tree by using the build_backend in a virtual env.
+    /// This function uses the `build_sdist` entry point of the build backend.
+    #[tracing::instrument(skip_all, fields(name = % stree.distribution_name(), version = % stree.version()))]
+    pub async fn build_sdist(&self, stree: &STree) -> Result<SDist, WheelBuildError> {
+        let build_environment = self.setup_build_venv(stree).await?;
+        let result = self.build_sdist_internal(&build_environment, stree).await;
+        self.handle_build_failure(result, &build_environment)
+    }
+
+    async fn build_sdist_internal(
+        &self,
+        build_environment: &BuildEnvironment,
+        stree: &STree,
+    ) -> Result<SDist, WheelBuildError> {
+        build_environment
+            .install_extra_requirements(self, "GetRequiresForBuildSDist")
+            .await?;
+
+        let output_dir = tempfile::tempdir()?;
+        let output = build_environment
+            .run_command("SDist", output_dir.path())
+            .await?;
+        if !output.status.success() {
+            return Err(build_environment.backend_error("SDist", &output));
+        }
+
+        let sdist_file: PathBuf = fs::read_to_string(output_dir.path().join("sdist_result"))?
+            .trim()
+            .into();
+        let package_name: NormalizedPackageName = PackageName::from_str(&stree.distribution_name())
+            .unwrap()
+            .into();
+        SDist::from_path(&sdist_file, &package_name)
+            .map_err(|e| WheelBuildError::Error(format!("Could not build sdist: {}", e)))
+    }
+
+    /// Build an editable wheel (PEP 660) from a local source tree by using the build_backend in a
+    /// virtual env. This function uses the `build_editable` entry point of the build backend, or
+    /// creates a wheel with a `.pth` file that points to the source tree if the backend does not
+    /// support editable builds.
+    ///
+    /// Editable wheels refer to the source tree so they are not cached.
+    #[tracing::instrument(skip_all, fields(name = % stree.distribution_name(), version = % stree.version()))]
+    pub async fn build_editable(&self, stree: &STree) -> Result<Wheel, WheelBuildError> {
+        let build_environment = self.setup_build_venv(stree).await?;
+        let result = self
+            .build_editable_internal(&build_environment, stree)
+            .await;
+        self.handle_build_failure(result, &build_environment)
+    }
+
+    async fn build_editable_internal(
+        &self,
+        build_environment: &BuildEnvironment,
+        stree: &STree,
+    ) -> Result<Wheel, WheelBuildError> {
+        build_environment
+            .install_extra_requirements(self, "GetRequiresForBuildEditable")
+            .await?;
+
+        // The editable wheel is built in the original source tree, not in the copy that is used to
+        // build regular wheels
+        let source_dir = stree.lock_data().clone();
+        let output_dir = tempfile::tempdir()?;
+        let output = build_environment
+            .run_command_in("Editable", output_dir.path(), &source_dir)
+            .await?;
+        if !output.status.success() {
+            return Err(build_environment.backend_error("Editable", &output));
+        }
+
+        let wheel_file: PathBuf = fs::read_to_string(output_dir.path().join("editable_result"))?
+            .trim()
+            .into();
+        let package_name: NormalizedPackageName = PackageName::from_str(&stree.distribution_name())
+            .unwrap()
+            .into();
+        Wheel::from_path(&wheel_file, &package_name)
+            .map_err(|e| WheelBuildError::Error(format!("Could not build editable wheel: {}", e)))
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::artifacts::SDist;
+    use crate::artifacts::{SDist, STree};
     use crate::index::{PackageDb, PackageSourcesBuilder};
-    use crate::python_env::{Pep508EnvMakers, PythonInterpreterVersion};
-    use crate::resolve::solve_options::{OnWheelBuildFailure, ResolveOptions};
+    use crate::python_env::{Pep508EnvMakers, PythonInterpreterVersion, PythonLocation};
+    use crate::resolve::solve_options::{BuildIsolation, OnWheelBuildFailure, ResolveOptions};
+    use crate::types::{ArtifactFromBytes, SDistFilename, STreeFilename};
     use crate::wheel_builder::wheel_cache::WheelCacheKey;
-    use crate::wheel_builder::WheelBuilder;
+    use crate::wheel_builder::{WheelBuildError, WheelBuilder};
+    use fs_err as fs;
     use futures::future::TryJoinAll;
     use reqwest::Client;
     use reqwest_middleware::ClientWithMiddleware;
     use std::collections::HashMap;
+    use std::io::Read;
     use std::path::Path;
     use std::sync::Arc;
+    use std::time::Duration;
     use tempfile::TempDir;
     use tokio_util::either::Either;
+    use tokio_util::sync::CancellationToken;
 
     fn get_package_db() -> (Arc<PackageDb>, TempDir) {
         let tempdir = tempfile::tempdir().unwrap();
@@ -482,6 +705,360 @@ mod tests {
         assert!(path.exists());
     }
 
+    /// Creates a source tree with a build backend that never finishes building a wheel
+    fn hanging_stree(dir: &Path) -> STree {
+        fs::write(
+            dir.join("pyproject.toml"),
+            "[build-system]\nrequires = []\nbuild-backend = \"hanging_backend\"\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("hanging_backend.py"),
+            "import time\ndef build_wheel(*args, **kwargs):\n    time.sleep(1000)\n",
+        )
+        .unwrap();
+        STree {
+            name: STreeFilename {
+                distribution: "hanging".parse().unwrap(),
+                version: "0.0.0".parse().unwrap(),
+                url: url::Url::from_directory_path(dir).unwrap(),
+            },
+            location: parking_lot::Mutex::new(dir.to_path_buf()),
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn share_build_environments() {
+        let backend_dir = tempfile::tempdir().unwrap();
+        fs::write(backend_dir.path().join("empty_backend.py"), "").unwrap();
+        let source_dirs = [tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap()];
+        let strees = source_dirs
+            .iter()
+            .enumerate()
+            .map(|(i, dir)| {
+                fs::write(
+                    dir.path().join("pyproject.toml"),
+                    "[build-system]\nrequires = []\nbuild-backend = \"empty_backend\"\n",
+                )
+                .unwrap();
+                STree {
+                    name: STreeFilename {
+                        distribution: format!("package-{i}").parse().unwrap(),
+                        version: "0.0.0".parse().unwrap(),
+                        url: url::Url::from_directory_path(dir.path()).unwrap(),
+                    },
+                    location: parking_lot::Mutex::new(dir.path().to_path_buf()),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let (package_db, _temp) = get_package_db();
+        let env_markers = Arc::new(Pep508EnvMakers::from_env().await.unwrap().0);
+        let env_variables = HashMap::from([(
+            "PYTHONPATH".to_string(),
+            backend_dir.path().to_string_lossy().to_string(),
+        )]);
+        let wheel_builder = || {
+            WheelBuilder::new(
+                package_db.clone(),
+                env_markers.clone(),
+                None,
+                ResolveOptions {
+                    persist_build_environments: true,
+                    ..Default::default()
+                },
+                env_variables.clone(),
+            )
+            .unwrap()
+        };
+
+        // Both sdists get their own copy of the same environment
+        let first_builder = wheel_builder();
+        let first = first_builder.setup_build_venv(&strees[0]).await.unwrap();
+        let second = first_builder.setup_build_venv(&strees[1]).await.unwrap();
+        assert_ne!(first.work_dir(), second.work_dir());
+        assert!(second.work_dir().join("venv/pyvenv.cfg").is_file());
+
+        // Only a single environment was created, it is persisted and reused by the next builder
+        let build_envs_dir = package_db.cache_dir().join("build-envs");
+        let persisted = || fs::read_dir(&build_envs_dir).unwrap().count();
+        assert_eq!(persisted(), 3);
+        let third = wheel_builder().setup_build_venv(&strees[0]).await.unwrap();
+        assert!(third.work_dir().join("venv/pyvenv.cfg").is_file());
+        assert_eq!(persisted(), 3);
+    }
+
+    /// Sets up a wheel builder that builds with the system python without build isolation, the
+    /// backend of [`hanging_stree`] is imported from `backend_dir`.
+    async fn setup_without_isolation(
+        backend_dir: &Path,
+        resolve_options: ResolveOptions,
+    ) -> (WheelBuilder, TempDir) {
+        let (package_db, tempdir) = get_package_db();
+        let env_markers = Arc::new(Pep508EnvMakers::from_env().await.unwrap().0);
+        let python = PythonLocation::System.executable().unwrap();
+        let env_variables = HashMap::from([(
+            "PYTHONPATH".to_string(),
+            backend_dir.to_string_lossy().to_string(),
+        )]);
+        let wheel_builder = WheelBuilder::new(
+            package_db,
+            env_markers,
+            None,
+            ResolveOptions {
+                build_isolation: BuildIsolation::NoIsolation(python),
+                ..resolve_options
+            },
+            env_variables,
+        )
+        .unwrap();
+        (wheel_builder, tempdir)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn sdist_metadata_is_cached() {
+        let backend_dir = tempfile::tempdir().unwrap();
+        fs::write(
+            backend_dir.path().join("metadata_backend.py"),
+            r#"import os
+
+def prepare_metadata_for_build_wheel(metadata_directory, config_settings=None):
+    with open(os.path.join(os.path.dirname(__file__), "calls"), "a") as f:
+        f.write("x")
+    dist_info = "metadata_package-1.0.dist-info"
+    os.mkdir(os.path.join(metadata_directory, dist_info))
+    with open(os.path.join(metadata_directory, dist_info, "METADATA"), "w") as f:
+        f.write("Metadata-Version: 2.1\nName: metadata-package\nVersion: 1.0\n")
+    return dist_info
+"#,
+        )
+        .unwrap();
+        let pyproject_toml =
+            "[build-system]\nrequires = []\nbuild-backend = \"metadata_backend\"\n";
+        let sdist = {
+            let mut archive = tar::Builder::new(flate2::write::GzEncoder::new(
+                Vec::new(),
+                flate2::Compression::default(),
+            ));
+            let mut header = tar::Header::new_gnu();
+            header.set_size(pyproject_toml.len() as u64);
+            header.set_mode(0o644);
+            archive
+                .append_data(
+                    &mut header,
+                    "metadata_package-1.0/pyproject.toml",
+                    pyproject_toml.as_bytes(),
+                )
+                .unwrap();
+            let bytes = archive.into_inner().unwrap().finish().unwrap();
+            let name = "metadata-package".parse().unwrap();
+            SDist::from_bytes(
+                SDistFilename::from_filename("metadata_package-1.0.tar.gz", &name).unwrap(),
+                Box::new(std::io::Cursor::new(bytes)),
+            )
+            .unwrap()
+        };
+        let source_dir = tempfile::tempdir().unwrap();
+        fs::write(source_dir.path().join("pyproject.toml"), pyproject_toml).unwrap();
+        let stree = STree {
+            name: STreeFilename {
+                distribution: "metadata-package".parse().unwrap(),
+                version: "0.0.0".parse().unwrap(),
+                url: url::Url::from_directory_path(source_dir.path()).unwrap(),
+            },
+            location: parking_lot::Mutex::new(source_dir.path().to_path_buf()),
+        };
+        let calls = || fs::read_to_string(backend_dir.path().join("calls")).unwrap();
+
+        let (wheel_builder, _temp) =
+            setup_without_isolation(backend_dir.path(), ResolveOptions::default()).await;
+        let (_, metadata) = wheel_builder.get_sdist_metadata(&sdist).await.unwrap();
+        assert_eq!(metadata.name.as_source_str(), "metadata-package");
+
+        // Another builder that uses the same cache does not run the build backend again
+        let wheel_builder = WheelBuilder::new(
+            wheel_builder.package_db.clone(),
+            wheel_builder.env_markers.clone(),
+            None,
+            wheel_builder.resolve_options.clone(),
+            wheel_builder.env_variables.clone(),
+        )
+        .unwrap();
+        let (_, metadata) = wheel_builder.get_sdist_metadata(&sdist).await.unwrap();
+        assert_eq!(metadata.name.as_source_str(), "metadata-package");
+        assert_eq!(calls(), "x");
+
+        // The metadata of a source tree is cached until one of its files changes
+        wheel_builder.get_sdist_metadata(&stree).await.unwrap();
+        wheel_builder.get_sdist_metadata(&stree).await.unwrap();
+        assert_eq!(calls(), "xx");
+        fs::write(source_dir.path().join("setup.py"), "").unwrap();
+        wheel_builder.get_sdist_metadata(&stree).await.unwrap();
+        assert_eq!(calls(), "xxx");
+        fs::write(
+            source_dir.path().join("pyproject.toml"),
+            format!("{pyproject_toml}# edited\n"),
+        )
+        .unwrap();
+        wheel_builder.get_sdist_metadata(&stree).await.unwrap();
+        assert_eq!(calls(), "xxxx");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn build_wheel_without_cache_runs_the_backend() {
+        let backend_dir = tempfile::tempdir().unwrap();
+        fs::write(
+            backend_dir.path().join("wheel_backend.py"),
+            r#"import os, zipfile
+
+def build_wheel(wheel_directory, config_settings=None, metadata_directory=None):
+    with open(os.path.join(os.path.dirname(__file__), "calls"), "a") as f:
+        f.write("x")
+    name = "wheel_package-1.0-py3-none-any.whl"
+    with zipfile.ZipFile(os.path.join(wheel_directory, name), "w") as z:
+        z.writestr("wheel_package-1.0.dist-info/METADATA", "Metadata-Version: 2.1\nName: wheel-package\nVersion: 1.0\n")
+        z.writestr("wheel_package-1.0.dist-info/WHEEL", "Wheel-Version: 1.0\nRoot-Is-Purelib: true\nTag: py3-none-any\n")
+        z.writestr("wheel_package-1.0.dist-info/RECORD", "")
+    return name
+"#,
+        )
+        .unwrap();
+        let source_dir = tempfile::tempdir().unwrap();
+        fs::write(
+            source_dir.path().join("pyproject.toml"),
+            "[build-system]\nrequires = []\nbuild-backend = \"wheel_backend\"\n",
+        )
+        .unwrap();
+        let stree = STree {
+            name: STreeFilename {
+                distribution: "wheel-package".parse().unwrap(),
+                version: "1.0".parse().unwrap(),
+                url: url::Url::from_directory_path(source_dir.path()).unwrap(),
+            },
+            location: parking_lot::Mutex::new(source_dir.path().to_path_buf()),
+        };
+        let calls = || fs::read_to_string(backend_dir.path().join("calls")).unwrap();
+
+        let (wheel_builder, _temp) =
+            setup_without_isolation(backend_dir.path(), ResolveOptions::default()).await;
+        wheel_builder.build_wheel(&stree).await.unwrap();
+        wheel_builder.build_wheel(&stree).await.unwrap();
+        assert_eq!(calls(), "x");
+
+        // The cached wheel is not used
+        let wheel = wheel_builder
+            .build_wheel_without_cache(&stree)
+            .await
+            .unwrap();
+        assert_eq!(wheel.name.distribution.as_source_str(), "wheel_package");
+        assert_eq!(calls(), "xx");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn build_editable_without_pep660_writes_a_record() {
+        // A backend without `build_editable` whose wheels don't contain a RECORD
+        let backend_dir = tempfile::tempdir().unwrap();
+        fs::write(
+            backend_dir.path().join("legacy_backend.py"),
+            r#"import os, zipfile
+
+def build_wheel(wheel_directory, config_settings=None, metadata_directory=None):
+    name = "legacy_package-1.0-py3-none-any.whl"
+    with zipfile.ZipFile(os.path.join(wheel_directory, name), "w") as z:
+        z.writestr("legacy_package/__init__.py", "")
+        z.writestr("legacy_package-1.0.dist-info/METADATA", "Metadata-Version: 2.1\nName: legacy-package\nVersion: 1.0\n")
+        z.writestr("legacy_package-1.0.dist-info/WHEEL", "Wheel-Version: 1.0\nRoot-Is-Purelib: true\nTag: py3-none-any\n")
+    return name
+"#,
+        )
+        .unwrap();
+        let source_dir = tempfile::tempdir().unwrap();
+        fs::write(
+            source_dir.path().join("pyproject.toml"),
+            "[build-system]\nrequires = []\nbuild-backend = \"legacy_backend\"\n",
+        )
+        .unwrap();
+        fs::create_dir(source_dir.path().join("legacy_package")).unwrap();
+        fs::write(source_dir.path().join("legacy_package/__init__.py"), "").unwrap();
+        let stree = STree {
+            name: STreeFilename {
+                distribution: "legacy-package".parse().unwrap(),
+                version: "1.0".parse().unwrap(),
+                url: url::Url::from_directory_path(source_dir.path()).unwrap(),
+            },
+            location: parking_lot::Mutex::new(source_dir.path().to_path_buf()),
+        };
+
+        let (wheel_builder, _temp) =
+            setup_without_isolation(backend_dir.path(), ResolveOptions::default()).await;
+        let wheel = wheel_builder.build_editable(&stree).await.unwrap();
+
+        let mut bytes = Vec::new();
+        wheel.write_to(&mut bytes).unwrap();
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes)).unwrap();
+        let mut record = String::new();
+        archive
+            .by_name("legacy_package-1.0.dist-info/RECORD")
+            .unwrap()
+            .read_to_string(&mut record)
+            .unwrap();
+        let files = record
+            .lines()
+            .map(|line| line.split(',').next().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            files,
+            [
+                "legacy_package-1.0.dist-info/METADATA",
+                "legacy_package-1.0.dist-info/WHEEL",
+                "__editable__.legacy_package.pth",
+                "legacy_package-1.0.dist-info/RECORD",
+            ]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn build_wheel_timeout() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let stree = hanging_stree(source_dir.path());
+        let (wheel_builder, _temp) = setup_without_isolation(
+            source_dir.path(),
+            ResolveOptions {
+                build_timeout: Some(Duration::from_secs(1)),
+                ..Default::default()
+            },
+        )
+        .await;
+
+        let result = wheel_builder.build_wheel(&stree).await;
+        assert!(
+            matches!(result, Err(WheelBuildError::Timeout { ref stage, .. }) if stage == "Wheel"),
+            "unexpected result: {:?}",
+            result.map(|wheel| wheel.name)
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    pub async fn build_wheel_cancelled() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let stree = hanging_stree(source_dir.path());
+        let (wheel_builder, _temp) =
+            setup_without_isolation(source_dir.path(), ResolveOptions::default()).await;
+        let cancellation_token = CancellationToken::new();
+        let wheel_builder = wheel_builder.with_cancellation_token(cancellation_token.clone());
+
+        let cancel = async {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            cancellation_token.cancel();
+        };
+        let (result, _) = futures::join!(wheel_builder.build_wheel(&stree), cancel);
+        assert!(
+            matches!(result, Err(WheelBuildError::Cancelled)),
+            "unexpected result: {:?}",
+            result.map(|wheel| wheel.name)
+        );
+    }
+
     // Enable this if you need to know what's going on
     // #[traced_test]
     #[tokio::test(flavor = "multi_thread")]
@@ -0,0 +1,370 @@
+//! Runs the build backend with restricted privileges, see [`BuildSandbox`].
+
+use crate::resolve::solve_options::BuildSandbox;
+use crate::wheel_builder::build_log::BuildLog;
+use crate::wheel_builder::WheelBuildError;
+use std::path::Path;
+use std::process::{Command, ExitStatus};
+use std::time::Duration;
+
+/// Describes how the build backend violated the restrictions of the sandbox.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum SandboxViolation {
+    /// The build backend used more CPU time than allowed
+    #[error("the build backend exceeded the cpu time limit of {}s", .0.as_secs())]
+    CpuTimeLimit(Duration),
+
+    /// The build backend tried to allocate more memory than allowed
+    #[error("the build backend exceeded the memory limit of {0} bytes")]
+    MemoryLimit(u64),
+
+    /// The build backend tried to access the network
+    #[error("the build backend tried to access the network")]
+    NetworkAccess,
+
+    /// The build backend tried to write outside of its output directory
+    #[error("the build backend tried to write to the read-only source tree")]
+    ReadOnlySourceTree,
+}
+
+/// Messages in the output of the build backend that indicate that it tried to access the network.
+const NETWORK_ACCESS_MESSAGES: &[&str] = &[
+    "Network is unreachable",
+    "Temporary failure in name resolution",
+];
+
+/// Determines whether a failed build backend hook failed because it violated the sandbox.
+pub(crate) fn detect_violation(
+    sandbox: &BuildSandbox,
+    status: &ExitStatus,
+    log: &BuildLog,
+) -> Option<SandboxViolation> {
+    if let Some(limit) = sandbox.cpu_time_limit {
+        if killed_by_cpu_time_limit(status) {
+            return Some(SandboxViolation::CpuTimeLimit(limit));
+        }
+    }
+
+    let contains = |needle: &str| log.lines().any(|line| line.contains(needle));
+    if let Some(limit) = sandbox.memory_limit {
+        if contains("MemoryError") || contains("Cannot allocate memory") {
+            return Some(SandboxViolation::MemoryLimit(limit));
+        }
+    }
+    if NETWORK_ACCESS_MESSAGES.iter().any(|m| contains(m)) {
+        return Some(SandboxViolation::NetworkAccess);
+    }
+    if contains("Read-only file system") {
+        return Some(SandboxViolation::ReadOnlySourceTree);
+    }
+
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn killed_by_cpu_time_limit(status: &ExitStatus) -> bool {
+    use std::os::unix::process::ExitStatusExt;
+    // The soft limit raises SIGXCPU. The hard limit kills the process with SIGKILL one second
+    // later, but so does the OOM killer or a user, so that is not attributed to the limit.
+    status.signal() == Some(libc::SIGXCPU)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn killed_by_cpu_time_limit(_status: &ExitStatus) -> bool {
+    false
+}
+
+/// Configures `command` to run in the sandbox. `protected_dir`, the source tree of the user, is
+/// mounted read-only, except for `output_dir`. The copy of the sources in the work directory that
+/// the backend builds from stays writable.
+#[cfg(target_os = "linux")]
+pub(crate) fn apply(
+    command: &mut Command,
+    sandbox: &BuildSandbox,
+    protected_dir: Option<&Path>,
+    output_dir: &Path,
+) -> Result<(), WheelBuildError> {
+    linux::apply(command, sandbox, protected_dir, output_dir)
+        .map_err(WheelBuildError::CouldNotSetupSandbox)
+}
+
+/// Sandboxing is only supported on Linux.
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn apply(
+    _command: &mut Command,
+    _sandbox: &BuildSandbox,
+    _protected_dir: Option<&Path>,
+    _output_dir: &Path,
+) -> Result<(), WheelBuildError> {
+    Err(WheelBuildError::SandboxUnsupported)
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use crate::resolve::solve_options::BuildSandbox;
+    use std::ffi::{CStr, CString};
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::process::CommandExt;
+    use std::path::Path;
+    use std::process::Command;
+
+    fn to_cstring(path: &Path) -> io::Result<CString> {
+        CString::new(path.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+    }
+
+    fn check(result: libc::c_int) -> io::Result<()> {
+        if result == -1 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns the mount flags of the file system that contains `path`. A bind mount inside a user
+    /// namespace can only be remounted if it keeps these flags.
+    fn mount_flags(path: &CStr) -> io::Result<libc::c_ulong> {
+        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+        check(unsafe { libc::statvfs(path.as_ptr(), &mut stat) })?;
+        let mut flags = 0;
+        for (st, ms) in [
+            (libc::ST_NOSUID, libc::MS_NOSUID),
+            (libc::ST_NODEV, libc::MS_NODEV),
+            (libc::ST_NOEXEC, libc::MS_NOEXEC),
+            (libc::ST_NOATIME, libc::MS_NOATIME),
+            (libc::ST_NODIRATIME, libc::MS_NODIRATIME),
+            (libc::ST_RELATIME, libc::MS_RELATIME),
+        ] {
+            if stat.f_flag & st != 0 {
+                flags |= ms;
+            }
+        }
+        Ok(flags)
+    }
+
+    /// Writes `contents` to the file at `path` without allocating, this runs after `fork`.
+    fn write_file(path: &CStr, contents: &[u8]) -> io::Result<()> {
+        let fd = unsafe { libc::open(path.as_ptr(), libc::O_WRONLY) };
+        check(fd)?;
+        let written = unsafe { libc::write(fd, contents.as_ptr().cast(), contents.len()) };
+        unsafe { libc::close(fd) };
+        if written == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    fn bind_mount(path: &CStr, flags: libc::c_ulong) -> io::Result<()> {
+        check(unsafe {
+            libc::mount(
+                path.as_ptr(),
+                path.as_ptr(),
+                std::ptr::null(),
+                flags,
+                std::ptr::null(),
+            )
+        })
+    }
+
+    fn limit(soft: u64, hard: u64) -> libc::rlimit {
+        libc::rlimit {
+            rlim_cur: soft as libc::rlim_t,
+            rlim_max: hard as libc::rlim_t,
+        }
+    }
+
+    fn c_str(bytes: &'static [u8]) -> &'static CStr {
+        CStr::from_bytes_with_nul(bytes).expect("string should be nul terminated")
+    }
+
+    pub(super) fn apply(
+        command: &mut Command,
+        sandbox: &BuildSandbox,
+        protected_dir: Option<&Path>,
+        output_dir: &Path,
+    ) -> io::Result<()> {
+        // Everything that requires an allocation is prepared before forking
+        let uid = unsafe { libc::getuid() };
+        let gid = unsafe { libc::getgid() };
+        let uid_map = format!("{uid} {uid} 1");
+        let gid_map = format!("{gid} {gid} 1");
+
+        let read_only = protected_dir
+            .map(|protected_dir| -> io::Result<_> {
+                let source = to_cstring(protected_dir)?;
+                let flags = mount_flags(&source)?;
+                let writable = if output_dir.starts_with(protected_dir) {
+                    Some(to_cstring(output_dir)?)
+                } else {
+                    None
+                };
+                Ok((source, flags, writable))
+            })
+            .transpose()?;
+        let current_dir = to_cstring(&std::env::current_dir()?)?;
+        let current_dir = command
+            .get_current_dir()
+            .map(to_cstring)
+            .transpose()?
+            .unwrap_or(current_dir);
+        let cpu_time_limit = sandbox.cpu_time_limit.map(|d| d.as_secs().max(1));
+        let memory_limit = sandbox.memory_limit;
+
+        let setup = move || -> io::Result<()> {
+            check(unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) })?;
+            check(unsafe {
+                libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNS | libc::CLONE_NEWNET)
+            })?;
+
+            // Map the current user into the new user namespace
+            write_file(c_str(b"/proc/self/setgroups\0"), b"deny")?;
+            write_file(c_str(b"/proc/self/uid_map\0"), uid_map.as_bytes())?;
+            write_file(c_str(b"/proc/self/gid_map\0"), gid_map.as_bytes())?;
+
+            if let Some((source, flags, writable)) = &read_only {
+                // Don't propagate the mounts below to the parent namespace
+                check(unsafe {
+                    libc::mount(
+                        std::ptr::null(),
+                        c_str(b"/\0").as_ptr(),
+                        std::ptr::null(),
+                        libc::MS_REC | libc::MS_PRIVATE,
+                        std::ptr::null(),
+                    )
+                })?;
+                // The output directory gets its own mount so that it stays writable
+                if let Some(writable) = writable {
+                    bind_mount(writable, libc::MS_BIND)?;
+                }
+                bind_mount(source, libc::MS_BIND | libc::MS_REC)?;
+                bind_mount(
+                    source,
+                    libc::MS_BIND | libc::MS_REMOUNT | libc::MS_RDONLY | flags,
+                )?;
+                // The working directory still refers to the original mount
+                check(unsafe { libc::chdir(current_dir.as_ptr()) })?;
+            }
+
+            if let Some(seconds) = cpu_time_limit {
+                check(unsafe { libc::setrlimit(libc::RLIMIT_CPU, &limit(seconds, seconds + 1)) })?;
+            }
+            if let Some(bytes) = memory_limit {
+                check(unsafe { libc::setrlimit(libc::RLIMIT_AS, &limit(bytes, bytes)) })?;
+            }
+            Ok(())
+        };
+
+        // SAFETY: the closure only performs system calls and does not allocate
+        unsafe { command.pre_exec(setup) };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::wheel_builder::build_log::BUILD_LOG_CAPACITY;
+
+    fn log(lines: &[&str]) -> BuildLog {
+        let mut log = BuildLog::with_capacity(BUILD_LOG_CAPACITY);
+        for line in lines {
+            log.push(line.to_string());
+        }
+        log
+    }
+
+    fn failed() -> ExitStatus {
+        Command::new("sh").args(["-c", "exit 1"]).status().unwrap()
+    }
+
+    #[test]
+    fn test_detect_violation() {
+        let sandbox = BuildSandbox {
+            cpu_time_limit: None,
+            memory_limit: Some(1024),
+        };
+        assert_eq!(
+            detect_violation(&sandbox, &failed(), &log(&["error: compiler crashed"])),
+            None
+        );
+        assert_eq!(
+            detect_violation(&sandbox, &failed(), &log(&["MemoryError"])),
+            Some(SandboxViolation::MemoryLimit(1024))
+        );
+        assert_eq!(
+            detect_violation(
+                &sandbox,
+                &failed(),
+                &log(&["OSError: [Errno 101] Network is unreachable"])
+            ),
+            Some(SandboxViolation::NetworkAccess)
+        );
+        assert_eq!(
+            detect_violation(
+                &sandbox,
+                &failed(),
+                &log(&["OSError: [Errno 30] Read-only file system: 'build'"])
+            ),
+            Some(SandboxViolation::ReadOnlySourceTree)
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_detect_cpu_time_limit() {
+        use std::os::unix::process::ExitStatusExt;
+        let sandbox = BuildSandbox {
+            cpu_time_limit: Some(Duration::from_secs(10)),
+            memory_limit: None,
+        };
+        assert_eq!(
+            detect_violation(&sandbox, &ExitStatus::from_raw(libc::SIGXCPU), &log(&[])),
+            Some(SandboxViolation::CpuTimeLimit(Duration::from_secs(10)))
+        );
+        assert_eq!(
+            detect_violation(&sandbox, &ExitStatus::from_raw(libc::SIGKILL), &log(&[])),
+            None
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_sandbox() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let output_dir = source_dir.path().join("output");
+        std::fs::create_dir(&output_dir).unwrap();
+        let work_dir = tempfile::tempdir().unwrap();
+
+        let mut command = Command::new("sh");
+        command.current_dir(work_dir.path()).args([
+            "-c",
+            &format!(
+                "touch {0}/output/allowed; touch {0}/denied; touch copy; cat /proc/net/dev",
+                source_dir.path().display()
+            ),
+        ]);
+        apply(
+            &mut command,
+            &BuildSandbox::default(),
+            Some(source_dir.path()),
+            &output_dir,
+        )
+        .unwrap();
+        let output = match command.output() {
+            Ok(output) => output,
+            // User namespaces are not available in every environment
+            Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => return,
+            Err(e) => panic!("{e}"),
+        };
+
+        assert!(output_dir.join("allowed").is_file());
+        assert!(!source_dir.path().join("denied").exists());
+        assert!(work_dir.path().join("copy").is_file());
+
+        // Only the loopback interface exists in the network namespace
+        let interfaces = String::from_utf8_lossy(&output.stdout);
+        assert!(interfaces.contains("lo:"));
+        assert_eq!(interfaces.lines().count(), 3);
+    }
+}
@@ -0,0 +1,370 @@
+//! Build environments that are shared between sdists with the same build requirements.
+
+use crate::artifacts::wheel::UnpackWheelOptions;
+use crate::index::CacheStore;
+use crate::python_env::{VEnv, VEnvError};
+use crate::resolve::{resolve, PinnedPackage};
+use crate::utils::retry_interrupted;
+use crate::wheel_builder::{WheelBuildError, WheelBuilder};
+use fs4::FileExt;
+use fs_err as fs;
+use itertools::Itertools;
+use parking_lot::Mutex;
+use pep508_rs::Requirement;
+use rattler_digest::Sha256;
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::sync::OnceCell;
+
+/// A virtual environment that only contains the resolved build requirements of one or more sdists.
+/// Every build gets its own copy of the environment because the build backend can request
+/// additional requirements.
+#[derive(Debug)]
+pub(crate) struct BuildEnvironmentTemplate {
+    venv: VEnv,
+
+    /// Removes the environment when it is not persisted in the cache directory
+    _temp_dir: Option<tempfile::TempDir>,
+}
+
+impl BuildEnvironmentTemplate {
+    /// Creates a copy of the environment at `target` in which an sdist can be built.
+    pub fn clone_to(&self, target: &Path) -> Result<VEnv, WheelBuildError> {
+        Ok(self.venv.clone_to(target)?)
+    }
+}
+
+type TemplateCell = Arc<OnceCell<Arc<BuildEnvironmentTemplate>>>;
+
+/// The build environments that are shared between all sdists built by a [`WheelBuilder`].
+#[derive(Default)]
+pub(crate) struct SharedBuildEnvironments {
+    /// The resolved build requirements, keyed by the sorted build requirements
+    resolutions: Mutex<HashMap<Vec<String>, Vec<PinnedPackage>>>,
+
+    /// The environments, keyed by the resolved build requirements (see [`template_key`])
+    templates: Mutex<HashMap<String, TemplateCell>>,
+}
+
+impl SharedBuildEnvironments {
+    /// Resolves the build requirements of an sdist. Identical requirements are only resolved once.
+    pub async fn resolve(
+        &self,
+        requirements: &[Requirement],
+        wheel_builder: &WheelBuilder,
+    ) -> Result<Vec<PinnedPackage>, WheelBuildError> {
+        let key = requirements
+            .iter()
+            .map(ToString::to_string)
+            .sorted()
+            .collect_vec();
+        if let Some(resolved) = self.resolutions.lock().get(&key) {
+            return Ok(resolved.clone());
+        }
+
+        let resolved = resolve(
+            wheel_builder.package_db.clone(),
+            requirements.iter(),
+            wheel_builder.env_markers.clone(),
+            wheel_builder.wheel_tags.clone(),
+            HashMap::default(),
+            HashMap::default(),
+            wheel_builder.resolve_options.clone(),
+            Default::default(),
+        )
+        .await
+        .map_err(|e| WheelBuildError::CouldNotResolveEnvironment(requirements.to_vec(), e))?
+        .packages;
+
+        self.resolutions.lock().insert(key, resolved.clone());
+        Ok(resolved)
+    }
+
+    /// Returns the environment that contains the resolved build requirements, the environment is
+    /// created if it does not exist yet.
+    pub async fn template(
+        &self,
+        resolved: &[PinnedPackage],
+        wheel_builder: &WheelBuilder,
+    ) -> Result<Arc<BuildEnvironmentTemplate>, WheelBuildError> {
+        let key = template_key(resolved, wheel_builder)?;
+        let cell = self
+            .templates
+            .lock()
+            .entry(key.clone())
+            .or_default()
+            .clone();
+        cell.get_or_try_init(|| create_template(&key, resolved, wheel_builder))
+            .await
+            .cloned()
+    }
+}
+
+/// Returns a key that identifies the environment with the resolved build requirements for the
+/// python interpreter of the wheel builder.
+fn template_key(
+    resolved: &[PinnedPackage],
+    wheel_builder: &WheelBuilder,
+) -> Result<String, WheelBuildError> {
+    let python = wheel_builder
+        .resolve_options
+        .python_location
+        .executable()
+        .map_err(VEnvError::from)?;
+    let packages = resolved
+        .iter()
+        .map(|package| {
+            let artifact = package.artifacts.first().map(|artifact| &artifact.url);
+            format!(
+                "{}=={} {}",
+                package.name.as_str(),
+                package.version,
+                artifact.map(ToString::to_string).unwrap_or_default()
+            )
+        })
+        .sorted()
+        .join("\n");
+    let version = wheel_builder.python_version();
+    let key = format!(
+        "{}\n{}.{}.{}\n{}",
+        python.display(),
+        version.major,
+        version.minor,
+        version.patch,
+        packages
+    );
+    Ok(format!(
+        "{:x}",
+        rattler_digest::compute_bytes_digest::<Sha256>(key)
+    ))
+}
+
+/// Creates an environment with the resolved build requirements. If build environments are
+/// persisted the environment is created in the cache directory, or reused from a previous run.
+async fn create_template(
+    key: &str,
+    resolved: &[PinnedPackage],
+    wheel_builder: &WheelBuilder,
+) -> Result<Arc<BuildEnvironmentTemplate>, WheelBuildError> {
+    let python_location = wheel_builder.resolve_options.python_location.clone();
+    if !wheel_builder.resolve_options.persist_build_environments {
+        let temp_dir = tempfile::tempdir()?;
+        let venv = VEnv::create(&temp_dir.path().join("venv"), python_location)?;
+        install_packages(&venv, resolved, wheel_builder).await?;
+        return Ok(Arc::new(BuildEnvironmentTemplate {
+            venv,
+            _temp_dir: Some(temp_dir),
+        }));
+    }
+
+    // Other processes might create the same environment at the same time
+    let build_envs_dir = wheel_builder
+        .package_db
+        .cache_dir()
+        .join(CacheStore::BuildEnvironments.dir_name());
+    fs::create_dir_all(&build_envs_dir)?;
+    let _lock = lock(&build_envs_dir.join(format!("{key}.lock"))).await?;
+
+    // The marker is written once all the packages have been installed
+    let location = build_envs_dir.join(key);
+    let complete_marker = build_envs_dir.join(format!("{key}.complete"));
+    if complete_marker.is_file() {
+        tracing::info!("reusing build environment {}", location.display());
+        // The modification time of the marker is used to prune environments that are not used
+        filetime::set_file_mtime(&complete_marker, filetime::FileTime::now())?;
+        return Ok(Arc::new(BuildEnvironmentTemplate {
+            venv: VEnv::from_location(location, wheel_builder.python_version().clone()),
+            _temp_dir: None,
+        }));
+    }
+
+    // Remove the remains of an interrupted attempt
+    if location.exists() {
+        fs::remove_dir_all(&location)?;
+    }
+    let venv = VEnv::create(&location, python_location)?;
+    install_packages(&venv, resolved, wheel_builder).await?;
+    fs::write(complete_marker, "")?;
+
+    Ok(Arc::new(BuildEnvironmentTemplate {
+        venv,
+        _temp_dir: None,
+    }))
+}
+
+/// Installs the resolved build requirements into the environment.
+async fn install_packages(
+    venv: &VEnv,
+    resolved: &[PinnedPackage],
+    wheel_builder: &WheelBuilder,
+) -> Result<(), WheelBuildError> {
+    for package_info in resolved.iter() {
+        let artifact_info = package_info.artifacts.first().unwrap();
+
+        let (artifact, _) = wheel_builder
+            .package_db
+            .get_wheel(artifact_info, Some(wheel_builder))
+            .await
+            .map_err(WheelBuildError::CouldNotGetArtifact)?;
+
+        venv.install_wheel(
+            &artifact,
+            &UnpackWheelOptions {
+                installer: None,
+                ..Default::default()
+            },
+        )?;
+    }
+    Ok(())
+}
+
+/// Takes an exclusive lock on the file at `path`, the lock is released when the file is dropped.
+async fn lock(path: &Path) -> std::io::Result<fs::File> {
+    let file = fs::OpenOptions::new().write(true).create(true).open(path)?;
+    tokio::task::spawn_blocking(move || {
+        retry_interrupted(|| file.file().lock_exclusive())?;
+        Ok(file)
+    })
+    .await
+    .expect("locking the build environment should not panic")
+}
+
+/// A build environment that is persisted in the cache directory.
+#[derive(Debug, Clone)]
+pub(crate) struct PersistedEnvironment {
+    /// The directory of the environment
+    pub path: PathBuf,
+
+    /// The total size of the files in the environment in bytes
+    pub size: u64,
+
+    /// The last time the environment was created or reused
+    pub last_access: SystemTime,
+
+    /// False if the creation of the environment was interrupted
+    pub complete: bool,
+}
+
+impl PersistedEnvironment {
+    fn lock_path(&self) -> PathBuf {
+        with_suffix(&self.path, "lock")
+    }
+
+    fn complete_marker(&self) -> PathBuf {
+        with_suffix(&self.path, "complete")
+    }
+}
+
+/// Returns `path` with `.{suffix}` appended to its file name.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".");
+    file_name.push(suffix);
+    path.with_file_name(file_name)
+}
+
+/// Returns the build environments that are persisted in `build_envs_dir`.
+pub(crate) fn persisted_environments(
+    build_envs_dir: &Path,
+) -> io::Result<Vec<PersistedEnvironment>> {
+    let read_dir = match fs::read_dir(build_envs_dir) {
+        Ok(read_dir) => read_dir,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+
+    let mut environments = Vec::new();
+    for entry in read_dir {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let path = entry.path();
+        let complete_marker = with_suffix(&path, "complete");
+        // Environments can be removed by other processes while we iterate over them
+        let last_access = match fs::metadata(&complete_marker).or_else(|_| entry.metadata()) {
+            Ok(metadata) => metadata.modified()?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        };
+        environments.push(PersistedEnvironment {
+            size: dir_size(&path)?,
+            last_access,
+            complete: complete_marker.is_file(),
+            path,
+        });
+    }
+    Ok(environments)
+}
+
+/// Removes a persisted build environment. Waits until no other process is creating it.
+pub(crate) async fn remove_persisted_environment(
+    environment: &PersistedEnvironment,
+) -> io::Result<()> {
+    let _lock = lock(&environment.lock_path()).await?;
+    // Remove the marker first so that the environment is not reused while it is removed
+    for result in [
+        fs::remove_file(environment.complete_marker()),
+        fs::remove_dir_all(&environment.path),
+    ] {
+        match result {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Removes the build environments whose creation was interrupted. Returns the removed
+/// environments.
+pub(crate) async fn remove_incomplete_environments(
+    build_envs_dir: &Path,
+) -> io::Result<Vec<PersistedEnvironment>> {
+    let mut removed = Vec::new();
+    for environment in persisted_environments(build_envs_dir)? {
+        if environment.complete {
+            continue;
+        }
+        let _lock = lock(&environment.lock_path()).await?;
+        // The environment might have been completed by the process that held the lock
+        if environment.complete_marker().is_file() {
+            continue;
+        }
+        match fs::remove_dir_all(&environment.path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => removed.push(environment),
+        }
+    }
+    Ok(removed)
+}
+
+/// Returns the total size of the files in a directory, files that are removed while iterating
+/// are skipped.
+fn dir_size(path: &Path) -> io::Result<u64> {
+    let mut size = 0;
+    let mut dirs = vec![path.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let read_dir = match fs::read_dir(&dir) {
+            Ok(read_dir) => read_dir,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        };
+        for entry in read_dir {
+            let entry = entry?;
+            let metadata = match entry.metadata() {
+                Ok(metadata) => metadata,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err),
+            };
+            if metadata.is_dir() {
+                dirs.push(entry.path());
+            } else {
+                size += metadata.len();
+            }
+        }
+    }
+    Ok(size)
+}
@@ -5,6 +5,9 @@ from pathlib import Path
 from importlib import import_module
 from json import loads
 from types import ModuleType
+from base64 import urlsafe_b64encode
+from hashlib import sha256
+from zipfile import ZipFile, ZIP_DEFLATED
 import json
 
 ################################################################
@@ -46,16 +49,46 @@ def get_backend_from_entry_point(entrypoint: str) -> ModuleType:
     return backend
 
 
-def get_requires_for_build_wheel(backend: ModuleType, work_dir: Path) -> [str]:
+def get_requires_for_build_wheel(backend: ModuleType, work_dir: Path, config_settings: dict) -> [str]:
     """
     Returns a list of requirements. This is only necessary if we do not
     have a pyproject.toml file.
     """
-    f = getattr(backend, "get_requires_for_build_wheel")
+    f = getattr(backend, "get_requires_for_build_wheel", None)
     if f is None:
         result = []
     else:
-        result = f()
+        result = f(config_settings=config_settings)
+
+    j = json.dumps(result)
+    out_json_file = work_dir / "extra_requirements.json"
+    out_json_file.write_text(j)
+    print(j)
+
+def get_requires_for_build_editable(backend: ModuleType, work_dir: Path, config_settings: dict) -> [str]:
+    """
+    Returns the additional requirements to build an editable wheel (PEP 660).
+    """
+    f = getattr(backend, "get_requires_for_build_editable", None)
+    if f is None:
+        result = []
+    else:
+        result = f(config_settings=config_settings)
+
+    j = json.dumps(result)
+    out_json_file = work_dir / "extra_requirements.json"
+    out_json_file.write_text(j)
+    print(j)
+
+def get_requires_for_build_sdist(backend: ModuleType, work_dir: Path, config_settings: dict) -> [str]:
+    """
+    Returns the additional requirements to build an sdist.
+    """
+    f = getattr(backend, "get_requires_for_build_sdist", None)
+    if f is None:
+        result = []
+    else:
+        result = f(config_settings=config_settings)
 
     j = json.dumps(result)
     out_json_file = work_dir / "extra_requirements.json"
@@ -65,7 +98,7 @@ def get_requires_for_build_wheel(backend: ModuleType, work_dir: Path) -> [str]:
 def metadata_dirs(work_dir: Path):
     return work_dir / "metadata"
 
-def prepare_metadata_for_build_wheel(backend: ModuleType, work_dir: Path):
+def prepare_metadata_for_build_wheel(backend: ModuleType, work_dir: Path, config_settings: dict):
     """
     Prepare any files that need to be generated before building the wheel.
     """
@@ -76,7 +109,7 @@ def prepare_metadata_for_build_wheel(backend: ModuleType, work_dir: Path):
         # Create the metadata output directory
         d = metadata_dirs(work_dir)
         d.mkdir()
-        dist_info = backend.prepare_metadata_for_build_wheel(str(d))
+        dist_info = backend.prepare_metadata_for_build_wheel(str(d), config_settings=config_settings)
         # Path to the dist-info directory
         result = str(d / dist_info)
         # Write the path to the dist-info directory to a file
@@ -87,7 +120,7 @@ def prepare_metadata_for_build_wheel(backend: ModuleType, work_dir: Path):
 def wheel_dirs(work_dir: Path):
     return work_dir / "wheel"
 
-def build_wheel(backend: ModuleType, work_dir: Path):
+def build_wheel(backend: ModuleType, work_dir: Path, config_settings: dict):
     """Take a folder with an SDist and build a wheel from it."""
     wheel_dir = wheel_dirs(work_dir)
     result_file = work_dir / "wheel_result"
@@ -102,13 +135,168 @@ def build_wheel(backend: ModuleType, work_dir: Path):
     wheel_dir.mkdir()
     wheel_basename = backend.build_wheel(
         str(wheel_dir),
+        config_settings=config_settings,
         metadata_directory=metadata_dir,
     )
 
     result_file.write_text(str(wheel_dir / wheel_basename))
 
+def sdist_dirs(work_dir: Path):
+    return work_dir / "sdist"
+
+def build_sdist(backend: ModuleType, work_dir: Path, config_settings: dict):
+    """Take a folder with a source tree and build an sdist from it."""
+    sdist_dir = sdist_dirs(work_dir)
+    result_file = work_dir / "sdist_result"
+
+    sdist_dir.mkdir()
+    sdist_basename = backend.build_sdist(str(sdist_dir), config_settings=config_settings)
+
+    result_file.write_text(str(sdist_dir / sdist_basename))
+
+def editable_dirs(work_dir: Path):
+    return work_dir / "editable"
+
+def build_editable(backend: ModuleType, work_dir: Path, config_settings: dict):
+    """Build an editable wheel (PEP 660) for the project in the current directory."""
+    editable_dir = editable_dirs(work_dir)
+    result_file = work_dir / "editable_result"
+    editable_dir.mkdir()
+
+    if hasattr(backend, "build_editable"):
+        metadata_dir = None
+        if hasattr(backend, "prepare_metadata_for_build_editable"):
+            d = work_dir / "editable_metadata"
+            d.mkdir()
+            dist_info = backend.prepare_metadata_for_build_editable(
+                str(d), config_settings=config_settings
+            )
+            metadata_dir = str(d / dist_info)
+
+        wheel_basename = backend.build_editable(
+            str(editable_dir),
+            config_settings=config_settings,
+            metadata_directory=metadata_dir,
+        )
+    else:
+        wheel_basename = build_pth_editable(backend, editable_dir, config_settings)
+
+    result_file.write_text(str(editable_dir / wheel_basename))
+
+def configured_package_dirs(project_dir: Path) -> [Path]:
+    """
+    Returns the directories the packages of the project are configured to live in, taken from
+    the setuptools configuration in pyproject.toml or setup.cfg.
+    """
+    dirs = []
+    try:
+        import tomllib
+    except ImportError:
+        try:
+            import tomli as tomllib
+        except ImportError:
+            tomllib = None
+    pyproject = project_dir / "pyproject.toml"
+    if tomllib is not None and pyproject.is_file():
+        try:
+            with open(pyproject, "rb") as f:
+                setuptools = tomllib.load(f).get("tool", {}).get("setuptools", {})
+        except tomllib.TOMLDecodeError:
+            setuptools = {}
+        package_dir = setuptools.get("package-dir", {})
+        if "" in package_dir:
+            dirs.append(project_dir / package_dir[""])
+        packages = setuptools.get("packages", {})
+        if isinstance(packages, dict):
+            dirs.extend(project_dir / where for where in packages.get("find", {}).get("where", []))
+
+    setup_cfg = project_dir / "setup.cfg"
+    if setup_cfg.is_file():
+        from configparser import ConfigParser
+        parser = ConfigParser()
+        parser.read(setup_cfg)
+        for line in parser.get("options", "package_dir", fallback="").splitlines():
+            name, sep, directory = line.partition("=")
+            if sep and not name.strip():
+                dirs.append(project_dir / directory.strip())
+
+    return dirs
+
+def import_roots(project_dir: Path, regular: ZipFile) -> [Path]:
+    """
+    Returns the directories that have to be added to sys.path to import the top-level packages
+    and modules that the backend put in the regular wheel.
+    """
+    top_levels = set()
+    for name in regular.namelist():
+        top_level = name.split("/")[0]
+        if not top_level.endswith((".dist-info", ".data")):
+            top_levels.add(top_level)
+
+    candidates = configured_package_dirs(project_dir) + [project_dir]
+    roots = []
+    for top_level in sorted(top_levels):
+        root = next((c for c in candidates if (c / top_level).exists()), None)
+        if root is None:
+            # Not in a configured location, look for the package anywhere in the project
+            for dir_path, dir_names, _ in os.walk(project_dir):
+                dir_names[:] = [d for d in dir_names if not d.startswith(".") and d not in ("build", "dist")]
+                if (Path(dir_path) / top_level).exists():
+                    root = Path(dir_path)
+                    break
+        if root is not None and root not in roots:
+            roots.append(root)
+
+    return roots or [project_dir]
+
+def build_pth_editable(backend: ModuleType, editable_dir: Path, config_settings: dict) -> str:
+    """
+    Backends that do not implement PEP 660 (e.g. setuptools < 64) can only build regular
+    wheels. Build a regular wheel and replace everything but its metadata with a .pth file that
+    adds the directories the packages are imported from to sys.path, like `setup.py develop` does.
+    """
+    wheel_dir = editable_dir / "regular"
+    wheel_dir.mkdir()
+    wheel_basename = backend.build_wheel(str(wheel_dir), config_settings=config_settings)
+
+    # Wheel filenames are escaped so the name and version never contain a dash
+    distribution, version = wheel_basename.split("-")[:2]
+
+    records = []
+    # Installers require a RECORD, fall back to the dist-info directory named by the wheel filename
+    # if the backend did not write one
+    record_path = f"{distribution}-{version}.dist-info/RECORD"
+    with ZipFile(wheel_dir / wheel_basename) as regular, \
+            ZipFile(editable_dir / wheel_basename, "w", ZIP_DEFLATED) as editable:
+        def write(name: str, data: bytes):
+            editable.writestr(name, data)
+            digest = urlsafe_b64encode(sha256(data).digest()).rstrip(b"=").decode()
+            records.append(f"{name},sha256={digest},{len(data)}")
+
+        for info in regular.infolist():
+            top_level, _, file_name = info.filename.partition("/")
+            if not top_level.endswith(".dist-info"):
+                continue
+            if file_name == "RECORD":
+                record_path = info.filename
+            else:
+                write(info.filename, regular.read(info))
+
+        roots = import_roots(Path.cwd(), regular)
+        write(f"__editable__.{distribution}.pth", "".join(f"{root}\n" for root in roots).encode())
+        records.append(f"{record_path},,")
+        editable.writestr(record_path, "\n".join(records) + "\n")
+
+    return wheel_basename
+
 if __name__ == "__main__":
-    work_dir, entry_point, goal = sys.argv[1:]
+    work_dir, entry_point, goal, config_settings = sys.argv[1:]
+
+    # Keys that were specified once are passed as a string, like pip does
+    config_settings = {
+        key: values[0] if len(values) == 1 else values
+        for key, values in loads(config_settings).items()
+    } or None
 
     backend_path = os.environ.get("PEP517_BACKEND_PATH")
     if backend_path:
@@ -121,10 +309,18 @@ if __name__ == "__main__":
     work_dir = Path(work_dir)
 
     if goal == "GetRequiresForBuildWheel":
-        get_requires_for_build_wheel(backend, work_dir)
+        get_requires_for_build_wheel(backend, work_dir, config_settings)
+    elif goal == "GetRequiresForBuildEditable":
+        get_requires_for_build_editable(backend, work_dir, config_settings)
+    elif goal == "GetRequiresForBuildSDist":
+        get_requires_for_build_sdist(backend, work_dir, config_settings)
     if goal == "WheelMetadata":
-        prepare_metadata_for_build_wheel(backend, work_dir)
+        prepare_metadata_for_build_wheel(backend, work_dir, config_settings)
     elif goal == "Wheel":
-        build_wheel(backend, work_dir)
+        build_wheel(backend, work_dir, config_settings)
+    elif goal == "Editable":
+        build_editable(backend, work_dir, config_settings)
+    elif goal == "SDist":
+        build_sdist(backend, work_dir, config_settings)
 
     exit(0)
@@ -29,13 +29,19 @@
 use crate::artifacts::Wheel;
 use crate::python_env::PythonInterpreterVersion;
 use crate::types::ArtifactFromSource;
-use crate::types::{ArtifactFromBytes, WheelFilename};
+use crate::types::{ArtifactFromBytes, NormalizedPackageName, Version, WheelFilename};
+use crate::utils::retry_interrupted;
 use cacache::{Integrity, WriteOpts};
+use fs4::FileExt;
+use fs_err as fs;
+use itertools::Itertools;
 use rattler_digest::Sha256;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::io::{Cursor, Read};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 /// Wrapper around an API built on top of cacache
 /// This is used to store wheels that are built from sdists
@@ -45,10 +51,36 @@ pub struct WheelCache {
     path: PathBuf,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 /// A key that can be used to retrieve a wheel from the cache
 pub struct WheelCacheKey(String);
 
+/// A wheel that is stored in the [`WheelCache`]
+#[derive(Debug, Clone)]
+pub struct WheelCacheEntry {
+    /// The key under which the wheel is stored
+    pub key: WheelCacheKey,
+
+    /// The filename of the wheel
+    pub wheel_filename: WheelFilename,
+
+    /// The size of the wheel in bytes
+    pub size: u64,
+
+    /// The time at which the wheel was added to the cache
+    pub time: SystemTime,
+
+    /// The integrity of the wheel contents
+    integrity: Integrity,
+}
+
+impl WheelCacheEntry {
+    /// The integrity of the wheel contents, entries with the same integrity share the wheel
+    pub(crate) fn integrity(&self) -> &Integrity {
+        &self.integrity
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 struct WheelKeyMetadata {
     wheel_filename: WheelFilename,
@@ -64,12 +96,36 @@ impl ToString for WheelCacheKey {
 }
 
 impl WheelCacheKey {
+    /// Returns the full key, including the prefix
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
     /// Create a wheel key from bytes, will become '{prefix}:{hash_hexadecimal}'
     pub fn from_bytes(prefix: impl AsRef<str>, bytes: impl AsRef<[u8]>) -> Self {
         let hash = rattler_digest::compute_bytes_digest::<Sha256>(bytes);
         Self(format!("{}:{:x}", prefix.as_ref(), hash))
     }
 
+    /// Returns the content hash of the sdist the wheel was built from, or `None` if the key was
+    /// not created with [`WheelCacheKey::from_sdist`]
+    pub fn sdist_hash(&self) -> Option<&str> {
+        let mut parts = self.0.split(':');
+        (parts.next()? == "sdist").then(|| parts.next()).flatten()
+    }
+
+    /// Returns the `major.minor` version of the python interpreter the wheel was built with, or
+    /// `None` if the key was not created with [`WheelCacheKey::from_sdist`]
+    pub fn python_version(&self) -> Option<&str> {
+        self.sdist_hash()?;
+        self.0.split(':').nth(2)?.strip_prefix('v')
+    }
+
+    /// Returns true if the wheel was built with PEP 517 `config_settings`
+    pub fn has_config_settings(&self) -> bool {
+        self.0.split(':').any(|part| part.starts_with("cs"))
+    }
+
     /// Create a wheel key from a prefix and a string, will become '{prefix}:{string}'
     pub fn new(prefix: impl AsRef<str>, key: impl AsRef<str>) -> Self {
         Self(format!("{}:{}", prefix.as_ref(), key.as_ref()))
@@ -92,8 +148,24 @@ impl WheelCacheKey {
             ),
         ))
     }
+
+    /// Extends the key with the PEP 517 `config_settings` that were passed to the build backend,
+    /// wheels that were built with different settings are cached separately. The key is not
+    /// changed if there are no settings.
+    pub fn with_config_settings(self, config_settings: &HashMap<String, Vec<String>>) -> Self {
+        if config_settings.is_empty() {
+            return self;
+        }
+        // Hash an unambiguous encoding of the settings with the keys in a deterministic order
+        let settings = serde_json::to_vec(&config_settings.iter().collect::<BTreeMap<_, _>>())
+            .expect("config settings can always be serialized");
+        let hash = rattler_digest::compute_bytes_digest::<Sha256>(settings);
+        Self(format!("{}:cs{:x}", self.0, hash))
+    }
 }
 
+/// An error that can occur when accessing the [`WheelCache`]
+#[allow(missing_docs)]
 #[derive(Debug, thiserror::Error)]
 pub enum WheelCacheError {
     #[error(transparent)]
@@ -123,12 +195,212 @@ impl WheelCache {
             })
     }
 
-    /// Save wheel into cache
-    fn save_wheel(&self, wheel_contents: &mut dyn Read) -> Result<Integrity, WheelCacheError> {
+    /// List the entries in the cache, the most recently added entries come first. Entries that
+    /// can't be read are skipped.
+    pub fn entries(&self) -> Result<Vec<WheelCacheEntry>, WheelCacheError> {
+        let mut entries = cacache::index::ls(&self.path)
+            .filter_map(|index| index.ok())
+            .filter_map(|index| {
+                let key = index.key.clone();
+                match self.entry(index) {
+                    Ok(entry) => Some(entry),
+                    Err(err) => {
+                        tracing::warn!("skipping unreadable wheel cache entry {key}: {err}");
+                        None
+                    }
+                }
+            })
+            .collect_vec();
+        entries.sort_by(|a, b| b.time.cmp(&a.time));
+        Ok(entries)
+    }
+
+    /// Reads the entry of the wheel from its index metadata
+    fn entry(&self, index: cacache::Metadata) -> Result<WheelCacheEntry, WheelCacheError> {
+        let metadata: WheelKeyMetadata = serde_json::from_value(index.metadata)?;
+        let integrity =
+            Integrity::from_str(&metadata.integrity).map_err(cacache::Error::IntegrityError)?;
+        // Older entries did not record the size of the wheel
+        let size = if index.size > 0 {
+            index.size as u64
+        } else {
+            cacache::read_hash_sync(&self.path, &integrity).map_or(0, |wheel| wheel.len() as u64)
+        };
+        Ok(WheelCacheEntry {
+            key: WheelCacheKey(index.key),
+            wheel_filename: metadata.wheel_filename,
+            size,
+            time: UNIX_EPOCH + Duration::from_millis(index.time as u64),
+            integrity,
+        })
+    }
+
+    /// Locks the cache. Wheels are added and read with a shared lock, they are only removed while
+    /// holding an exclusive lock so that a wheel is never removed while another process adds a
+    /// reference to it or reads it. The lock is released when the returned file is dropped.
+    fn lock(&self, exclusive: bool) -> Result<fs::File, WheelCacheError> {
+        fs::create_dir_all(&self.path)?;
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(self.path.join("wheels.lock"))?;
+        retry_interrupted(|| {
+            if exclusive {
+                file.file().lock_exclusive()
+            } else {
+                file.file().lock_shared()
+            }
+        })?;
+        Ok(file)
+    }
+
+    /// Removes entries from the cache. The wheel of an entry is only removed if no other entry
+    /// refers to it.
+    pub fn remove(&self, entries: &[WheelCacheEntry]) -> Result<(), WheelCacheError> {
+        let _lock = self.lock(true)?;
+        for entry in entries {
+            cacache::index::delete(&self.path, &entry.key.0)?;
+        }
+
+        let referenced = cacache::index::ls(&self.path)
+            .filter_map(|index| index.ok())
+            .filter_map(|index| serde_json::from_value::<WheelKeyMetadata>(index.metadata).ok())
+            .map(|metadata| metadata.integrity)
+            .collect::<HashSet<_>>();
+        for integrity in entries.iter().map(|entry| &entry.integrity).unique() {
+            if !referenced.contains(&integrity.to_string()) {
+                cacache::remove_hash_sync(&self.path, integrity)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the wheels of a package, or only the wheels of a specific version of the package.
+    /// Returns the removed entries.
+    pub fn remove_package(
+        &self,
+        name: &NormalizedPackageName,
+        version: Option<&Version>,
+    ) -> Result<Vec<WheelCacheEntry>, WheelCacheError> {
+        let removed = self
+            .entries()?
+            .into_iter()
+            .filter(|entry| {
+                NormalizedPackageName::from(entry.wheel_filename.distribution.clone()) == *name
+                    && version.map_or(true, |version| entry.wheel_filename.version == *version)
+            })
+            .collect_vec();
+        self.remove(&removed)?;
+        Ok(removed)
+    }
+
+    /// Removes the wheels that were added longer than `max_age` ago, and then the least recently
+    /// added wheels until the total size of the cache is at most `max_size` bytes. Returns the
+    /// removed entries.
+    pub fn prune(
+        &self,
+        max_age: Option<Duration>,
+        max_size: Option<u64>,
+    ) -> Result<Vec<WheelCacheEntry>, WheelCacheError> {
+        let now = SystemTime::now();
+        let mut total_size = 0;
+        let mut kept = HashSet::new();
+        let mut removed = Vec::new();
+        for entry in self.entries()? {
+            // Entries can share a wheel, its size is only counted once
+            let size = if kept.contains(&entry.integrity) {
+                0
+            } else {
+                entry.size
+            };
+            let too_old = max_age.map_or(false, |max_age| {
+                now.duration_since(entry.time).unwrap_or_default() > max_age
+            });
+            let too_large = max_size.map_or(false, |max_size| total_size + size > max_size);
+            if too_old || too_large {
+                removed.push(entry);
+            } else {
+                total_size += size;
+                kept.insert(entry.integrity.clone());
+            }
+        }
+        self.remove(&removed)?;
+        Ok(removed)
+    }
+
+    /// Verifies the integrity of the wheels in the cache and removes the entries of wheels that are
+    /// missing or corrupted. Returns the removed entries.
+    pub fn verify(&self) -> Result<Vec<WheelCacheEntry>, WheelCacheError> {
+        let _lock = self.lock(true)?;
+        let mut removed = Vec::new();
+        for (_, entries) in self
+            .entries()?
+            .into_iter()
+            .into_group_map_by(|entry| entry.integrity.to_string())
+        {
+            let integrity = &entries[0].integrity;
+            match cacache::read_hash_sync(&self.path, integrity) {
+                Err(err) if is_corrupted(&err) => {
+                    tracing::warn!(
+                        "removing corrupted wheel {} from the cache: {err}",
+                        entries[0].wheel_filename
+                    );
+                    self.remove_corrupted(entries.iter().map(|entry| &entry.key), integrity)?;
+                    removed.extend(entries);
+                }
+                result => {
+                    result?;
+                }
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Removes the index entries of a wheel that is corrupted, and the wheel itself if it exists.
+    /// The caller must hold an exclusive lock, see [`Self::lock`].
+    fn remove_corrupted<'k>(
+        &self,
+        keys: impl IntoIterator<Item = &'k WheelCacheKey>,
+        integrity: &Integrity,
+    ) -> Result<(), WheelCacheError> {
+        for key in keys {
+            cacache::index::delete(&self.path, &key.0)?;
+        }
+        // The wheel might not exist at all
+        let _ = cacache::remove_hash_sync(&self.path, integrity);
+        Ok(())
+    }
+
+    /// Writes the wheels in the cache to `dir`. If there are multiple wheels with the same
+    /// filename the most recently added one is written. Returns the paths of the written wheels.
+    pub fn export(&self, dir: &Path) -> Result<Vec<PathBuf>, WheelCacheError> {
+        fs::create_dir_all(dir)?;
+        let mut exported = Vec::new();
+        for entry in self
+            .entries()?
+            .into_iter()
+            .unique_by(|entry| entry.wheel_filename.to_string())
+        {
+            let path = dir.join(entry.wheel_filename.to_string());
+            fs::write(
+                &path,
+                cacache::read_hash_sync(&self.path, &entry.integrity)?,
+            )?;
+            exported.push(path);
+        }
+        Ok(exported)
+    }
+
+    /// Save wheel into cache, returns the integrity and size of the wheel
+    fn save_wheel(
+        &self,
+        wheel_contents: &mut dyn Read,
+    ) -> Result<(Integrity, u64), WheelCacheError> {
         // Write the wheel to the cache
         let mut writer = WriteOpts::new().open_hash_sync(&self.path)?;
-        std::io::copy(wheel_contents, &mut writer)?;
-        Ok(writer.commit()?)
+        let size = std::io::copy(wheel_contents, &mut writer)?;
+        Ok((writer.commit()?, size))
     }
 
     /// Associate wheel with cache key
@@ -139,7 +411,8 @@ impl WheelCache {
         wheel: &mut dyn Read,
     ) -> Result<(), WheelCacheError> {
         // Save the wheel to the cache
-        let wheel_integrity = self.save_wheel(wheel)?;
+        let _lock = self.lock(false)?;
+        let (wheel_integrity, size) = self.save_wheel(wheel)?;
         let metadata = serde_json::to_value(WheelKeyMetadata {
             wheel_filename: wheel_name,
             integrity: wheel_integrity.to_string(),
@@ -151,6 +424,7 @@ impl WheelCache {
             WriteOpts::new()
                 // This is just so the index entry is loadable.
                 .integrity("sha256-deadbeef".parse().unwrap())
+                .size(size as usize)
                 .metadata(metadata),
         )?;
 
@@ -163,6 +437,7 @@ impl WheelCache {
         wheel_key: &WheelCacheKey,
     ) -> Result<Option<Wheel>, WheelCacheError> {
         // Find metadata for the key
+        let lock = self.lock(false)?;
         let metadata = cacache::index::find(&self.path, &wheel_key.0)?;
 
         if let Some(metadata) = metadata {
@@ -171,8 +446,21 @@ impl WheelCache {
             let integrity =
                 Integrity::from_str(&value.integrity).map_err(cacache::Error::IntegrityError)?;
 
-            // Find wheel associated with integrity
-            let bytes = Cursor::new(cacache::read_hash_sync(&self.path, &integrity)?);
+            // Find wheel associated with integrity, a corrupted wheel is removed so it can be rebuilt
+            let bytes = match cacache::read_hash_sync(&self.path, &integrity) {
+                Ok(bytes) => Cursor::new(bytes),
+                Err(err) if is_corrupted(&err) => {
+                    tracing::warn!(
+                        "removing corrupted wheel {} from the cache: {err}",
+                        value.wheel_filename
+                    );
+                    drop(lock);
+                    let _lock = self.lock(true)?;
+                    self.remove_corrupted([wheel_key], &integrity)?;
+                    return Ok(None);
+                }
+                Err(err) => return Err(err.into()),
+            };
             let wheel = Wheel::from_bytes(value.wheel_filename, Box::new(bytes));
 
             // Need to do this to get out of miette::Result
@@ -187,10 +475,20 @@ impl WheelCache {
     }
 }
 
+/// Returns true if reading a wheel failed because it is missing or its content is corrupted.
+fn is_corrupted(err: &cacache::Error) -> bool {
+    match err {
+        cacache::Error::IntegrityError(_) | cacache::Error::SizeMismatch(..) => true,
+        cacache::Error::IoError(err, _) => err.kind() == std::io::ErrorKind::NotFound,
+        _ => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::types::WheelFilename;
     use crate::wheel_builder::wheel_cache::WheelCache;
+    use std::collections::HashMap;
     use std::path::Path;
 
     #[test]
@@ -204,6 +502,39 @@ mod tests {
         "###);
     }
 
+    #[test]
+    pub fn test_key_with_config_settings() {
+        let key = super::WheelCacheKey::new("sdist", "abc:v3.11");
+        let unchanged = super::WheelCacheKey::new("sdist", "abc:v3.11")
+            .with_config_settings(&HashMap::default());
+        assert_eq!(unchanged.0, key.0);
+
+        let settings = HashMap::from([
+            ("cmake.build-type".to_string(), vec!["Debug".to_string()]),
+            (
+                "setup-args".to_string(),
+                vec!["-Da=1".to_string(), "-Db=2".to_string()],
+            ),
+        ]);
+        let a = super::WheelCacheKey::new("sdist", "abc:v3.11").with_config_settings(&settings);
+        let b = super::WheelCacheKey::new("sdist", "abc:v3.11").with_config_settings(&settings);
+        assert_eq!(a.0, b.0);
+        assert_ne!(a.0, key.0);
+        assert_eq!(a.to_string(), key.to_string());
+
+        // Values that contain the separators must not produce the same key
+        let joined = HashMap::from([("a".to_string(), vec!["b,c".to_string()])]);
+        let separate = HashMap::from([("a".to_string(), vec!["b".to_string(), "c".to_string()])]);
+        assert_ne!(
+            super::WheelCacheKey::new("sdist", "abc:v3.11")
+                .with_config_settings(&joined)
+                .0,
+            super::WheelCacheKey::new("sdist", "abc:v3.11")
+                .with_config_settings(&separate)
+                .0
+        );
+    }
+
     #[test]
     pub fn save_retrieve_wheel() {
         let cache = WheelCache::new(tempfile::tempdir().unwrap().into_path());
@@ -232,4 +563,226 @@ mod tests {
 
         assert_eq!(cache.wheels().count(), 1);
     }
+
+    /// Adds the wheel in the test-data directory to the cache under the given key
+    fn add_wheel(cache: &WheelCache, filename: &str, name: &str, key: &super::WheelCacheKey) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../test-data/wheels")
+            .join(filename);
+        let wheel_filename =
+            WheelFilename::from_filename(filename, &name.parse().unwrap()).unwrap();
+        let wheel = fs_err::File::open(path).unwrap();
+        cache
+            .associate_wheel(key, wheel_filename, &mut std::io::BufReader::new(wheel))
+            .unwrap();
+    }
+
+    #[test]
+    pub fn test_key_info() {
+        let key = super::WheelCacheKey::new("sdist", "abc:v3.11");
+        assert_eq!(key.sdist_hash(), Some("abc"));
+        assert_eq!(key.python_version(), Some("3.11"));
+        assert!(!key.has_config_settings());
+
+        let settings = HashMap::from([("a".to_string(), vec!["b".to_string()])]);
+        assert!(key.with_config_settings(&settings).has_config_settings());
+
+        let key = super::WheelCacheKey::from_bytes("bla", "foo");
+        assert_eq!(key.sdist_hash(), None);
+        assert_eq!(key.python_version(), None);
+    }
+
+    #[test]
+    pub fn remove_and_prune_wheels() {
+        let cache = WheelCache::new(tempfile::tempdir().unwrap().into_path());
+        let wordle = "wordle_python-2.3.32-py3-none-any.whl";
+        add_wheel(
+            &cache,
+            wordle,
+            "wordle_python",
+            &super::WheelCacheKey::new("sdist", "a:v3.11"),
+        );
+        add_wheel(
+            &cache,
+            wordle,
+            "wordle_python",
+            &super::WheelCacheKey::new("sdist", "a:v3.12"),
+        );
+        add_wheel(
+            &cache,
+            "miniblack-23.1.0-py3-none-any.whl",
+            "miniblack",
+            &super::WheelCacheKey::new("sdist", "b:v3.11"),
+        );
+
+        let entries = cache.entries().unwrap();
+        assert_eq!(entries.len(), 3);
+        assert!(entries.iter().all(|entry| entry.size > 0));
+
+        // Both wordle entries refer to the same wheel, which is kept as long as one is left
+        let wordle_entries = entries
+            .iter()
+            .filter(|entry| entry.key.python_version() == Some("3.12"))
+            .cloned()
+            .collect::<Vec<_>>();
+        cache.remove(&wordle_entries).unwrap();
+        assert_eq!(cache.entries().unwrap().len(), 2);
+        cache
+            .wheel_for_key(&super::WheelCacheKey::new("sdist", "a:v3.11"))
+            .unwrap()
+            .unwrap();
+
+        let removed = cache
+            .remove_package(&"wordle-python".parse().unwrap(), None)
+            .unwrap();
+        assert_eq!(removed.len(), 1);
+        let entries = cache.entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].wheel_filename.distribution.as_str(), "miniblack");
+
+        // Nothing is old enough to be removed, but the cache is too large
+        assert!(cache
+            .prune(Some(std::time::Duration::from_secs(3600)), None)
+            .unwrap()
+            .is_empty());
+        assert_eq!(cache.prune(None, Some(1)).unwrap().len(), 1);
+        assert!(cache.entries().unwrap().is_empty());
+    }
+
+    #[test]
+    pub fn prune_counts_shared_wheels_once() {
+        let cache = WheelCache::new(tempfile::tempdir().unwrap().into_path());
+        let wordle = "wordle_python-2.3.32-py3-none-any.whl";
+        for key in ["a:v3.11", "a:v3.12"] {
+            add_wheel(
+                &cache,
+                wordle,
+                "wordle_python",
+                &super::WheelCacheKey::new("sdist", key),
+            );
+        }
+        // An entry that can't be read does not prevent listing the other entries
+        cacache::index::insert(
+            &cache.path,
+            "sdist:c:v3.11",
+            cacache::WriteOpts::new()
+                .integrity("sha256-deadbeef".parse().unwrap())
+                .metadata(serde_json::json!({ "unexpected": true })),
+        )
+        .unwrap();
+
+        let entries = cache.entries().unwrap();
+        assert_eq!(entries.len(), 2);
+
+        // Both entries fit because they share the wheel
+        assert!(cache.prune(None, Some(entries[0].size)).unwrap().is_empty());
+        assert_eq!(cache.entries().unwrap().len(), 2);
+    }
+
+    #[test]
+    pub fn export_wheels() {
+        let cache = WheelCache::new(tempfile::tempdir().unwrap().into_path());
+        let wordle = "wordle_python-2.3.32-py3-none-any.whl";
+        add_wheel(
+            &cache,
+            wordle,
+            "wordle_python",
+            &super::WheelCacheKey::new("sdist", "a:v3.11"),
+        );
+        add_wheel(
+            &cache,
+            wordle,
+            "wordle_python",
+            &super::WheelCacheKey::new("sdist", "a:v3.12"),
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let exported = cache.export(dir.path()).unwrap();
+        assert_eq!(exported, vec![dir.path().join(wordle)]);
+        assert_eq!(
+            fs_err::read(&exported[0]).unwrap(),
+            fs_err::read(
+                Path::new(env!("CARGO_MANIFEST_DIR"))
+                    .join("../../test-data/wheels")
+                    .join(wordle)
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    pub fn verify_removes_corrupted_wheels() {
+        let cache = WheelCache::new(tempfile::tempdir().unwrap().into_path());
+        let wordle_key = super::WheelCacheKey::new("sdist", "a:v3.11");
+        let miniblack_key = super::WheelCacheKey::new("sdist", "b:v3.11");
+        add_wheel(
+            &cache,
+            "wordle_python-2.3.32-py3-none-any.whl",
+            "wordle_python",
+            &wordle_key,
+        );
+        add_wheel(
+            &cache,
+            "miniblack-23.1.0-py3-none-any.whl",
+            "miniblack",
+            &miniblack_key,
+        );
+        assert!(cache.verify().unwrap().is_empty());
+
+        // Truncate the content of the wordle wheel
+        let wordle = cache
+            .entries()
+            .unwrap()
+            .into_iter()
+            .find(|entry| entry.key == wordle_key)
+            .unwrap();
+        let content = walkdir(&cache.path.join("content-v2"))
+            .into_iter()
+            .find(|path| fs_err::read(path).unwrap().len() as u64 == wordle.size)
+            .unwrap();
+        fs_err::OpenOptions::new()
+            .write(true)
+            .open(content)
+            .unwrap()
+            .set_len(10)
+            .unwrap();
+
+        let removed = cache.verify().unwrap();
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].key, wordle_key);
+        assert!(cache.wheel_for_key(&wordle_key).unwrap().is_none());
+        assert!(cache.wheel_for_key(&miniblack_key).unwrap().is_some());
+    }
+
+    #[test]
+    pub fn corrupted_wheel_is_not_returned() {
+        let cache = WheelCache::new(tempfile::tempdir().unwrap().into_path());
+        let key = super::WheelCacheKey::new("sdist", "a:v3.11");
+        add_wheel(
+            &cache,
+            "miniblack-23.1.0-py3-none-any.whl",
+            "miniblack",
+            &key,
+        );
+        for path in walkdir(&cache.path.join("content-v2")) {
+            fs_err::write(path, b"corrupted").unwrap();
+        }
+
+        assert!(cache.wheel_for_key(&key).unwrap().is_none());
+        assert!(cache.entries().unwrap().is_empty());
+    }
+
+    /// Returns all the files in a directory and its subdirectories
+    fn walkdir(dir: &Path) -> Vec<std::path::PathBuf> {
+        let mut files = Vec::new();
+        for entry in fs_err::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                files.extend(walkdir(&path));
+            } else {
+                files.push(path);
+            }
+        }
+        files
+    }
 }
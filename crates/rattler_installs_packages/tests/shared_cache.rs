@@ -0,0 +1,216 @@
+//! Tests that multiple processes can share a cache directory. The tests spawn copies of this test
+//! binary that only run the [`worker`] test, all against the same cache directory.
+
+use rattler_installs_packages::artifacts::{SDist, STree};
+use rattler_installs_packages::index::PackageDb;
+use rattler_installs_packages::python_env::{Pep508EnvMakers, PythonLocation};
+use rattler_installs_packages::resolve::solve_options::{BuildIsolation, ResolveOptions};
+use rattler_installs_packages::types::{ArtifactFromSource, STreeFilename};
+use rattler_installs_packages::wheel_builder::WheelBuilder;
+use reqwest::Client;
+use reqwest_middleware::ClientWithMiddleware;
+use rstest::rstest;
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+use std::sync::Arc;
+
+/// The directory with the test setup, the worker test does nothing if this is not set
+const WORKER_DIR_ENV: &str = "RIP_SHARED_CACHE_WORKER_DIR";
+
+/// What the worker should do, either `wheel` or `metadata`
+const WORKER_TASK_ENV: &str = "RIP_SHARED_CACHE_WORKER_TASK";
+
+/// What the worker should build from, either `stree` or `sdist`
+const WORKER_SOURCE_ENV: &str = "RIP_SHARED_CACHE_WORKER_SOURCE";
+
+const PYPROJECT_TOML: &str = "[build-system]\nrequires = []\nbuild-backend = \"shared_backend\"\n";
+
+/// A build backend that records every call in a file and takes a while to finish, so that the
+/// processes overlap.
+const BACKEND: &str = r#"import os, time, zipfile
+
+def record(call):
+    with open(os.path.join(os.path.dirname(__file__), "calls"), "a") as f:
+        f.write(call + "\n")
+    time.sleep(1)
+
+METADATA = "Metadata-Version: 2.1\nName: shared\nVersion: 1.0\n"
+
+def prepare_metadata_for_build_wheel(metadata_directory, config_settings=None):
+    record("metadata")
+    os.mkdir(os.path.join(metadata_directory, "shared-1.0.dist-info"))
+    with open(os.path.join(metadata_directory, "shared-1.0.dist-info", "METADATA"), "w") as f:
+        f.write(METADATA)
+    return "shared-1.0.dist-info"
+
+def build_wheel(wheel_directory, config_settings=None, metadata_directory=None):
+    record("wheel")
+    name = "shared-1.0-py3-none-any.whl"
+    with zipfile.ZipFile(os.path.join(wheel_directory, name), "w") as z:
+        z.writestr("shared/__init__.py", "")
+        z.writestr("shared-1.0.dist-info/METADATA", METADATA)
+        z.writestr(
+            "shared-1.0.dist-info/WHEEL",
+            "Wheel-Version: 1.0\nGenerator: test\nRoot-Is-Purelib: true\nTag: py3-none-any\n",
+        )
+        z.writestr("shared-1.0.dist-info/RECORD", "")
+    return name
+"#;
+
+/// Creates the source tree, an sdist of it, the build backend and the cache directory in `dir`.
+fn create_test_setup(dir: &Path) {
+    fs_err::create_dir_all(dir.join("source")).unwrap();
+    fs_err::write(dir.join("source/pyproject.toml"), PYPROJECT_TOML).unwrap();
+
+    let mut archive = tar::Builder::new(flate2::write::GzEncoder::new(
+        Vec::new(),
+        flate2::Compression::default(),
+    ));
+    let mut header = tar::Header::new_gnu();
+    header.set_size(PYPROJECT_TOML.len() as u64);
+    header.set_mode(0o644);
+    archive
+        .append_data(
+            &mut header,
+            "shared-1.0/pyproject.toml",
+            PYPROJECT_TOML.as_bytes(),
+        )
+        .unwrap();
+    let sdist = archive.into_inner().unwrap().finish().unwrap();
+    fs_err::write(dir.join("shared-1.0.tar.gz"), sdist).unwrap();
+
+    fs_err::create_dir_all(dir.join("backend")).unwrap();
+    fs_err::write(dir.join("backend/shared_backend.py"), BACKEND).unwrap();
+    fs_err::create_dir_all(dir.join("cache")).unwrap();
+}
+
+/// Runs `count` worker processes at the same time and waits until they are all finished.
+fn run_workers(dir: &Path, source: &str, task: &str, count: usize) {
+    let children = (0..count)
+        .map(|_| {
+            Command::new(std::env::current_exe().unwrap())
+                .args(["worker", "--exact", "--nocapture"])
+                .env(WORKER_DIR_ENV, dir)
+                .env(WORKER_TASK_ENV, task)
+                .env(WORKER_SOURCE_ENV, source)
+                .spawn()
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+    for mut child in children {
+        assert!(child.wait().unwrap().success(), "a worker process failed");
+    }
+}
+
+/// Returns the calls that were made to the build backend
+fn backend_calls(dir: &Path) -> Vec<String> {
+    fs_err::read_to_string(dir.join("backend/calls"))
+        .unwrap()
+        .lines()
+        .map(ToString::to_string)
+        .collect()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn worker() {
+    let Some(dir) = std::env::var_os(WORKER_DIR_ENV) else {
+        return;
+    };
+    let dir = Path::new(&dir);
+    let task = std::env::var(WORKER_TASK_ENV).unwrap();
+    let source_kind = std::env::var(WORKER_SOURCE_ENV).unwrap();
+
+    let package_db = Arc::new(
+        PackageDb::new(
+            "https://pypi.org/simple/"
+                .parse::<url::Url>()
+                .unwrap()
+                .into(),
+            ClientWithMiddleware::from(Client::new()),
+            &dir.join("cache"),
+        )
+        .unwrap(),
+    );
+    let env_markers = Arc::new(Pep508EnvMakers::from_env().await.unwrap().0);
+    let python = PythonLocation::System.executable().unwrap();
+    let wheel_builder = WheelBuilder::new(
+        package_db,
+        env_markers,
+        None,
+        ResolveOptions {
+            build_isolation: BuildIsolation::NoIsolation(python),
+            ..Default::default()
+        },
+        HashMap::from([(
+            "PYTHONPATH".to_string(),
+            dir.join("backend").to_string_lossy().to_string(),
+        )]),
+    )
+    .unwrap();
+
+    match source_kind.as_str() {
+        "stree" => {
+            let source = dir.join("source");
+            let stree = STree {
+                name: STreeFilename {
+                    distribution: "shared".parse().unwrap(),
+                    version: "1.0".parse().unwrap(),
+                    url: url::Url::from_directory_path(&source).unwrap(),
+                },
+                location: parking_lot::Mutex::new(source),
+            };
+            run_task(&wheel_builder, &stree, &task).await;
+        }
+        "sdist" => {
+            let sdist =
+                SDist::from_path(&dir.join("shared-1.0.tar.gz"), &"shared".parse().unwrap())
+                    .unwrap();
+            run_task(&wheel_builder, &sdist, &task).await;
+        }
+        _ => unreachable!("unknown source {source_kind}"),
+    }
+}
+
+async fn run_task(wheel_builder: &WheelBuilder, source: &impl ArtifactFromSource, task: &str) {
+    match task {
+        "wheel" => {
+            wheel_builder.build_wheel(source).await.unwrap();
+        }
+        "metadata" => {
+            wheel_builder.get_sdist_metadata(source).await.unwrap();
+        }
+        _ => unreachable!("unknown task {task}"),
+    }
+}
+
+#[rstest]
+#[case("stree")]
+#[case("sdist")]
+fn concurrent_processes_build_a_wheel_once(#[case] source: &str) {
+    let dir = tempfile::tempdir().unwrap();
+    create_test_setup(dir.path());
+
+    run_workers(dir.path(), source, "wheel", 3);
+
+    assert_eq!(backend_calls(dir.path()), vec!["wheel"]);
+}
+
+#[rstest]
+#[case("stree")]
+#[case("sdist")]
+fn concurrent_processes_generate_metadata_once(#[case] source: &str) {
+    let dir = tempfile::tempdir().unwrap();
+    create_test_setup(dir.path());
+
+    run_workers(dir.path(), source, "metadata", 3);
+    assert_eq!(backend_calls(dir.path()), vec!["metadata"]);
+
+    // A built wheel is reused for its metadata as well
+    let dir = tempfile::tempdir().unwrap();
+    create_test_setup(dir.path());
+
+    run_workers(dir.path(), source, "wheel", 1);
+    run_workers(dir.path(), source, "metadata", 2);
+    assert_eq!(backend_calls(dir.path()), vec!["wheel"]);
+}
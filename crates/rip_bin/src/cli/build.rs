@@ -0,0 +1,203 @@
+use clap::Parser;
+use fs_err as fs;
+use miette::{Context, IntoDiagnostic};
+use rattler_installs_packages::artifacts::{STree, Wheel};
+use rattler_installs_packages::index::PackageDb;
+use rattler_installs_packages::python_env::{Pep508EnvMakers, PythonLocation, WheelTags};
+use rattler_installs_packages::requirements::project_name_from_source_tree;
+use rattler_installs_packages::resolve::solve_options::{
+    BuildIsolation, OnWheelBuildFailure, ResolveOptions,
+};
+use rattler_installs_packages::types::{ArtifactFromSource, STreeFilename, Version};
+use rattler_installs_packages::wheel_builder::WheelBuilder;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use url::Url;
+
+use crate::cli::resolve::{build_sandbox, parse_config_setting};
+
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+pub struct Args {
+    /// The directory that contains the source tree of the project
+    #[clap(default_value = ".")]
+    path: PathBuf,
+
+    /// Build an sdist
+    #[clap(long)]
+    sdist: bool,
+
+    /// Build a wheel, if `--sdist` is also passed the wheel is built from the source tree
+    /// directly. If neither is passed an sdist is built and the wheel is built from that sdist.
+    #[clap(long)]
+    wheel: bool,
+
+    /// The directory in which the built artifacts are stored
+    #[clap(long, short, default_value = "dist")]
+    out_dir: PathBuf,
+
+    /// Path to the python interpreter to use for creating the build environment
+    #[clap(long, short)]
+    python_interpreter: Option<PathBuf>,
+
+    /// Disable inheritance of env variables.
+    #[arg(short = 'c', long)]
+    clean_env: bool,
+
+    /// Build with the python interpreter instead of in a new virtual environment, the build
+    /// requirements must already be installed
+    #[arg(long)]
+    no_build_isolation: bool,
+
+    /// Kill a build backend hook that runs longer than the given number of seconds
+    #[arg(long, value_name = "SECONDS")]
+    build_timeout: Option<u64>,
+
+    /// Keep the environments with the build requirements of sdists in the cache directory so they
+    /// can be reused by later runs
+    #[arg(long)]
+    persist_build_envs: bool,
+
+    /// Build in a sandbox without network access and with a read-only source tree (Linux only)
+    #[arg(long)]
+    sandbox: bool,
+
+    /// Limit the CPU time of every build backend hook in the sandbox
+    #[arg(long, value_name = "SECONDS", requires = "sandbox")]
+    sandbox_cpu_limit: Option<u64>,
+
+    /// Limit the memory of the build backend in the sandbox
+    #[arg(long, value_name = "MIB", requires = "sandbox")]
+    sandbox_memory_limit: Option<u64>,
+
+    /// Pass a PEP 517 config setting to the build backend, can be specified multiple times
+    #[arg(long = "config-settings", value_name = "KEY=VALUE", value_parser = parse_config_setting)]
+    config_settings: Vec<(String, String)>,
+
+    /// Save failed build environments
+    #[arg(long)]
+    save_on_failure: bool,
+}
+
+pub async fn execute(package_db: Arc<PackageDb>, args: Args) -> miette::Result<()> {
+    let path = fs::canonicalize(&args.path).into_diagnostic()?;
+    let name = project_name_from_source_tree(&path).ok_or_else(|| {
+        miette::miette!(
+            "could not determine the name of the project in {}, expected a pyproject.toml with a [project] table or a setup.cfg with a [metadata] section",
+            path.display()
+        )
+    })?;
+    let url = Url::from_directory_path(&path)
+        .map_err(|_| miette::miette!("could not convert {} to a url", path.display()))?;
+    let mut stree = STree {
+        name: STreeFilename {
+            distribution: name,
+            version: Version::from_str("0.0.0").expect("0.0.0 version should always be parseable"),
+            url,
+        },
+        location: parking_lot::Mutex::new(path),
+    };
+
+    let python_location = match args.python_interpreter {
+        Some(ref python) => PythonLocation::Custom(python.clone()),
+        None => PythonLocation::System,
+    };
+    let python = python_location.executable().into_diagnostic()?;
+    let env_markers = Arc::new(
+        Pep508EnvMakers::from_python(&python)
+            .await
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                format!(
+                    "failed to determine environment markers (could not run Python in path: {:?})",
+                    python
+                )
+            })?
+            .0,
+    );
+    let compatible_tags = WheelTags::from_python(&python)
+        .await
+        .into_diagnostic()
+        .map(Arc::new)?;
+
+    let mut config_settings: HashMap<String, Vec<String>> = HashMap::default();
+    for (key, value) in args.config_settings {
+        config_settings.entry(key).or_default().push(value);
+    }
+
+    let resolve_opts = ResolveOptions {
+        python_location,
+        clean_env: args.clean_env,
+        build_isolation: if args.no_build_isolation {
+            BuildIsolation::NoIsolation(python)
+        } else {
+            BuildIsolation::Isolated
+        },
+        build_sandbox: build_sandbox(
+            args.sandbox,
+            args.sandbox_cpu_limit,
+            args.sandbox_memory_limit,
+        ),
+        build_timeout: args.build_timeout.map(Duration::from_secs),
+        persist_build_environments: args.persist_build_envs,
+        config_settings,
+        on_wheel_build_failure: if args.save_on_failure {
+            OnWheelBuildFailure::SaveBuildEnv
+        } else {
+            OnWheelBuildFailure::DeleteBuildEnv
+        },
+        ..Default::default()
+    };
+
+    let wheel_builder = WheelBuilder::new(
+        package_db,
+        env_markers,
+        Some(compatible_tags),
+        resolve_opts,
+        Default::default(),
+    )
+    .into_diagnostic()?;
+
+    // The version of the project is only known from its metadata
+    let (_, metadata) = wheel_builder.get_sdist_metadata(&stree).await?;
+    stree.name.version = metadata.version;
+
+    fs::create_dir_all(&args.out_dir).into_diagnostic()?;
+
+    let (build_sdist, build_wheel_from_sdist, build_wheel) = match (args.sdist, args.wheel) {
+        (false, false) => (true, true, false),
+        (sdist, wheel) => (sdist, false, wheel),
+    };
+
+    if build_sdist {
+        let sdist = wheel_builder.build_sdist(&stree).await?;
+        let sdist_path = args.out_dir.join(sdist.name.to_string());
+        fs::write(&sdist_path, sdist.try_get_bytes().into_diagnostic()?).into_diagnostic()?;
+        println!("Successfully built {}", sdist_path.display());
+
+        // Wheels are always built again, a wheel from the cache could be stale
+        if build_wheel_from_sdist {
+            let wheel = wheel_builder.build_wheel_without_cache(&sdist).await?;
+            write_wheel(wheel, &args.out_dir)?;
+        }
+    }
+
+    if build_wheel {
+        let wheel = wheel_builder.build_wheel_without_cache(&stree).await?;
+        write_wheel(wheel, &args.out_dir)?;
+    }
+
+    Ok(())
+}
+
+/// Writes a built wheel to the output directory
+fn write_wheel(wheel: Wheel, out_dir: &Path) -> miette::Result<()> {
+    let wheel_path = out_dir.join(wheel.name.to_string());
+    let mut file = fs::File::create(&wheel_path).into_diagnostic()?;
+    wheel.write_to(&mut file).into_diagnostic()?;
+    println!("Successfully built {}", wheel_path.display());
+    Ok(())
+}
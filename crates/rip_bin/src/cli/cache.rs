@@ -0,0 +1,170 @@
+use super::wheels::format_size;
+use clap::{Parser, Subcommand, ValueEnum};
+use miette::IntoDiagnostic;
+use rattler_installs_packages::index::{CacheStore, CacheUsage, PackageDb};
+use std::io::Write;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+#[command(propagate_version = true)]
+pub struct Args {
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+pub enum Commands {
+    /// Show the location of the cache and the size of the stores in it
+    Info,
+
+    /// Remove everything from the cache, or only from specific stores
+    Clean {
+        /// The stores to clean, all stores are cleaned if none are specified
+        #[arg(value_enum)]
+        stores: Vec<Store>,
+    },
+
+    /// Check the integrity of the cache and remove corrupted entries
+    Verify {
+        /// The stores to verify, all stores are verified if none are specified
+        #[arg(value_enum)]
+        stores: Vec<Store>,
+    },
+
+    /// Remove the least recently used entries from the cache
+    #[command(arg_required_else_help = true)]
+    Prune {
+        /// Remove entries that were not used for more than the given number of days
+        #[arg(long, value_name = "DAYS")]
+        older_than: Option<u64>,
+
+        /// Remove the least recently used entries until the cache is smaller than the given size
+        #[arg(long, value_name = "MIB")]
+        max_size: Option<u64>,
+    },
+}
+
+/// A store in the cache
+#[derive(Copy, Clone, ValueEnum)]
+pub enum Store {
+    /// Responses of HTTP requests, including downloaded artifacts
+    Http,
+    /// The metadata of artifacts
+    Metadata,
+    /// Locally built wheels
+    Wheels,
+    /// Environments with the build requirements of sdists
+    BuildEnvs,
+}
+
+impl From<Store> for CacheStore {
+    fn from(store: Store) -> Self {
+        match store {
+            Store::Http => CacheStore::Http,
+            Store::Metadata => CacheStore::Metadata,
+            Store::Wheels => CacheStore::LocalWheels,
+            Store::BuildEnvs => CacheStore::BuildEnvironments,
+        }
+    }
+}
+
+pub async fn execute(package_db: Arc<PackageDb>, args: Args) -> miette::Result<()> {
+    match args.command {
+        Commands::Info => cache_info(package_db),
+        Commands::Clean { stores } => {
+            let mut removed = CacheUsage::default();
+            for store in selected_stores(stores) {
+                let usage = package_db.clean_cache(store).await?;
+                removed.entries += usage.entries;
+                removed.size += usage.size;
+            }
+            print_removed(removed);
+            Ok(())
+        }
+        Commands::Verify { stores } => {
+            for store in selected_stores(stores) {
+                let checked = package_db.cache_usage(store)?;
+                let removed = package_db.verify_cache(store).await?;
+                println!(
+                    "{}: checked {} entries, removed {} corrupted entries",
+                    store.dir_name(),
+                    checked.entries,
+                    removed.entries
+                );
+            }
+            Ok(())
+        }
+        Commands::Prune {
+            older_than,
+            max_size,
+        } => {
+            let removed = package_db
+                .prune_cache(
+                    older_than.map(|days| Duration::from_secs(days * 24 * 60 * 60)),
+                    max_size.map(|mib| mib * 1024 * 1024),
+                )
+                .await?;
+            print_removed(removed);
+            Ok(())
+        }
+    }
+}
+
+/// Returns the stores that were selected on the command line, or all stores if none were selected
+fn selected_stores(stores: Vec<Store>) -> Vec<CacheStore> {
+    if stores.is_empty() {
+        CacheStore::ALL.to_vec()
+    } else {
+        stores.into_iter().map(CacheStore::from).collect()
+    }
+}
+
+fn cache_info(package_db: Arc<PackageDb>) -> miette::Result<()> {
+    println!("cache directory: {}", package_db.cache_dir().display());
+
+    let mut tabbed_stdout = tabwriter::TabWriter::new(std::io::stdout());
+    writeln!(
+        tabbed_stdout,
+        "{}\t{}\t{}",
+        console::style("Store").bold(),
+        console::style("Entries").bold(),
+        console::style("Size").bold(),
+    )
+    .into_diagnostic()?;
+
+    let mut total = CacheUsage::default();
+    for store in CacheStore::ALL {
+        let usage = package_db.cache_usage(store)?;
+        total.entries += usage.entries;
+        total.size += usage.size;
+        writeln!(
+            tabbed_stdout,
+            "{}\t{}\t{}",
+            store.dir_name(),
+            usage.entries,
+            format_size(usage.size)
+        )
+        .into_diagnostic()?;
+    }
+    writeln!(
+        tabbed_stdout,
+        "{}\t{}\t{}",
+        console::style("total").bold(),
+        total.entries,
+        format_size(total.size)
+    )
+    .into_diagnostic()?;
+
+    tabbed_stdout.flush().into_diagnostic()?;
+    Ok(())
+}
+
+fn print_removed(removed: CacheUsage) {
+    println!(
+        "removed {} entries, {}",
+        removed.entries,
+        format_size(removed.size)
+    );
+}
@@ -1,3 +1,7 @@
+pub mod build;
+
+pub mod cache;
+
 pub mod resolve;
 
 pub mod wheels;
@@ -5,17 +5,20 @@ use miette::{Context, IntoDiagnostic};
 use rattler_installs_packages::artifacts::wheel::UnpackWheelOptions;
 use rattler_installs_packages::index::PackageDb;
 use rattler_installs_packages::python_env::{Pep508EnvMakers, PythonLocation, WheelTags};
+use rattler_installs_packages::requirements::{to_requirements_txt, CollectedRequirements};
 use rattler_installs_packages::resolve::solve_options::{
-    OnWheelBuildFailure, PreReleaseResolution, ResolveOptions, SDistResolution,
+    BuildIsolation, BuildSandbox, OnWheelBuildFailure, PackageResolveOptions, PreReleaseResolution,
+    ResolveOptions, SDistResolution,
 };
-use rattler_installs_packages::resolve::PinnedPackage;
-use rattler_installs_packages::types::Requirement;
+use rattler_installs_packages::resolve::{PinnedPackage, Resolution, MAX_OMITTED_PATHS};
+use rattler_installs_packages::types::{Extra, NormalizedPackageName, Requirement};
 use rattler_installs_packages::wheel_builder::WheelBuilder;
 use serde::Serialize;
 use std::collections::HashMap;
 use std::io::Write;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Serialize, Debug)]
 struct Solution {
@@ -33,19 +36,67 @@ pub enum Commands {
     /// Resolve and install a set of requirements
     #[clap(alias = "i")]
     Install(InstallArgs),
+
+    /// Resolve a set of requirements and output the dependency graph
+    Tree(TreeArgs),
+
+    /// Resolve a set of requirements and explain why a package was selected
+    Why(WhyArgs),
 }
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 pub struct ResolveArgs {
-    #[clap(num_args = 1.., required = true)]
+    #[clap(flatten)]
+    resolution_args: ResolutionArgs,
+
+    /// Output the result as json, same as `--output-format json`
+    #[clap(long, conflicts_with = "output_format")]
+    json: bool,
+
+    /// The format in which the resolved environment is written to stdout
+    #[clap(long, value_enum, default_value_t = OutputFormat::Table)]
+    output_format: OutputFormat,
+}
+
+/// The arguments that determine how a set of requirements is resolved.
+#[derive(Parser)]
+pub struct ResolutionArgs {
+    #[clap(num_args = 1.., required_unless_present_any = ["requirement", "editable"])]
     /// The specs to resolve
     specs: Vec<Requirement>,
 
+    /// Read requirements from the given requirements.txt, pyproject.toml or setup.cfg file, can be
+    /// specified multiple times
+    #[clap(long, short, value_name = "FILE")]
+    requirement: Vec<PathBuf>,
+
+    /// Install the project in the given local directory in editable mode, can be specified
+    /// multiple times
+    #[clap(long, short, value_name = "PATH")]
+    editable: Vec<PathBuf>,
+
+    /// Constrain versions using the given requirements.txt file, can be specified multiple times
+    #[clap(long, value_name = "FILE")]
+    constraint: Vec<PathBuf>,
+
+    /// Include the optional dependencies of the given extra from pyproject.toml and setup.cfg
+    /// files, can be specified multiple times
+    #[clap(long, value_name = "EXTRA")]
+    extra: Vec<Extra>,
+
     /// How to handle SDists
     #[clap(flatten)]
     sdist_resolution: SDistResolutionArgs,
 
+    /// Only select versions with sdists for the given package, can be specified multiple times
+    #[clap(long, value_name = "PACKAGE")]
+    no_binary: Vec<NormalizedPackageName>,
+
+    /// Only select versions with wheels for the given package, can be specified multiple times
+    #[clap(long, value_name = "PACKAGE")]
+    only_binary: Vec<NormalizedPackageName>,
+
     /// Path to the python interpreter to use for resolving environment markers and creating venvs
     #[clap(long, short)]
     python_interpreter: Option<PathBuf>,
@@ -54,6 +105,38 @@ pub struct ResolveArgs {
     #[arg(short = 'c', long)]
     clean_env: bool,
 
+    /// Build sdists with the python interpreter instead of in a new virtual environment, the build
+    /// requirements must already be installed
+    #[arg(long)]
+    no_build_isolation: bool,
+
+    /// Kill a build backend hook that runs longer than the given number of seconds
+    #[arg(long, value_name = "SECONDS")]
+    build_timeout: Option<u64>,
+
+    /// Keep the environments with the build requirements of sdists in the cache directory so they
+    /// can be reused by later runs
+    #[arg(long)]
+    persist_build_envs: bool,
+
+    /// Build sdists in a sandbox without network access and with a read-only source tree (Linux
+    /// only)
+    #[arg(long)]
+    sandbox: bool,
+
+    /// Limit the CPU time of every build backend hook in the sandbox
+    #[arg(long, value_name = "SECONDS", requires = "sandbox")]
+    sandbox_cpu_limit: Option<u64>,
+
+    /// Limit the memory of the build backend in the sandbox
+    #[arg(long, value_name = "MIB", requires = "sandbox")]
+    sandbox_memory_limit: Option<u64>,
+
+    /// Pass a PEP 517 config setting to the build backend of every sdist, can be specified
+    /// multiple times
+    #[arg(long = "config-settings", value_name = "KEY=VALUE", value_parser = parse_config_setting)]
+    config_settings: Vec<(String, String)>,
+
     /// Save failed wheel build environments
     #[arg(long)]
     save_on_failure: bool,
@@ -61,14 +144,10 @@ pub struct ResolveArgs {
     /// Prefer pre-releases to normal releases
     #[clap(long)]
     pre: bool,
-
-    /// Output the result as json
-    #[clap(long)]
-    json: bool,
 }
 
 #[derive(Parser)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, allow_missing_positional = true)]
 pub struct InstallArgs {
     #[clap(flatten)]
     resolve_args: ResolveArgs,
@@ -77,6 +156,52 @@ pub struct InstallArgs {
     target: PathBuf,
 }
 
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+pub struct TreeArgs {
+    #[clap(flatten)]
+    resolution_args: ResolutionArgs,
+
+    /// The format in which the dependency graph is written to stdout
+    #[clap(long, value_enum, default_value_t = GraphFormat::Text)]
+    format: GraphFormat,
+}
+
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+pub struct WhyArgs {
+    /// The package to explain
+    package: NormalizedPackageName,
+
+    /// The maximum number of paths through which the package was required that are shown, the
+    /// number of paths can grow exponentially with the number of shared dependencies
+    #[clap(long, default_value_t = 50)]
+    max_paths: usize,
+
+    #[clap(flatten)]
+    resolution_args: ResolutionArgs,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum GraphFormat {
+    /// A tree per requested package, similar to `pipdeptree`
+    Text,
+    /// A graph in the DOT format of Graphviz
+    Dot,
+    /// A json document with all packages and the requirements between them
+    Json,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// A human readable table
+    Table,
+    /// The table followed by a json document
+    Json,
+    /// A requirements.txt file that can be installed with pip, with the hashes that are known
+    RequirementsTxt,
+}
+
 #[derive(Parser)]
 #[group(multiple = false)]
 pub struct SDistResolutionArgs {
@@ -113,11 +238,124 @@ impl From<SDistResolutionArgs> for SDistResolution {
     }
 }
 
-pub async fn execute(package_db: Arc<PackageDb>, commands: Commands) -> miette::Result<()> {
-    let (args, target) = match commands {
-        Commands::Resolve(args) => (args, None),
-        Commands::Install(args) => (args.resolve_args, Some(args.target)),
+/// Parses a `--config-settings` value of the form `key=value`.
+pub(crate) fn parse_config_setting(value: &str) -> Result<(String, String), String> {
+    value
+        .split_once('=')
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| format!("invalid config setting '{value}', expected KEY=VALUE"))
+}
+
+/// Constructs the [`BuildSandbox`] from the command line arguments.
+pub(crate) fn build_sandbox(
+    sandbox: bool,
+    cpu_limit: Option<u64>,
+    memory_limit: Option<u64>,
+) -> Option<BuildSandbox> {
+    sandbox.then(|| BuildSandbox {
+        cpu_time_limit: cpu_limit.map(Duration::from_secs),
+        memory_limit: memory_limit.map(|mib| mib * 1024 * 1024),
+    })
+}
+
+impl ResolveArgs {
+    fn output_format(&self) -> OutputFormat {
+        if self.json {
+            OutputFormat::Json
+        } else {
+            self.output_format
+        }
+    }
+}
+
+impl Commands {
+    fn resolution_args(&self) -> &ResolutionArgs {
+        match self {
+            Commands::Resolve(args) => &args.resolution_args,
+            Commands::Install(args) => &args.resolve_args.resolution_args,
+            Commands::Tree(args) => &args.resolution_args,
+            Commands::Why(args) => &args.resolution_args,
+        }
+    }
+
+    /// Collect the requirements from the command line and from the requirement and constraint
+    /// files that were passed.
+    pub fn collect_requirements(&self) -> miette::Result<CollectedRequirements> {
+        let args = self.resolution_args();
+        let mut collected = CollectedRequirements {
+            requirements: args.specs.clone(),
+            ..Default::default()
+        };
+        for path in args.requirement.iter() {
+            collected.extend(CollectedRequirements::from_path(path, &args.extra)?);
+        }
+        for path in args.editable.iter() {
+            collected.extend(CollectedRequirements::from_editable(path)?);
+        }
+        for path in args.constraint.iter() {
+            let constraints = CollectedRequirements::from_requirements_txt(path)?;
+            collected.constraints.extend(constraints.requirements);
+            collected.constraints.extend(constraints.constraints);
+        }
+
+        // Ignoring these options would contact an index that the user wanted to avoid or install
+        // packages without checking their hashes
+        if collected.no_index {
+            miette::bail!("--no-index is not supported");
+        }
+        if let Some(find_links) = collected.find_links.first() {
+            miette::bail!("--find-links is not supported (--find-links {find_links})");
+        }
+        if let Some(name) = collected.hashes.keys().sorted().next() {
+            miette::bail!("hash checking is not supported, found --hash options for {name}");
+        }
+
+        Ok(collected)
+    }
+}
+
+pub async fn execute(
+    package_db: Arc<PackageDb>,
+    commands: Commands,
+    requirements: CollectedRequirements,
+) -> miette::Result<()> {
+    // Only `resolve` and `install` write the resolved environment in the selected output format
+    let (args, output_format, target, graph_format, why) = match commands {
+        Commands::Resolve(args) => {
+            let output_format = args.output_format();
+            (args.resolution_args, output_format, None, None, None)
+        }
+        Commands::Install(args) => {
+            let output_format = args.resolve_args.output_format();
+            (
+                args.resolve_args.resolution_args,
+                output_format,
+                Some(args.target),
+                None,
+                None,
+            )
+        }
+        Commands::Tree(args) => (
+            args.resolution_args,
+            OutputFormat::Table,
+            None,
+            Some(args.format),
+            None,
+        ),
+        Commands::Why(args) => (
+            args.resolution_args,
+            OutputFormat::Table,
+            None,
+            None,
+            Some((args.package, args.max_paths)),
+        ),
     };
+    let CollectedRequirements {
+        requirements: specs,
+        constraints,
+        editables,
+        ..
+    } = requirements;
 
     // Determine the environment markers for the current machine
     let env_markers = Arc::new(match args.python_interpreter {
@@ -165,22 +403,66 @@ pub async fn execute(package_db: Arc<PackageDb>, commands: Commands) -> miette::
     let pre_release_resolution = if args.pre {
         PreReleaseResolution::Allow
     } else {
-        PreReleaseResolution::from_specs(&args.specs)
+        PreReleaseResolution::from_specs(&specs)
     };
 
+    let mut package_options: HashMap<NormalizedPackageName, PackageResolveOptions> =
+        HashMap::default();
+    for name in args.only_binary.iter() {
+        package_options
+            .entry(name.clone())
+            .or_default()
+            .sdist_resolution = Some(SDistResolution::OnlyWheels);
+    }
+    for name in args.no_binary.iter() {
+        if args.only_binary.contains(name) {
+            miette::bail!("'{name}' cannot be passed to both --no-binary and --only-binary");
+        }
+        package_options
+            .entry(name.clone())
+            .or_default()
+            .sdist_resolution = Some(SDistResolution::OnlySDists);
+    }
+
+    let build_isolation = if args.no_build_isolation {
+        BuildIsolation::NoIsolation(python_location.executable().into_diagnostic()?)
+    } else {
+        BuildIsolation::Isolated
+    };
+
+    let mut config_settings: HashMap<String, Vec<String>> = HashMap::default();
+    for (key, value) in args.config_settings.iter() {
+        config_settings
+            .entry(key.clone())
+            .or_default()
+            .push(value.clone());
+    }
+
     let resolve_opts = ResolveOptions {
         sdist_resolution: args.sdist_resolution.into(),
         python_location: python_location.clone(),
         clean_env: args.clean_env,
+        build_isolation,
+        build_sandbox: build_sandbox(
+            args.sandbox,
+            args.sandbox_cpu_limit,
+            args.sandbox_memory_limit,
+        ),
+        build_timeout: args.build_timeout.map(Duration::from_secs),
+        persist_build_environments: args.persist_build_envs,
+        config_settings,
         on_wheel_build_failure,
         pre_release_resolution,
+        package_options,
+        constraints,
+        editables,
         ..Default::default()
     };
 
     // Solve the environment
     let blueprint = match rattler_installs_packages::resolve::resolve(
         package_db.clone(),
-        &args.specs,
+        &specs,
         env_markers.clone(),
         Some(compatible_tags.clone()),
         HashMap::default(),
@@ -192,7 +474,7 @@ pub async fn execute(package_db: Arc<PackageDb>, commands: Commands) -> miette::
     {
         Ok(blueprint) => blueprint,
         Err(err) => {
-            return if args.json {
+            return if output_format == OutputFormat::Json {
                 let solution = Solution {
                     resolved: false,
                     packages: HashMap::default(),
@@ -206,12 +488,122 @@ pub async fn execute(package_db: Arc<PackageDb>, commands: Commands) -> miette::
         }
     };
 
+    // Explain why a package is part of the environment
+    if let Some((name, max_paths)) = why {
+        return print_why(&blueprint, &name, max_paths);
+    }
+
+    // Output the dependency graph
+    if let Some(graph_format) = graph_format {
+        match graph_format {
+            GraphFormat::Text => print!("{}", blueprint.to_tree()),
+            GraphFormat::Dot => print!("{}", blueprint.to_dot()),
+            GraphFormat::Json => println!("{}", blueprint.to_json()),
+        }
+        return Ok(());
+    }
+
     // Output the selected versions
+    match output_format {
+        OutputFormat::Table => print_table(&specs, &blueprint.packages)?,
+        OutputFormat::Json => {
+            print_table(&specs, &blueprint.packages)?;
+            let solution = Solution {
+                resolved: true,
+                packages: blueprint
+                    .packages
+                    .iter()
+                    .map(|p| (p.name.to_string(), p.version.to_string()))
+                    .collect(),
+                error: None,
+            };
+            println!("{}", serde_json::to_string_pretty(&solution).unwrap());
+        }
+        OutputFormat::RequirementsTxt => print!("{}", to_requirements_txt(&blueprint)),
+    }
+
+    // Install if requested
+    if let Some(target) = target {
+        let wheel_builder = WheelBuilder::new(
+            package_db.clone(),
+            env_markers,
+            Some(compatible_tags),
+            resolve_opts,
+            Default::default(),
+        )
+        .into_diagnostic()?;
+
+        install_packages(
+            package_db,
+            wheel_builder,
+            blueprint.packages,
+            python_location,
+            target,
+        )
+        .await?
+    }
+
+    Ok(())
+}
+
+/// Print the paths through which a package was required, the selected version and the versions
+/// that were rejected.
+fn print_why(
+    resolution: &Resolution,
+    name: &NormalizedPackageName,
+    max_paths: usize,
+) -> miette::Result<()> {
+    let Some(package) = resolution.package(name) else {
+        miette::bail!("'{name}' is not part of the resolved environment");
+    };
+
+    match &package.url {
+        Some(url) => println!(
+            "{} {} was selected from {url}",
+            console::style(name).bold(),
+            package.version
+        ),
+        None => println!(
+            "{} {} was selected",
+            console::style(name).bold(),
+            package.version
+        ),
+    }
+
+    println!();
+    println!("{}:", console::style("Required by").bold());
+    let paths = resolution.paths_to(name, max_paths);
+    for path in &paths.paths {
+        println!("- {}", path.iter().map(|d| format!("({d})")).join(" -> "));
+    }
+    if paths.omitted > 0 {
+        let more = if paths.omitted >= MAX_OMITTED_PATHS {
+            format!("at least {}", paths.omitted)
+        } else {
+            paths.omitted.to_string()
+        };
+        println!("- ... and {more} more paths (at most {max_paths} paths are shown, use --max-paths to show more)");
+    }
+
+    let rejected = resolution.rejected_candidates(name);
+    if !rejected.is_empty() {
+        println!();
+        println!("{}:", console::style("Rejected versions").bold());
+        for (candidate, reason) in rejected {
+            println!("- {}: {reason}", candidate.version);
+        }
+    }
+
+    Ok(())
+}
+
+/// Print the requested specs and the selected versions as a table
+fn print_table(specs: &[Requirement], pinned_packages: &[PinnedPackage]) -> miette::Result<()> {
     println!(
         "{}:",
         console::style("Successfully resolved environment").bold()
     );
-    for spec in args.specs.iter() {
+    for spec in specs.iter() {
         println!("- {}", spec);
     }
 
@@ -225,7 +617,7 @@ pub async fn execute(package_db: Arc<PackageDb>, commands: Commands) -> miette::
     )
     .into_diagnostic()?;
 
-    for pinned_package in blueprint.iter().sorted_by(|a, b| a.name.cmp(&b.name)) {
+    for pinned_package in pinned_packages.iter().sorted_by(|a, b| a.name.cmp(&b.name)) {
         write!(tabbed_stdout, "{name}", name = pinned_package.name.as_str()).into_diagnostic()?;
         if !pinned_package.extras.is_empty() {
             write!(
@@ -244,39 +636,6 @@ pub async fn execute(package_db: Arc<PackageDb>, commands: Commands) -> miette::
     }
     tabbed_stdout.flush().into_diagnostic()?;
 
-    if args.json {
-        let solution = Solution {
-            resolved: true,
-            packages: blueprint
-                .iter()
-                .map(|p| (p.name.to_string(), p.version.to_string()))
-                .collect(),
-            error: None,
-        };
-        println!("{}", serde_json::to_string_pretty(&solution).unwrap());
-    }
-
-    // Install if requested
-    if let Some(target) = target {
-        let wheel_builder = WheelBuilder::new(
-            package_db.clone(),
-            env_markers,
-            Some(compatible_tags),
-            resolve_opts,
-            Default::default(),
-        )
-        .into_diagnostic()?;
-
-        install_packages(
-            package_db,
-            wheel_builder,
-            blueprint,
-            python_location,
-            target,
-        )
-        .await?
-    }
-
     Ok(())
 }
 
@@ -325,9 +684,16 @@ pub async fn install_packages(
         //     console::style(pinned_package.version).italic()
         // );
         let artifact_info = pinned_package.artifacts.first().unwrap();
-        let (artifact, direct_url_json) = package_db
-            .get_wheel(artifact_info, Some(&wheel_builder))
-            .await?;
+        let (artifact, direct_url_json) = if pinned_package.editable {
+            let (artifact, direct_url_json) = package_db
+                .get_editable_wheel(artifact_info, &wheel_builder)
+                .await?;
+            (artifact, Some(direct_url_json))
+        } else {
+            package_db
+                .get_wheel(artifact_info, Some(&wheel_builder))
+                .await?
+        };
         venv.install_wheel(
             &artifact,
             &UnpackWheelOptions {
@@ -1,7 +1,13 @@
 use clap::{Parser, Subcommand};
+use itertools::Itertools;
 use miette::IntoDiagnostic;
 use rattler_installs_packages::index::PackageDb;
+use rattler_installs_packages::types::{NormalizedPackageName, Version};
+use rattler_installs_packages::wheel_builder::WheelCacheEntry;
+use std::io::Write;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -15,11 +21,81 @@ pub struct Args {
 pub enum Commands {
     /// List locally built wheels
     List,
+
+    /// Show the sdist and python version that locally built wheels were built from
+    Info {
+        /// Only show the wheels of this package
+        name: Option<NormalizedPackageName>,
+    },
+
+    /// Remove the locally built wheels of a package
+    Remove {
+        /// The name of the package
+        name: NormalizedPackageName,
+
+        /// Only remove the wheels of this version
+        #[arg(value_name = "VERSION")]
+        package_version: Option<Version>,
+    },
+
+    /// Remove locally built wheels that are old or exceed a size budget
+    #[command(arg_required_else_help = true)]
+    Prune {
+        /// Remove wheels that were built more than the given number of days ago
+        #[arg(long, value_name = "DAYS")]
+        older_than: Option<u64>,
+
+        /// Remove the least recently built wheels until the total size is below the given size
+        #[arg(long, value_name = "MIB")]
+        max_size: Option<u64>,
+    },
+
+    /// Copy the locally built wheels to a directory
+    Export {
+        /// The directory to copy the wheels to
+        dir: PathBuf,
+    },
 }
 
 pub fn wheels(package_db: Arc<PackageDb>, args: Args) -> miette::Result<()> {
     match args.command {
         Commands::List => list_wheels(package_db),
+        Commands::Info { name } => wheel_info(package_db, name),
+        Commands::Remove {
+            name,
+            package_version,
+        } => {
+            let removed = package_db
+                .local_wheel_cache()
+                .remove_package(&name, package_version.as_ref())
+                .into_diagnostic()?;
+            print_removed(&removed);
+            Ok(())
+        }
+        Commands::Prune {
+            older_than,
+            max_size,
+        } => {
+            let removed = package_db
+                .local_wheel_cache()
+                .prune(
+                    older_than.map(|days| Duration::from_secs(days * 24 * 60 * 60)),
+                    max_size.map(|mib| mib * 1024 * 1024),
+                )
+                .into_diagnostic()?;
+            print_removed(&removed);
+            Ok(())
+        }
+        Commands::Export { dir } => {
+            let exported = package_db
+                .local_wheel_cache()
+                .export(&dir)
+                .into_diagnostic()?;
+            for path in exported {
+                println!("{}", path.display());
+            }
+            Ok(())
+        }
     }
 }
 
@@ -31,3 +107,82 @@ fn list_wheels(package_db: Arc<PackageDb>) -> miette::Result<()> {
 
     Ok(())
 }
+
+fn wheel_info(
+    package_db: Arc<PackageDb>,
+    name: Option<NormalizedPackageName>,
+) -> miette::Result<()> {
+    let entries = package_db.local_wheel_cache().entries().into_diagnostic()?;
+
+    let mut tabbed_stdout = tabwriter::TabWriter::new(std::io::stdout());
+    writeln!(
+        tabbed_stdout,
+        "{}\t{}\t{}\t{}\t{}",
+        console::style("Wheel").bold(),
+        console::style("Sdist hash").bold(),
+        console::style("Python").bold(),
+        console::style("Size").bold(),
+        console::style("Built").bold(),
+    )
+    .into_diagnostic()?;
+
+    for entry in entries
+        .iter()
+        .filter(|entry| {
+            name.as_ref().map_or(true, |name| {
+                NormalizedPackageName::from(entry.wheel_filename.distribution.clone()) == *name
+            })
+        })
+        .sorted_by_key(|entry| entry.wheel_filename.to_string())
+    {
+        let python = entry.key.python_version().unwrap_or("-");
+        writeln!(
+            tabbed_stdout,
+            "{}\t{}\t{}{}\t{}\t{}",
+            entry.wheel_filename,
+            entry.key.sdist_hash().unwrap_or("-"),
+            python,
+            if entry.key.has_config_settings() {
+                " (with config settings)"
+            } else {
+                ""
+            },
+            format_size(entry.size),
+            format_age(entry.time),
+        )
+        .into_diagnostic()?;
+    }
+
+    tabbed_stdout.flush().into_diagnostic()?;
+    Ok(())
+}
+
+fn print_removed(removed: &[WheelCacheEntry]) {
+    for entry in removed {
+        println!("removed {}", entry.wheel_filename);
+    }
+    let size: u64 = removed.iter().map(|entry| entry.size).sum();
+    println!("removed {} wheel(s), {}", removed.len(), format_size(size));
+}
+
+/// Formats a size in bytes, e.g. `1.5 MiB`
+pub(crate) fn format_size(size: u64) -> String {
+    if size < 1024 * 1024 {
+        format!("{:.1} KiB", size as f64 / 1024.0)
+    } else {
+        format!("{:.1} MiB", size as f64 / (1024.0 * 1024.0))
+    }
+}
+
+/// Formats the time that passed since `time`, e.g. `3 days ago`
+fn format_age(time: SystemTime) -> String {
+    let age = SystemTime::now()
+        .duration_since(time)
+        .unwrap_or_default()
+        .as_secs();
+    match age {
+        age if age < 60 * 60 => format!("{} minutes ago", age / 60),
+        age if age < 24 * 60 * 60 => format!("{} hours ago", age / (60 * 60)),
+        age => format!("{} days ago", age / (24 * 60 * 60)),
+    }
+}
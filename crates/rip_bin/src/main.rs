@@ -8,15 +8,18 @@ use miette::Context;
 use tracing_subscriber::filter::Directive;
 use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
-use rattler_installs_packages::index::PackageSourcesBuilder;
-
+use rattler_installs_packages::index::RetryPolicy;
 use rattler_installs_packages::normalize_index_url;
+use rattler_installs_packages::requirements::CollectedRequirements;
 use reqwest::Client;
 use reqwest_middleware::ClientWithMiddleware;
 use rip_bin::cli::wheels::wheels;
 use tracing::metadata::LevelFilter;
 use url::Url;
 
+/// The index that is used if no other index is specified.
+const DEFAULT_INDEX_URL: &str = "https://pypi.org/simple/";
+
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 #[command(propagate_version = true)]
@@ -30,9 +33,25 @@ struct Cli {
     verbose: clap_verbosity_flag::Verbosity,
 
     /// Base URL of the Python Package Index (default <https://pypi.org/simple>). This should point
-    /// to a repository compliant with PEP 503 (the simple repository API).
-    #[clap(default_value = "https://pypi.org/simple/", long, global = true)]
-    index_url: Url,
+    /// to a repository compliant with PEP 503 (the simple repository API). Overrides any
+    /// `--index-url` found in requirement files.
+    #[clap(long, global = true)]
+    index_url: Option<Url>,
+
+    /// Download artifacts whose URL starts with PREFIX from a URL that starts with REPLACEMENT
+    /// instead, e.g. `https://files.pythonhosted.org/=https://proxy.example.com/pypi/`. Can be
+    /// specified multiple times.
+    #[clap(long, global = true, value_name = "PREFIX=REPLACEMENT", value_parser = parse_url_rewrite)]
+    url_rewrite: Vec<(Url, Url)>,
+
+    /// Remove the least recently used entries from the cache after running the command until the
+    /// cache is smaller than the given size
+    #[clap(long, global = true, value_name = "MIB")]
+    max_cache_size: Option<u64>,
+
+    /// The maximum number of times a failed HTTP request is retried
+    #[clap(long, global = true, default_value_t = RetryPolicy::default().max_retries)]
+    retries: u32,
 }
 
 #[derive(Subcommand)]
@@ -40,10 +59,32 @@ enum Commands {
     /// Options w.r.t locally built wheels
     Wheels(cli::wheels::Args),
 
+    /// Inspect and clean the cache
+    Cache(cli::cache::Args),
+
+    /// Build an sdist and/or a wheel from a local source tree
+    Build(cli::build::Args),
+
     #[command(flatten)]
     InstallOrResolve(cli::resolve::Commands),
 }
 
+/// Parses a `PREFIX=REPLACEMENT` url rewrite rule. Both urls can contain `=`, the rule is split at
+/// the first `=` for which both sides are valid urls.
+fn parse_url_rewrite(rule: &str) -> Result<(Url, Url), String> {
+    let parse = |url: &str| Url::parse(url).map_err(|err| format!("invalid url '{url}': {err}"));
+    let mut first_error = None;
+    for (index, _) in rule.match_indices('=') {
+        match parse(&rule[..index]).and_then(|prefix| Ok((prefix, parse(&rule[index + 1..])?))) {
+            Ok(rewrite) => return Ok(rewrite),
+            Err(err) => {
+                first_error.get_or_insert(err);
+            }
+        }
+    }
+    Err(first_error.unwrap_or_else(|| String::from("expected PREFIX=REPLACEMENT")))
+}
+
 async fn actual_main() -> miette::Result<()> {
     let args = Cli::parse();
 
@@ -62,9 +103,31 @@ async fn actual_main() -> miette::Result<()> {
         .join("rattler/pypi");
     tracing::info!("cache directory: {}", cache_dir.display());
 
+    // Collect the requirements, requirement files can also specify the indexes to use
+    let mut requirements = match &args.command {
+        Commands::InstallOrResolve(cmds) => cmds.collect_requirements()?,
+        Commands::Wheels(_) | Commands::Cache(_) | Commands::Build(_) => {
+            CollectedRequirements::default()
+        }
+    };
+    if let Some(index_url) = args.index_url {
+        requirements.index_url = Some(index_url);
+    }
+
     // Construct a package database
-    let index_url = normalize_index_url(args.index_url.clone());
-    let sources = PackageSourcesBuilder::new(index_url).build()?;
+    let index_url = normalize_index_url(requirements.index_url.take().unwrap_or_else(|| {
+        DEFAULT_INDEX_URL
+            .parse()
+            .expect("invalid default index url")
+    }));
+    let sources = args
+        .url_rewrite
+        .iter()
+        .fold(
+            requirements.package_sources_builder(index_url.clone()),
+            |builder, (prefix, replacement)| builder.with_url_rewrite(prefix, replacement),
+        )
+        .build()?;
 
     let client = ClientWithMiddleware::from(Client::new());
     let package_db = Arc::new(
@@ -72,15 +135,38 @@ async fn actual_main() -> miette::Result<()> {
             .wrap_err_with(|| {
                 format!(
                     "failed to construct package database for index {}",
-                    args.index_url
+                    index_url
                 )
-            })?,
+            })?
+            .with_retry_policy(RetryPolicy {
+                max_retries: args.retries,
+                ..Default::default()
+            }),
     );
 
     match args.command {
-        Commands::InstallOrResolve(cmds) => cli::resolve::execute(package_db.clone(), cmds).await,
-        Commands::Wheels(args) => wheels(package_db.clone(), args),
+        Commands::InstallOrResolve(cmds) => {
+            cli::resolve::execute(package_db.clone(), cmds, requirements).await?
+        }
+        Commands::Wheels(args) => wheels(package_db.clone(), args)?,
+        Commands::Cache(args) => cli::cache::execute(package_db.clone(), args).await?,
+        Commands::Build(args) => cli::build::execute(package_db.clone(), args).await?,
     }
+
+    if let Some(max_cache_size) = args.max_cache_size {
+        let removed = package_db
+            .prune_cache(None, Some(max_cache_size * 1024 * 1024))
+            .await?;
+        if removed.entries > 0 {
+            tracing::info!(
+                "removed {} entries from the cache to stay within {} MiB",
+                removed.entries,
+                max_cache_size
+            );
+        }
+    }
+
+    Ok(())
 }
 
 #[tokio::main]